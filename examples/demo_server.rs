@@ -57,6 +57,9 @@ impl PromptDefinition for GreetingPrompt {
             name: "name".to_string(),
             description: "Who to greet".to_string(),
             required: false,
+            schema: None,
+            default: None,
+            max_size: None,
         }]
     }
 }
@@ -71,6 +74,7 @@ impl McpPromptService for GreetingPrompt {
         Ok(PromptExecutionResult {
             description: "A greeting".to_string(),
             message: format!("Say hello to {}!", name),
+            ..Default::default()
         })
     }
 }
@@ -86,14 +90,13 @@ impl ResourceDefinition for StaticGreetingResource {
 
 #[async_trait::async_trait]
 impl McpResourceService for StaticGreetingResource {
-    async fn read_resource(&self) -> Result<ResourceReadResult, String> {
+    async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
         Ok(ResourceReadResult {
-            contents: vec![ResourceContent {
-                uri: Self::RESOURCE_URI.to_string(),
-                mime_type: Self::MIME_TYPE.to_string(),
-                text: Some("Hello from the static resource!".to_string()),
-                blob: None,
-            }],
+            contents: vec![ResourceContent::text(
+                uri,
+                Self::MIME_TYPE,
+                "Hello from the static resource!",
+            )],
         })
     }
 }
@@ -104,15 +107,14 @@ pub struct DynamicClockResource;
 
 #[async_trait::async_trait]
 impl McpResourceService for DynamicClockResource {
-    async fn read_resource(&self) -> Result<ResourceReadResult, String> {
+    async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
         let now = rust_extensions::date_time::DateTimeAsMicroseconds::now();
         Ok(ResourceReadResult {
-            contents: vec![ResourceContent {
-                uri: DYNAMIC_RESOURCE_URI.to_string(),
-                mime_type: "text/plain".to_string(),
-                text: Some(format!("Server time: {}", now.to_rfc3339())),
-                blob: None,
-            }],
+            contents: vec![ResourceContent::text(
+                uri,
+                "text/plain",
+                format!("Server time: {}", now.to_rfc3339()),
+            )],
         })
     }
 }
@@ -237,4 +239,7 @@ async fn main() {
 
     println!("MCP demo server listening on http://localhost:8081/mcp");
     tokio::signal::ctrl_c().await.unwrap();
+
+    let notified = mcp.notify_shutdown("demo server is stopping").await;
+    println!("Told {} live stream(s) about the shutdown", notified);
 }
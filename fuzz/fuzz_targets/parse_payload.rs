@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mcp_server_middleware::McpInputPayload;
+
+// Any panic is a crash: libfuzzer-sys aborts on it before it unwinds.
+fuzz_target!(|data: &[u8]| {
+    let _ = McpInputPayload::parse_untrusted(data);
+});
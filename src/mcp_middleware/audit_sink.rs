@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Host hook that sees every exchange on the MCP endpoint — the request
+/// as received and the JSON-RPC message written back — for an audit
+/// trail. Unlike metrics it gets the payloads themselves; fields named
+/// with [`crate::McpMiddleware::with_audit_redaction`] are masked, in
+/// the request and the response alike, before the sink sees them.
+/// Register it with [`crate::McpMiddleware::register_audit_sink`].
+///
+/// [`Self::on_exchange`] is called once per POST, when its response is
+/// written or, for requests that get none, when the request is done. It
+/// runs on the request path: hand the record to a channel or a buffered
+/// writer rather than doing slow I/O in place.
+pub trait McpAuditSink {
+    fn on_exchange(&self, exchange: &McpAuditedExchange);
+}
+
+#[derive(Debug, Clone)]
+pub struct McpAuditedExchange {
+    /// The `mcp-session-id` the request was served under: the new one
+    /// for `initialize`, empty in stateless mode or when the request
+    /// carried none.
+    pub session_id: String,
+    /// The request body, redacted.
+    pub request: String,
+    /// The JSON-RPC message sent back, without SSE framing, redacted.
+    /// `None` when nothing was: notifications, replies to the server's
+    /// own requests, requests turned away at the HTTP level, calls whose
+    /// client went away first.
+    pub response: Option<String>,
+}
+
+/// What a redacted field's value is replaced with.
+pub const AUDIT_REDACTED: &str = "***";
+
+/// One exchange on its way to the sink. Recorded with the response by
+/// [`Self::record`]; dropped without one, it is recorded as having had
+/// none.
+pub(crate) struct AuditTrail {
+    sink: Arc<dyn McpAuditSink + Send + Sync + 'static>,
+    redaction: Arc<AuditRedaction>,
+    exchange: Option<McpAuditedExchange>,
+}
+
+impl AuditTrail {
+    pub(crate) fn new(
+        sink: Arc<dyn McpAuditSink + Send + Sync + 'static>,
+        session_id: &str,
+        body: &[u8],
+        redaction: Arc<AuditRedaction>,
+    ) -> Self {
+        Self {
+            sink,
+            exchange: Some(McpAuditedExchange {
+                session_id: session_id.to_string(),
+                request: redaction.apply(body),
+                response: None,
+            }),
+            redaction,
+        }
+    }
+
+    pub(crate) fn set_session_id(&mut self, session_id: &str) {
+        if let Some(exchange) = self.exchange.as_mut() {
+            exchange.session_id = session_id.to_string();
+        }
+    }
+
+    pub(crate) fn record(mut self, response: String) {
+        if let Some(mut exchange) = self.exchange.take() {
+            exchange.response = Some(self.redaction.apply(response.as_bytes()));
+            self.sink.on_exchange(&exchange);
+        }
+    }
+}
+
+impl Drop for AuditTrail {
+    fn drop(&mut self) {
+        if let Some(exchange) = self.exchange.take() {
+            self.sink.on_exchange(&exchange);
+        }
+    }
+}
+
+/// What is masked in audited and logged payloads. See
+/// [`crate::McpMiddleware::with_audit_redaction`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AuditRedaction {
+    /// Object keys masked at any depth.
+    fields: HashSet<String>,
+    /// JSON pointers (RFC 6901) masked where they point.
+    pointers: Vec<String>,
+}
+
+impl AuditRedaction {
+    /// A `field` starting with `/` is a JSON pointer, anything else a
+    /// key name.
+    pub(crate) fn add(&mut self, field: &str) {
+        if field.starts_with('/') {
+            self.pointers.push(field.to_string());
+        } else {
+            self.fields.insert(field.to_string());
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.pointers.is_empty()
+    }
+
+    /// The body with every redacted value replaced by
+    /// [`AUDIT_REDACTED`]. With nothing to redact the body is kept byte
+    /// for byte; a body that is not JSON can not be searched for
+    /// secrets, so it is withheld when redaction is on.
+    ///
+    /// A `tools/call` result carries its `structuredContent` a second
+    /// time as the JSON text of a content block: that text is written
+    /// again from the redacted value, and any other JSON text block is
+    /// searched for redacted keys too.
+    pub(crate) fn apply(&self, body: &[u8]) -> String {
+        if self.is_empty() {
+            return String::from_utf8_lossy(body).to_string();
+        }
+
+        let Ok(mut value) = serde_json::from_slice::<serde_json::Value>(body) else {
+            return format!("[unparsable payload withheld: {} bytes]", body.len());
+        };
+
+        let structured = value.pointer("/result/structuredContent").cloned();
+
+        self.redact(&mut value);
+
+        let redacted_structured = structured
+            .as_ref()
+            .map(|_| value_at(&value, "/result/structuredContent"));
+        if let Some(serde_json::Value::Array(blocks)) = value.pointer_mut("/result/content") {
+            for block in blocks.iter_mut() {
+                self.redact_text_block(block, structured.as_ref(), redacted_structured.as_ref());
+            }
+        }
+
+        value.to_string()
+    }
+
+    /// Tool arguments as logged: the pointers, which are rooted at the
+    /// request (`/params/arguments/token`), are applied as if the
+    /// arguments were still in it. Arguments that are not JSON are
+    /// withheld.
+    pub(crate) fn apply_to_tool_arguments(&self, arguments: &str) -> String {
+        if self.is_empty() {
+            return arguments.to_string();
+        }
+
+        let Ok(arguments_value) = serde_json::from_str::<serde_json::Value>(arguments) else {
+            return format!("[unparsable payload withheld: {} bytes]", arguments.len());
+        };
+
+        let mut request = serde_json::json!({ "params": { "arguments": arguments_value } });
+        self.redact(&mut request);
+        value_at(&request, "/params/arguments").to_string()
+    }
+
+    fn redact(&self, value: &mut serde_json::Value) {
+        redact_value(value, &self.fields);
+
+        for pointer in self.pointers.iter() {
+            if let Some(target) = value.pointer_mut(pointer) {
+                *target = serde_json::Value::String(AUDIT_REDACTED.to_string());
+            }
+        }
+    }
+
+    fn redact_text_block(
+        &self,
+        block: &mut serde_json::Value,
+        structured: Option<&serde_json::Value>,
+        redacted_structured: Option<&serde_json::Value>,
+    ) {
+        let Some(serde_json::Value::String(text)) = block.get_mut("text") else {
+            return;
+        };
+        let Ok(mut parsed) = serde_json::from_str::<serde_json::Value>(text) else {
+            return;
+        };
+
+        if let (Some(structured), Some(redacted)) = (structured, redacted_structured) {
+            if &parsed == structured {
+                *text = redacted.to_string();
+                return;
+            }
+        }
+
+        let before = parsed.clone();
+        redact_value(&mut parsed, &self.fields);
+        if parsed != before {
+            *text = parsed.to_string();
+        }
+    }
+}
+
+fn value_at(value: &serde_json::Value, pointer: &str) -> serde_json::Value {
+    value
+        .pointer(pointer)
+        .cloned()
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn redact_value(value: &mut serde_json::Value, fields: &HashSet<String>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if fields.contains(key) {
+                    *value = serde_json::Value::String(AUDIT_REDACTED.to_string());
+                } else {
+                    redact_value(value, fields);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items.iter_mut() {
+                redact_value(item, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redaction(fields: &[&str]) -> AuditRedaction {
+        let mut redaction = AuditRedaction::default();
+        for field in fields {
+            redaction.add(field);
+        }
+        redaction
+    }
+
+    #[test]
+    fn logged_tool_arguments_take_pointers_rooted_at_the_request() {
+        let redaction = redaction(&["/params/arguments/token", "password"]);
+        let logged = redaction.apply_to_tool_arguments(
+            r#"{"text":"hi","token":"s3cr3t","password":"hunter2","meta":{"token":"kept"}}"#,
+        );
+
+        let logged: serde_json::Value = serde_json::from_str(&logged).unwrap();
+        assert_eq!(
+            logged,
+            serde_json::json!({
+                "text": "hi",
+                "token": "***",
+                "password": "***",
+                "meta": {"token": "kept"}
+            })
+        );
+    }
+
+    #[test]
+    fn logged_tool_arguments_that_are_not_json_are_withheld() {
+        let redaction = redaction(&["password"]);
+        assert_eq!(
+            redaction.apply_to_tool_arguments("password=hunter2"),
+            "[unparsable payload withheld: 16 bytes]"
+        );
+        assert_eq!(
+            AuditRedaction::default().apply_to_tool_arguments("password=hunter2"),
+            "password=hunter2"
+        );
+    }
+
+    #[test]
+    fn the_text_copy_of_structured_content_is_redacted_too() {
+        let redaction = redaction(&["/result/structuredContent/token", "password"]);
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {
+                "content": [
+                    {"type": "text", "text": r#"{"token":"s3cr3t","text":"hi"}"#},
+                    {"type": "text", "text": r#"{"password":"hunter2"}"#},
+                    {"type": "text", "text": "plain words"}
+                ],
+                "structuredContent": {"token": "s3cr3t", "text": "hi"},
+                "isError": false
+            }
+        });
+
+        let redacted = redaction.apply(response.to_string().as_bytes());
+        assert!(!redacted.contains("s3cr3t"), "{}", redacted);
+        assert!(!redacted.contains("hunter2"), "{}", redacted);
+
+        let redacted: serde_json::Value = serde_json::from_str(&redacted).unwrap();
+        let content = &redacted["result"]["content"];
+        let copy: serde_json::Value =
+            serde_json::from_str(content[0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(copy, redacted["result"]["structuredContent"]);
+        assert_eq!(copy["token"], AUDIT_REDACTED);
+        assert_eq!(content[1]["text"], r#"{"password":"***"}"#);
+        assert_eq!(content[2]["text"], "plain words");
+    }
+}
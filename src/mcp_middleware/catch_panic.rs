@@ -0,0 +1,48 @@
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Awaits `work`, turning a panic raised while polling it into `Err`
+/// with the panic message. Host handlers run through this, so a
+/// panicking tool, prompt or resource is answered with `-32603` instead
+/// of unwinding through the middleware — or, for a tool running in its
+/// own task, silently ending the response stream.
+pub(crate) async fn catch_panic<T>(work: impl Future<Output = T>) -> Result<T, String> {
+    CatchPanic {
+        work: Box::pin(work),
+    }
+    .await
+}
+
+struct CatchPanic<F> {
+    work: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for CatchPanic<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let work = self.work.as_mut();
+
+        // The future is never polled again after a panic, so no broken
+        // state it may have left behind is observed.
+        match catch_unwind(AssertUnwindSafe(|| work.poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(panic) => Poll::Ready(Err(panic_message(panic.as_ref()))),
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+
+    if let Some(message) = panic.downcast_ref::<String>() {
+        return message.clone();
+    }
+
+    "non-string panic payload".to_string()
+}
@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+/// Where the middleware reads the current time — session `last_access`
+/// stamps and the idle-session GC. The default is [`SystemClock`];
+/// swap in a [`MockClock`] via [`crate::McpMiddleware::with_clock`] to
+/// drive time-dependent behavior from a test.
+pub trait McpClock {
+    fn now(&self) -> DateTimeAsMicroseconds;
+}
+
+/// The wall clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl McpClock for SystemClock {
+    fn now(&self) -> DateTimeAsMicroseconds {
+        DateTimeAsMicroseconds::now()
+    }
+}
+
+/// A clock that only moves when told to. Shared through an `Arc`, so a
+/// test keeps a handle and advances the same clock the middleware reads.
+#[derive(Debug)]
+pub struct MockClock {
+    unix_microseconds: AtomicI64,
+}
+
+impl MockClock {
+    pub fn new(start: DateTimeAsMicroseconds) -> Self {
+        Self {
+            unix_microseconds: AtomicI64::new(start.unix_microseconds),
+        }
+    }
+
+    /// A mock clock frozen at the current wall-clock time.
+    pub fn starting_now() -> Self {
+        Self::new(DateTimeAsMicroseconds::now())
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.unix_microseconds
+            .fetch_add(by.as_micros() as i64, Ordering::SeqCst);
+    }
+
+    pub fn set(&self, now: DateTimeAsMicroseconds) {
+        self.unix_microseconds
+            .store(now.unix_microseconds, Ordering::SeqCst);
+    }
+}
+
+impl McpClock for MockClock {
+    fn now(&self) -> DateTimeAsMicroseconds {
+        DateTimeAsMicroseconds::new(self.unix_microseconds.load(Ordering::SeqCst))
+    }
+}
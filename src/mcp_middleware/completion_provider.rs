@@ -0,0 +1,53 @@
+use my_http_server::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Most values a `completion/complete` result may carry, per the spec.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+/// What a `completion/complete` request completes an argument of: a
+/// prompt by name, or a resource template by URI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CompletionReference {
+    #[serde(rename = "ref/prompt")]
+    Prompt { name: String },
+    #[serde(rename = "ref/resource")]
+    Resource { uri: String },
+}
+
+/// Suggestions for an argument. Values past [`MAX_COMPLETION_VALUES`]
+/// are cut off, and the result is then marked as having more.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionValues {
+    pub values: Vec<String>,
+    /// How many matches there are in all, when the provider knows.
+    pub total: Option<usize>,
+    pub has_more: bool,
+}
+
+impl CompletionValues {
+    pub fn new(values: Vec<String>) -> Self {
+        Self {
+            values,
+            total: None,
+            has_more: false,
+        }
+    }
+}
+
+/// Answers `completion/complete` — argument suggestions as the user
+/// types. Register it with
+/// [`crate::McpMiddleware::register_completion_provider`]; `initialize`
+/// then advertises the `completions` capability.
+#[async_trait::async_trait]
+pub trait McpCompletionProvider {
+    /// Suggestions for `argument_name` of `reference`, given the
+    /// `value` typed so far. `Err` goes out as `-32602`: an unknown
+    /// prompt, say.
+    async fn complete(
+        &self,
+        reference: &CompletionReference,
+        argument_name: &str,
+        value: &str,
+    ) -> Result<CompletionValues, String>;
+}
@@ -0,0 +1,147 @@
+use crate::mcp_middleware::{ArgumentError, McpMethod, validate_arguments};
+
+/// The bundled result schemas, one per supported protocol version.
+/// Reduced by hand from upstream; `schemas/README.md` says from what
+/// and what was cut.
+const SCHEMAS: [(&str, &str); 3] = [
+    ("2025-03-26", include_str!("../../schemas/mcp-2025-03-26.json")),
+    ("2025-06-18", include_str!("../../schemas/mcp-2025-06-18.json")),
+    ("2025-11-25", include_str!("../../schemas/mcp-2025-11-25.json")),
+];
+
+/// Checks one response the middleware compiled — a `compile_*` output,
+/// framed or not — against the MCP schema of `protocol_version` for
+/// `method`: the JSON-RPC envelope, then the `result` or `error`. Returns
+/// every failure, each with the JSON pointer of the offending value in
+/// the response; empty when the response conforms. A method without a
+/// bundled schema only gets its envelope checked.
+///
+/// Meant for tests: built with the `conformance` feature.
+pub fn check_response_conformance(
+    method: &McpMethod,
+    protocol_version: &str,
+    response: &str,
+) -> Vec<ArgumentError> {
+    let failure = |pointer: &str, message: String| ArgumentError {
+        pointer: pointer.to_string(),
+        message,
+    };
+
+    let Some((_, schema)) = SCHEMAS.iter().find(|(version, _)| *version == protocol_version)
+    else {
+        return vec![failure(
+            "",
+            format!("No bundled schema for protocol version {}", protocol_version),
+        )];
+    };
+    let schema: serde_json::Value =
+        serde_json::from_str(schema).expect("bundled MCP schemas are valid JSON");
+
+    let json = super::unframe(response.to_string());
+    let message: serde_json::Value = match serde_json::from_str(&json) {
+        Ok(message) => message,
+        Err(err) => return vec![failure("", format!("Not JSON. Msg: {}", err))],
+    };
+
+    let Some(envelope) = message.as_object() else {
+        return vec![failure("", "A JSON-RPC message is an object".to_string())];
+    };
+
+    let mut failures = Vec::new();
+
+    if envelope.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        failures.push(failure("/jsonrpc", "Expected \"2.0\"".to_string()));
+    }
+
+    match envelope.get("id") {
+        Some(serde_json::Value::String(_) | serde_json::Value::Number(_)) => {}
+        // Only an error may answer a request whose id could not be read.
+        Some(serde_json::Value::Null) if envelope.contains_key("error") => {}
+        _ => failures.push(failure("/id", "Expected a string or a number".to_string())),
+    }
+
+    let (pointer, body, body_schema) = match (envelope.get("result"), envelope.get("error")) {
+        (Some(result), None) => ("/result", result, schema["results"].get(method.as_str())),
+        (None, Some(error)) => ("/error", error, schema.get("error")),
+        (Some(_), Some(_)) => {
+            failures.push(failure("", "Has both result and error".to_string()));
+            return failures;
+        }
+        (None, None) => {
+            failures.push(failure("", "Has neither result nor error".to_string()));
+            return failures;
+        }
+    };
+
+    let body_schema = body_schema
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({"type": "object"}));
+
+    failures.extend(
+        validate_arguments(&body_schema, body)
+            .into_iter()
+            .map(|err| failure(&format!("{}{}", pointer, err.pointer), err.message)),
+    );
+
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp_middleware::{
+        RequestId, ResourceContent, ResourceReadResult, SUPPORTED_PROTOCOL_VERSIONS,
+        compile_empty_result_response, compile_jsonrpc_error, compile_read_resource_response,
+    };
+
+    #[test]
+    fn every_supported_version_has_a_schema() {
+        for version in SUPPORTED_PROTOCOL_VERSIONS {
+            let response = compile_empty_result_response(&RequestId::Int(1));
+            let failures = check_response_conformance(&McpMethod::Ping, version, &response);
+            assert!(failures.is_empty(), "{}: {:?}", version, failures);
+        }
+    }
+
+    #[test]
+    fn compiled_responses_conform() {
+        let response = ResourceReadResult {
+            contents: vec![ResourceContent::text("notes://1", "", "hello")],
+        };
+        let response = compile_read_resource_response(response, &RequestId::Int(2), None);
+        let failures =
+            check_response_conformance(&McpMethod::ResourcesRead, "2025-06-18", &response);
+        assert!(failures.is_empty(), "{:?}", failures);
+
+        let response = compile_jsonrpc_error(-32602, "Invalid params", &RequestId::Int(3));
+        let failures = check_response_conformance(&McpMethod::ToolsCall, "2025-06-18", &response);
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+
+    #[test]
+    fn a_malformed_response_is_flagged() {
+        let response = r#"{"jsonrpc":"2.0","id":4,"result":{"contents":[{"mimeType":"text/plain","text":7}]}}"#;
+        let failures =
+            check_response_conformance(&McpMethod::ResourcesRead, "2025-06-18", response);
+
+        let mut pointers: Vec<&str> = failures.iter().map(|f| f.pointer.as_str()).collect();
+        pointers.sort();
+        assert_eq!(pointers, vec!["/result/contents/0/text", "/result/contents/0/uri"]);
+
+        let response = r#"{"jsonrpc":"1.0","result":{},"error":{"code":1,"message":"x"}}"#;
+        let failures = check_response_conformance(&McpMethod::Ping, "2025-06-18", response);
+        let pointers: Vec<&str> = failures.iter().map(|f| f.pointer.as_str()).collect();
+        assert_eq!(pointers, vec!["/jsonrpc", "/id", ""]);
+    }
+
+    #[test]
+    fn content_types_follow_the_version() {
+        let response = r#"{"jsonrpc":"2.0","id":5,"result":{"content":[{"type":"resource_link","uri":"notes://1","name":"n"}]}}"#;
+        let failures = check_response_conformance(&McpMethod::ToolsCall, "2025-06-18", response);
+        assert!(failures.is_empty(), "{:?}", failures);
+
+        let failures = check_response_conformance(&McpMethod::ToolsCall, "2025-03-26", response);
+        assert_eq!(failures.len(), 1, "{:?}", failures);
+        assert_eq!(failures[0].pointer, "/result/content/0/type");
+    }
+}
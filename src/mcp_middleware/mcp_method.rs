@@ -0,0 +1,174 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// A JSON-RPC `method` of the MCP protocol. Every method the middleware
+/// knows is a variant; anything else — unknown methods, notifications
+/// without a handler — is [`Self::Other`] with the name as received.
+/// Adding a method starts here: its name in [`Self::as_str`], its params
+/// in [`Self::takes_named_params`], its capability in
+/// [`Self::capability`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum McpMethod {
+    Initialize,
+    Ping,
+    ToolsList,
+    ToolsCall,
+    PromptsList,
+    PromptsGet,
+    ResourcesList,
+    ResourcesRead,
+    ResourcesSubscribe,
+    ResourcesUnsubscribe,
+    ResourceTemplatesList,
+    CompletionComplete,
+    NotificationsInitialized,
+    Other(String),
+}
+
+impl McpMethod {
+    /// Every variant but [`Self::Other`].
+    pub const KNOWN: &'static [McpMethod] = &[
+        Self::Initialize,
+        Self::Ping,
+        Self::ToolsList,
+        Self::ToolsCall,
+        Self::PromptsList,
+        Self::PromptsGet,
+        Self::ResourcesList,
+        Self::ResourcesRead,
+        Self::ResourcesSubscribe,
+        Self::ResourcesUnsubscribe,
+        Self::ResourceTemplatesList,
+        Self::CompletionComplete,
+        Self::NotificationsInitialized,
+    ];
+
+    pub fn parse(method: &str) -> Self {
+        match Self::KNOWN.iter().find(|known| known.as_str() == method) {
+            Some(known) => known.clone(),
+            None => Self::Other(method.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Initialize => "initialize",
+            Self::Ping => "ping",
+            Self::ToolsList => "tools/list",
+            Self::ToolsCall => "tools/call",
+            Self::PromptsList => "prompts/list",
+            Self::PromptsGet => "prompts/get",
+            Self::ResourcesList => "resources/list",
+            Self::ResourcesRead => "resources/read",
+            Self::ResourcesSubscribe => "resources/subscribe",
+            Self::ResourcesUnsubscribe => "resources/unsubscribe",
+            Self::ResourceTemplatesList => "resources/templates/list",
+            Self::CompletionComplete => "completion/complete",
+            Self::NotificationsInitialized => "notifications/initialized",
+            Self::Other(method) => method.as_str(),
+        }
+    }
+
+    /// Whether the method's `params` are deserialized by name. The rest
+    /// either take no params (`tools/list`, `ping`) or are passed through
+    /// untouched.
+    pub fn takes_named_params(&self) -> bool {
+        matches!(
+            self,
+            Self::Initialize
+                | Self::ResourcesList
+                | Self::ResourcesRead
+                | Self::ResourcesSubscribe
+                | Self::ResourcesUnsubscribe
+                | Self::PromptsGet
+                | Self::ToolsCall
+                | Self::CompletionComplete
+        )
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.as_str().starts_with("notifications/")
+    }
+
+    /// The server capability of `initialize` the method belongs to —
+    /// `tools`, `prompts`, `resources` or `completions`. `None` for the
+    /// lifecycle and utility methods every server answers.
+    pub fn capability(&self) -> Option<&'static str> {
+        match self {
+            Self::ToolsList | Self::ToolsCall => Some("tools"),
+            Self::PromptsList | Self::PromptsGet => Some("prompts"),
+            Self::ResourcesList
+            | Self::ResourcesRead
+            | Self::ResourcesSubscribe
+            | Self::ResourcesUnsubscribe
+            | Self::ResourceTemplatesList => Some("resources"),
+            Self::CompletionComplete => Some("completions"),
+            Self::Initialize | Self::Ping | Self::NotificationsInitialized | Self::Other(_) => None,
+        }
+    }
+}
+
+/// Notifications a client may send per the spec, besides
+/// `notifications/initialized`. The middleware has no handler for them
+/// and accepts them quietly; any other notification is unknown — see
+/// [`UnknownNotificationPolicy`].
+pub const KNOWN_CLIENT_NOTIFICATIONS: &[&str] = &[
+    "notifications/cancelled",
+    "notifications/progress",
+    "notifications/roots/list_changed",
+];
+
+/// What the middleware does with a notification whose method it does
+/// not know — often a sign of a client bug. It is accepted with `202`
+/// whatever the policy. See
+/// `McpMiddleware::with_unknown_notification_policy`.
+#[derive(Debug, Clone, Default)]
+pub enum UnknownNotificationPolicy {
+    #[default]
+    Ignore,
+    /// Logs the method and the session it came from.
+    Log,
+    /// Adds one to the host's counter, for it to export as a metric.
+    Count(std::sync::Arc<std::sync::atomic::AtomicU64>),
+}
+
+impl FromStr for McpMethod {
+    type Err = Infallible;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(method))
+    }
+}
+
+impl fmt::Display for McpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_methods_round_trip() {
+        for method in McpMethod::KNOWN {
+            let parsed: McpMethod = method.as_str().parse().unwrap();
+            assert_eq!(&parsed, method);
+            assert_eq!(parsed.to_string(), method.as_str());
+        }
+    }
+
+    #[test]
+    fn unknown_methods_keep_their_name() {
+        let parsed: McpMethod = "logging/setLevel".parse().unwrap();
+        assert_eq!(parsed, McpMethod::Other("logging/setLevel".to_string()));
+        assert_eq!(parsed.as_str(), "logging/setLevel");
+        assert_eq!(parsed.capability(), None);
+
+        let parsed: McpMethod = "notifications/cancelled".parse().unwrap();
+        assert!(parsed.is_notification());
+        assert!(matches!(parsed, McpMethod::Other(_)));
+    }
+}
@@ -1,35 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
+use my_ai_agent::{ToolDefinition, json_schema::*};
 use my_http_server::{hyper::Method, *};
 use rust_extensions::date_time::DateTimeAsMicroseconds;
 use serde::{Serialize, de::DeserializeOwned};
 
 use crate::mcp_middleware::{
-    DynamicResourceExecutor, DynamicResources, InitializeMpcContract, McpConnectionInfo,
-    McpElicitations, McpInputData, McpInputPayload, McpPromptService, McpPrompts,
-    McpResourceService, McpResources, McpSessions, McpToolCallExWithInstruction,
-    McpToolCallWithInstruction, McpToolCalls, PromptDefinition, PromptExecutor, RequestId,
-    ResourceDefinition, ResourceExecutor, ResourceIcon, SESSION_HEADER, ToolCallContext,
-    ToolCallExecutor, ToolCallExecutorEx, parse_elicitation_response,
+    AuditRedaction, AuditTrail, DynamicPromptProvider, DynamicResourceExecutor, DynamicResources,
+    ExecutedToolCall, InitializeMpcContract, McpAuditSink, McpClock, McpCompletionProvider,
+    McpConnectionInfo, McpElicitations, McpInputData, McpInputPayload, McpMethod, McpPromptService,
+    McpPrompts, McpRequestFilter, McpRequestHeaders, McpResourceService, McpResources,
+    McpSessionStream, McpSessions, McpToolCallAbstract, McpToolCallError,
+    McpToolCallExWithInstruction, McpToolCallWithInstruction, McpToolCalls, PendingResponse,
+    PromptDefinition, PromptExecutor, RequestId, ResourceBody, ResourceDefinition,
+    ResourceExecutor, ResourceIcon, ResourceReadResult, SESSION_HEADER, ServerVersion,
+    SlowClientPolicy, SystemClock, ToolCallContext, ToolCallExecutor, ToolCallExecutorEx,
+    ToolCallLoad, ToolCallScheduler, parse_elicitation_response,
 };
 
-use my_ai_agent::{ToolDefinition, json_schema::*};
-
 pub struct McpMiddleware {
     mcp_path: &'static str,
     name: &'static str,
-    version: &'static str,
-    instructions: &'static str,
+    /// `serverInfo.version`. See [`Self::with_version`].
+    version: String,
+    /// Sent at initialize. See [`Self::with_max_instructions_len`].
+    instructions: String,
+    /// Sent as `serverInfo._meta` at initialize. See
+    /// [`Self::with_server_meta`].
+    server_meta: BTreeMap<String, serde_json::Value>,
+    /// Sent as `capabilities.experimental` at initialize. See
+    /// [`Self::with_experimental_capability`].
+    experimental_capabilities: BTreeMap<String, serde_json::Value>,
     sessions: Arc<McpSessions>,
-    tool_calls: McpToolCalls,
-    prompts: McpPrompts,
-    resources: McpResources,
+    /// The registries are behind locks, like `dynamic_resources`, so
+    /// [`Self::reset`] and the `unregister_*` calls work on a middleware
+    /// already mounted on the HTTP server.
+    tool_calls: tokio::sync::RwLock<McpToolCalls>,
+    prompts: tokio::sync::RwLock<McpPrompts>,
+    resources: tokio::sync::RwLock<McpResources>,
     /// Runtime-registered resources. Static resources go through
     /// `resources`; this registry serves URIs minted after `new()`
     /// (e.g. one resource per downloaded Telegram media item).
     dynamic_resources: Arc<tokio::sync::RwLock<DynamicResources>>,
+    /// Answers `resources/read` for URIs neither registry knows. See
+    /// [`Self::register_resource_fallback`].
+    resource_fallback: Option<Arc<dyn McpResourceService + Send + Sync + 'static>>,
+    /// Answers `completion/complete`. See
+    /// [`Self::register_completion_provider`].
+    completion_provider: Option<Arc<dyn McpCompletionProvider + Send + Sync + 'static>>,
+    /// Sees raw POST bodies before parsing. See
+    /// [`Self::register_request_filter`].
+    request_filter: Option<Arc<dyn McpRequestFilter + Send + Sync + 'static>>,
+    /// See [`Self::register_audit_sink`].
+    audit_sink: Option<Arc<dyn McpAuditSink + Send + Sync + 'static>>,
+    /// Values the audit sink and the logs never see. See
+    /// [`Self::with_audit_redaction`].
+    audit_redaction: Arc<AuditRedaction>,
+    /// Per-URI caps on concurrent `resources/read`. See
+    /// [`Self::with_resource_read_limit`].
+    resource_read_limits: HashMap<String, Arc<tokio::sync::Semaphore>>,
+    /// Content hash of each resource as of its last
+    /// `notifications/resources/updated`; `None` unless
+    /// [`Self::with_resource_change_detection`] is on.
+    notified_resource_hashes: Option<parking_lot::Mutex<HashMap<String, u64>>>,
+    /// Hash of the tool list clients last saw: the first one served by
+    /// `tools/list`, then each one announced. See
+    /// [`Self::notify_tools_changed`].
+    announced_tools_hash: parking_lot::Mutex<Option<u64>>,
+    /// Server-wide cap on running tool calls. See
+    /// [`Self::with_max_concurrent_tool_calls`].
+    tool_call_scheduler: Option<Arc<ToolCallScheduler>>,
+    /// Deadlines by method name. See [`Self::with_method_timeout`].
+    method_timeouts: HashMap<String, Duration>,
+    /// Deadline of methods without an entry in `method_timeouts`. See
+    /// [`Self::with_request_timeout`].
+    request_timeout: Option<Duration>,
+    /// When on (the default), `resources/read` of an alias returns the
+    /// target's contents. See [`Self::disabled_resource_redirects`].
+    follow_resource_redirects: bool,
+    /// Whether `application/json` resource text is checked, and
+    /// re-indented, at read time. See
+    /// [`Self::with_json_resource_validation`].
+    json_resources: JsonResourceCheck,
     /// Registry of in-flight server→client `elicitation/create`
     /// requests. Tools opted into [`McpToolCallEx`] reach this through
     /// the [`ToolCallContext`] supplied at execute-time.
@@ -41,37 +96,302 @@ pub struct McpMiddleware {
     /// unknown `mcp-session-id` adopts that id instead of getting a
     /// `404`. See [`Self::disabled_lazy_session_creation`].
     lazy_session_creation: bool,
+    /// When on, responses of one session are written in the order the
+    /// requests arrived. See [`Self::with_ordered_responses`].
+    ordered_responses: bool,
+    /// When on (the default), a request reusing the id of a request
+    /// still in flight on the same session gets `-32600`. See
+    /// [`Self::disabled_duplicate_request_id_check`].
+    duplicate_request_id_check: bool,
+    /// When on, `tools/call` arguments sent as a string holding a JSON
+    /// object are unwrapped. See [`Self::with_string_arguments_coercion`].
+    coerce_string_arguments: bool,
+    /// When on, `tools/call` arguments are checked against the tool's
+    /// input schema first. See [`Self::with_input_schema_validation`].
+    validate_arguments: bool,
+    /// Response bodies at least this large are compressed for clients
+    /// that accept it. See [`Self::with_response_compression`].
+    compression_threshold: Option<usize>,
+    /// How long a `tools/call` may take and still be answered with a
+    /// plain JSON body. See [`Self::with_json_responses`].
+    json_response_window: Option<Duration>,
+    /// Longest `data:` line a response is sent with. See
+    /// [`Self::with_max_sse_line_size`].
+    max_sse_line_size: Option<usize>,
+    /// Most items a page of a list or of `resources/read` holds,
+    /// whatever the client asks for. See [`Self::with_max_page_size`].
+    max_page_size: usize,
+    /// When on (the default), tool results carry `structuredContent`
+    /// and tools are listed with their `outputSchema`. See
+    /// [`Self::disabled_structured_content`].
+    structured_content: bool,
+    /// When on, optional prompt arguments are listed with
+    /// `"required": false`. See [`Self::with_explicit_required_false`].
+    explicit_required_false: bool,
+    /// When on, no session is stored: see [`Self::with_stateless_mode`].
+    stateless: bool,
+    /// See [`Self::with_max_instructions_len`].
+    max_instructions_len: Option<usize>,
+    /// Set once the operator was warned that the instructions are cut.
+    instructions_truncation_warned: AtomicBool,
+    /// See [`Self::with_capability_instructions`].
+    capability_instructions: bool,
+    /// See [`Self::with_capability_gating`].
+    capability_gating: bool,
+    /// See [`Self::with_unknown_notification_policy`].
+    unknown_notifications: super::UnknownNotificationPolicy,
+    /// Source of "now" for session stamps and the idle GC. See
+    /// [`Self::with_clock`].
+    clock: Arc<dyn McpClock + Send + Sync>,
     /// The GC task is started lazily on the first request, which is
     /// guaranteed to run inside the tokio runtime (unlike `new()`).
     gc_started: AtomicBool,
 }
 
+/// Registry sizes as of the moment [`McpMiddleware::get_capability_summary`]
+/// was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct McpCapabilitySummary {
+    pub tools: usize,
+    /// Registered prompts; those of a [`DynamicPromptProvider`] are not
+    /// counted.
+    pub prompts: usize,
+    /// Static resources plus the ones registered at runtime.
+    pub resources: usize,
+}
+
+impl McpCapabilitySummary {
+    pub fn has_tools(&self) -> bool {
+        self.tools > 0
+    }
+
+    pub fn has_prompts(&self) -> bool {
+        self.prompts > 0
+    }
+
+    pub fn has_resources(&self) -> bool {
+        self.resources > 0
+    }
+}
+
 const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
 
 impl McpMiddleware {
+    /// `version` is sent as `serverInfo.version` as given; one that is
+    /// not semver is logged as a warning, since some clients expect it.
+    /// [`Self::with_version`] sets a typed one instead.
     pub fn new(
         mcp_path: &'static str,
         name: &'static str,
         version: &'static str,
         instructions: &'static str,
     ) -> Self {
+        if let Some(warning) = super::non_semver_version_warning(version) {
+            eprintln!("{}", warning);
+        }
+
         Self {
             mcp_path,
             name,
-            version,
-            instructions,
+            version: version.to_string(),
+            instructions: instructions.to_string(),
+            server_meta: BTreeMap::new(),
+            experimental_capabilities: BTreeMap::new(),
             sessions: Arc::new(McpSessions::new()),
-            tool_calls: McpToolCalls::new(),
-            prompts: McpPrompts::new(),
-            resources: McpResources::new(),
+            tool_calls: tokio::sync::RwLock::new(McpToolCalls::new()),
+            prompts: tokio::sync::RwLock::new(McpPrompts::new()),
+            resources: tokio::sync::RwLock::new(McpResources::new()),
             dynamic_resources: Arc::new(tokio::sync::RwLock::new(DynamicResources::new())),
+            resource_fallback: None,
+            completion_provider: None,
+            request_filter: None,
+            audit_sink: None,
+            audit_redaction: Arc::new(AuditRedaction::default()),
+            resource_read_limits: HashMap::new(),
+            tool_call_scheduler: None,
+            announced_tools_hash: parking_lot::Mutex::new(None),
+            notified_resource_hashes: None,
+            method_timeouts: HashMap::new(),
+            request_timeout: None,
+            follow_resource_redirects: true,
+            json_resources: JsonResourceCheck::Off,
             elicitations: Arc::new(McpElicitations::new()),
             session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
             lazy_session_creation: true,
+            ordered_responses: false,
+            duplicate_request_id_check: true,
+            coerce_string_arguments: false,
+            validate_arguments: false,
+            compression_threshold: None,
+            json_response_window: None,
+            max_sse_line_size: None,
+            max_page_size: super::LIST_PAGE_SIZE.max(super::READ_PAGE_SIZE),
+            structured_content: true,
+            explicit_required_false: false,
+            stateless: false,
+            max_instructions_len: None,
+            instructions_truncation_warned: AtomicBool::new(false),
+            capability_instructions: false,
+            capability_gating: false,
+            unknown_notifications: super::UnknownNotificationPolicy::Ignore,
+            clock: Arc::new(SystemClock),
             gc_started: AtomicBool::new(false),
         }
     }
 
+    /// Replaces the version given to [`Self::new`] with a checked one.
+    pub fn with_version(mut self, version: ServerVersion) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Caps the `instructions` sent at `initialize` at `max_len` bytes,
+    /// so an overgrown text does not eat into the client's context
+    /// budget. The cap applies to the text as sent, capability guidance
+    /// included. Longer instructions are cut at the last sentence that
+    /// fits, followed by a note saying they were truncated, and the
+    /// operator is warned the first time that happens. Off by default.
+    pub fn with_max_instructions_len(mut self, max_len: usize) -> Self {
+        self.max_instructions_len = Some(max_len);
+        self
+    }
+
+    /// Appends a line of guidance per enabled capability to the
+    /// `instructions` sent at `initialize` — say, that the server
+    /// supports resource subscriptions — after a blank line, so the
+    /// hand-written text does not have to keep up with the
+    /// configuration. Only capabilities the session really has are
+    /// mentioned; [`Self::with_max_instructions_len`] caps the text
+    /// with the guidance in it. Off by default.
+    pub fn with_capability_instructions(mut self) -> Self {
+        self.capability_instructions = true;
+        self
+    }
+
+    /// Adds a `serverInfo._meta` entry to the `initialize` response —
+    /// build info, commit hash, environment. Clients that do not know a
+    /// key ignore it. Setting a key twice keeps the last value.
+    pub fn with_server_meta(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.server_meta.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Advertises a non-standard feature as
+    /// `capabilities.experimental.<name>` at `initialize`. Setting a
+    /// name twice keeps the last value.
+    pub fn with_experimental_capability(
+        mut self,
+        name: &str,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.experimental_capabilities
+            .insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Answers a request for a capability the session was not offered
+    /// at `initialize` with `-32601`, instead of serving it anyway: a
+    /// `prompts/get` on a server without prompts, a
+    /// `resources/subscribe` in stateless mode. What is enforced is
+    /// what was advertised — the same [`super::ServerCapabilities`],
+    /// kept with the session. Off by default.
+    pub fn with_capability_gating(mut self) -> Self {
+        self.capability_gating = true;
+        self
+    }
+
+    /// Lets at most `max_concurrent` reads of `uri` run at once, across
+    /// all sessions — for resources that are expensive to produce (a
+    /// remote fetch, a heavy query). Reads over the cap queue in arrival
+    /// order until a running one finishes. Applies whether `uri` is a
+    /// static, dynamic or fallback resource, and to reads redirected to
+    /// it; the wait counts toward the `resources/read` deadline.
+    /// `max_concurrent` of `0` is treated as `1`.
+    pub fn with_resource_read_limit(mut self, uri: &str, max_concurrent: usize) -> Self {
+        self.resource_read_limits.insert(
+            uri.to_string(),
+            Arc::new(tokio::sync::Semaphore::new(max_concurrent.max(1))),
+        );
+        self
+    }
+
+    /// Makes [`Self::notify_resource_updated`] check that the resource
+    /// really changed: it reads the resource and only notifies when the
+    /// content hash differs from the one of the last notification. Lets
+    /// the host call it on every upstream event without flooding
+    /// subscribers with updates that change nothing. A resource nobody
+    /// subscribed to is not read; one that fails to read is notified.
+    pub fn with_resource_change_detection(mut self) -> Self {
+        self.notified_resource_hashes = Some(parking_lot::Mutex::new(HashMap::new()));
+        self
+    }
+
+    /// Lets at most `max_concurrent` tool calls run at once, server-wide,
+    /// shared fairly between sessions: calls over the cap wait in a queue
+    /// per session, and each freed slot goes to the next session in turn.
+    /// A session that floods the server with calls gets its share and no
+    /// more — another session's call waits for one turn, not for the
+    /// whole flood. In stateless mode all requests count as one session.
+    /// Queue time counts towards the `tools/call` timeout.
+    /// `max_concurrent` of `0` is treated as `1`.
+    pub fn with_max_concurrent_tool_calls(mut self, max_concurrent: usize) -> Self {
+        self.tool_call_scheduler = Some(Arc::new(ToolCallScheduler::new(max_concurrent)));
+        self
+    }
+
+    /// Deadline for every request of `method` (`"tools/call"`,
+    /// `"resources/read"`, `"prompts/get"`), overriding
+    /// [`Self::with_request_timeout`] for it. A request that runs longer
+    /// is answered with `-32001` and its handler is dropped.
+    pub fn with_method_timeout(mut self, method: &str, timeout: Duration) -> Self {
+        self.method_timeouts.insert(method.to_string(), timeout);
+        self
+    }
+
+    /// Deadline for requests of methods without their own
+    /// [`Self::with_method_timeout`]. Default: none — handlers run as
+    /// long as they take.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Stops `resources/read` from following aliases: a resource that
+    /// answers with [`crate::ResourceContent::redirect`] is returned as
+    /// a link to its target (`text/uri-list`, `_meta.redirect`) for the
+    /// client to read itself. By default the middleware follows up to
+    /// 8 hops and fails the read on a loop.
+    pub fn disabled_resource_redirects(mut self) -> Self {
+        self.follow_resource_redirects = false;
+        self
+    }
+
+    /// Checks at read time that the text of every `application/json`
+    /// resource content is valid JSON. A read that returns invalid JSON
+    /// fails with `-32603` instead of handing the client a document it
+    /// can not parse. Off by default.
+    pub fn with_json_resource_validation(mut self) -> Self {
+        self.json_resources = JsonResourceCheck::Validate;
+        self
+    }
+
+    /// Same as [`Self::with_json_resource_validation`], and the valid
+    /// text is also re-indented for people to read. Key order, numbers
+    /// and strings are kept as they are.
+    pub fn with_pretty_json_resources(mut self) -> Self {
+        self.json_resources = JsonResourceCheck::Pretty;
+        self
+    }
+
+    /// Replaces the wall clock the middleware reads "now" from — session
+    /// `last_access` stamps and the idle-session GC. Meant for tests:
+    /// pass a shared [`crate::MockClock`] and advance it to expire
+    /// sessions without sleeping.
+    pub fn with_clock(mut self, clock: Arc<dyn McpClock + Send + Sync>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Overrides how long a session may stay idle (no requests, no live
     /// SSE stream) before the background GC drops it. Default: 30 min.
     pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
@@ -79,6 +399,17 @@ impl McpMiddleware {
         self
     }
 
+    /// Lets the `GET` stream of each session hold up to `capacity`
+    /// notifications the client has not read yet, and picks what
+    /// happens to the next one when it is full — see
+    /// [`SlowClientPolicy`]. Default: 32, [`SlowClientPolicy::Block`].
+    /// Applies to streams opened afterwards. `capacity` of `0` is
+    /// treated as `1`.
+    pub fn with_session_buffer(self, capacity: usize, policy: SlowClientPolicy) -> Self {
+        self.sessions.set_buffer(capacity, policy);
+        self
+    }
+
     /// Registers the host hook for session lifecycle events — a session
     /// appeared (together with the request that created it) and a
     /// session is gone. Optional: without it nothing is fired and the
@@ -91,6 +422,49 @@ impl McpMiddleware {
         self.sessions.set_connection_info(connection_info);
     }
 
+    /// Registers the pre-parse filter: it gets every POST body raw,
+    /// together with the request context, and may answer the request
+    /// itself instead of letting it through. See [`McpRequestFilter`].
+    /// A later registration replaces an earlier one.
+    pub fn register_request_filter(
+        &mut self,
+        filter: Arc<dyn McpRequestFilter + Send + Sync + 'static>,
+    ) {
+        self.request_filter = Some(filter);
+    }
+
+    /// Registers the audit sink: it gets every POST exchange, request
+    /// and response, once the response is written. See [`McpAuditSink`].
+    /// A later registration replaces an earlier one.
+    pub fn register_audit_sink(&mut self, sink: Arc<dyn McpAuditSink + Send + Sync + 'static>) {
+        self.audit_sink = Some(sink);
+    }
+
+    /// Masks the value of every `field` key — at any depth, so
+    /// `params.arguments.password` as much as a top-level one — in the
+    /// requests and responses the audit sink gets, and in the tool
+    /// arguments written to the log. A `field` starting with `/` is a
+    /// JSON pointer instead, such as `/params/arguments/token`, and masks
+    /// that one value only. Call once per field.
+    pub fn with_audit_redaction(mut self, field: &str) -> Self {
+        Arc::make_mut(&mut self.audit_redaction).add(field);
+        self
+    }
+
+    /// What to do with a notification whose method the middleware does
+    /// not know — neither one it handles nor one of
+    /// [`super::KNOWN_CLIENT_NOTIFICATIONS`] — to catch client bugs:
+    /// ignore it (the default), log it, or count it. Unknown requests,
+    /// which carry an id, are answered with `-32601` and logged as
+    /// before. Either way the notification is accepted with `202`.
+    pub fn with_unknown_notification_policy(
+        mut self,
+        policy: super::UnknownNotificationPolicy,
+    ) -> Self {
+        self.unknown_notifications = policy;
+        self
+    }
+
     /// Turns lazy session creation off and restores the spec behavior:
     /// a non-`initialize` request whose `mcp-session-id` is unknown gets
     /// `404` so the client re-runs `initialize`. By default the id is
@@ -101,6 +475,129 @@ impl McpMiddleware {
         self
     }
 
+    /// Writes the responses of a session in request-arrival order, for
+    /// clients that can not match out-of-order replies. Handlers still
+    /// run concurrently; a response that is ready early is held back
+    /// (its stream kept alive) until every earlier request of the same
+    /// session has been answered. Off by default.
+    pub fn with_ordered_responses(mut self) -> Self {
+        self.ordered_responses = true;
+        self
+    }
+
+    /// Turns off the per-session check for duplicate request ids. By
+    /// default a request whose id equals the id of a request the same
+    /// session still has in flight is rejected with `-32600` (Invalid
+    /// Request), since its response could not be told apart from the
+    /// first one. An id is free again as soon as its response is out.
+    pub fn disabled_duplicate_request_id_check(mut self) -> Self {
+        self.duplicate_request_id_check = false;
+        self
+    }
+
+    /// Accepts `tools/call` arguments some clients send stringified:
+    /// `"arguments": "{\"text\":\"hi\"}"` is served as if the object
+    /// had been sent. Only a string that parses as a JSON object is
+    /// unwrapped; any other string is passed on as is, for the tool to
+    /// reject. Off by default, since a tool may take a string argument
+    /// list on purpose.
+    pub fn with_string_arguments_coercion(mut self) -> Self {
+        self.coerce_string_arguments = true;
+        self
+    }
+
+    /// Checks `tools/call` arguments against the tool's `inputSchema`
+    /// before the tool runs. Arguments that miss it get one `-32602`
+    /// listing every failure in `error.data`, as `{pointer, message}`
+    /// objects a client can map to its form fields — instead of the
+    /// first deserialization error only. See
+    /// [`crate::validate_arguments`] for the keywords checked.
+    pub fn with_input_schema_validation(mut self) -> Self {
+        self.validate_arguments = true;
+        self
+    }
+
+    /// Compresses response bodies of `min_size` bytes and more with
+    /// gzip (or deflate) when the request's `Accept-Encoding` allows it.
+    /// Applies to the single-frame responses — `tools/list`,
+    /// `resources/read` and the like; a `tools/call` stream interleaves
+    /// keepalives with the result and stays uncompressed. Off by
+    /// default.
+    pub fn with_response_compression(mut self, min_size: usize) -> Self {
+        self.compression_threshold = Some(min_size);
+        self
+    }
+
+    /// Lets the server pick the response type for clients whose
+    /// `Accept` lists `application/json` next to `text/event-stream`.
+    /// A single immediate result — `tools/list`, `resources/read` and
+    /// the like — is then a plain `application/json` body. A
+    /// `tools/call` is given up to `window` to finish: if it does, its
+    /// result goes out as JSON too, otherwise the response is upgraded
+    /// to the SSE stream with keepalives. Clients that accept only SSE
+    /// are always streamed to. Off by default.
+    pub fn with_json_responses(mut self, window: Duration) -> Self {
+        self.json_response_window = Some(window);
+        self
+    }
+
+    /// Breaks response frames with a `data:` line longer than
+    /// `max_bytes` into several `data:` lines of the same event, which
+    /// SSE clients join back together — for clients and proxies that cap
+    /// the line length (a large `resources/read` easily runs to
+    /// megabytes). Lines are only broken between JSON tokens, so one
+    /// string longer than `max_bytes` still goes out whole. Off by
+    /// default; has no effect without the `sse-framing` feature.
+    pub fn with_max_sse_line_size(mut self, max_bytes: usize) -> Self {
+        self.max_sse_line_size = Some(max_bytes);
+        self
+    }
+
+    /// Caps the pages of `tools/list`, `prompts/list`, `resources/list`
+    /// and `resources/read` at `max_items`. A client may ask for its own
+    /// page size with the `pageSize` param (an extension); a bigger one
+    /// is clamped to the cap, and so are the default pages of 100 when
+    /// the cap is lower. 100 by default; 0 counts as 1.
+    pub fn with_max_page_size(mut self, max_items: usize) -> Self {
+        self.max_page_size = max_items.max(1);
+        self
+    }
+
+    /// Answers `tools/call` with the text content block only, without
+    /// `structuredContent`, for clients that show both to the model and
+    /// so pay for the same result twice. Tools are then listed without
+    /// `outputSchema`, which would oblige the server to send structured
+    /// results. On by default.
+    pub fn disabled_structured_content(mut self) -> Self {
+        self.structured_content = false;
+        self
+    }
+
+    /// Lists optional prompt arguments with `"required": false`, for
+    /// strict clients that expect the field on every argument. By
+    /// default `prompts/list` writes `required` only where it is `true`.
+    pub fn with_explicit_required_false(mut self) -> Self {
+        self.explicit_required_false = true;
+        self
+    }
+
+    /// Serves every request on its own, with no session stored server
+    /// side — for serverless / edge hosting where consecutive requests
+    /// may land on different instances. `initialize` is optional: it is
+    /// answered as usual but issues no `mcp-session-id`, and other
+    /// requests are served with or without a session header (a header
+    /// that is sent is ignored). The client is assumed to have no
+    /// capabilities, so there is no elicitation. There is no
+    /// server→client channel either: GET and DELETE answer `405`,
+    /// subscriptions and notifications have nobody to reach — so
+    /// `initialize` advertises `resources.subscribe` as `false` — and
+    /// ordered responses and the duplicate request id check, being
+    /// per-session, do not apply.
+    pub fn with_stateless_mode(mut self) -> Self {
+        self.stateless = true;
+        self
+    }
+
     /// Snapshot of the sessions the middleware currently holds, oldest
     /// `create` first (ties broken by id). Takes `&self`, so a host that
     /// handed its `Arc<McpMiddleware>` to `add_middleware` can keep
@@ -113,19 +610,378 @@ impl McpMiddleware {
         self.sessions.get_sessions()
     }
 
+    /// What `initialize` negotiated for the session — protocol version
+    /// and client capabilities — as one consistent snapshot, safe to
+    /// read while the client re-initializes. `None` for an unknown
+    /// session. See [`super::McpSessions::session_capabilities`].
+    pub fn get_session_capabilities(&self, session_id: &str) -> Option<super::SessionCapabilities> {
+        self.sessions.session_capabilities(session_id)
+    }
+
+    /// Opens the session's server→client channel — what a `GET` on the
+    /// MCP endpoint streams — as a [`McpSessionStream`] of frames, to be
+    /// piped into a response body of any HTTP server. Like a new `GET`,
+    /// it replaces the session's previous channel, which ends. `None` for
+    /// an unknown session and in stateless mode. Must be called inside
+    /// the tokio runtime.
+    pub fn open_session_stream(&self, session_id: &str) -> Option<McpSessionStream> {
+        if self.stateless {
+            return None;
+        }
+
+        let receiver = self
+            .sessions
+            .subscribe_to_notifications(session_id, self.clock.now())?;
+
+        Some(McpSessionStream::new(
+            receiver,
+            self.sessions.clone(),
+            session_id.to_string(),
+        ))
+    }
+
+    /// What the server offers right now — what `initialize` advertises.
+    /// A session keeps the set it was advertised, which is what
+    /// [`Self::with_capability_gating`] enforces.
+    pub async fn server_capabilities(&self) -> super::ServerCapabilities {
+        super::ServerCapabilities {
+            tools: self.tool_calls.read().await.has_tools(),
+            prompts: self.prompts.read().await.has_prompts(),
+            // Stateless mode has no stream to push updates down.
+            resource_subscribe: !self.stateless,
+            completions: self.completion_provider.is_some(),
+            experimental: self.experimental_capabilities.clone(),
+        }
+    }
+
+    /// How many tools, prompts and resources are registered right now —
+    /// the cheap alternative to walking `tools/list` & co. when all an
+    /// operator wants is a number for a dashboard or a startup log.
+    pub async fn get_capability_summary(&self) -> McpCapabilitySummary {
+        let dynamic_resources = self.dynamic_resources.read().await.count();
+
+        McpCapabilitySummary {
+            tools: self.tool_calls.read().await.count(),
+            prompts: self.prompts.read().await.count(),
+            resources: self.resources.read().await.count() + dynamic_resources,
+        }
+    }
+
+    /// How many tool calls hold a slot of
+    /// [`Self::with_max_concurrent_tool_calls`] right now, and how many
+    /// wait for one — for a gauge on a dashboard. `None` without the cap:
+    /// nothing queues then.
+    pub fn get_tool_call_load(&self) -> Option<ToolCallLoad> {
+        self.tool_call_scheduler
+            .as_ref()
+            .map(|scheduler| scheduler.load())
+    }
+
+    /// The first page of the `tools/list` response the middleware would
+    /// send for request `id`, as bare JSON-RPC (no SSE framing) — for
+    /// hosts that serve MCP over their own transport. See
+    /// [`Self::list_tools_page`] for the pages after it.
+    pub async fn list_tools(&self, id: &RequestId) -> String {
+        self.list_tools_page(id, None).await
+    }
+
+    /// [`Self::list_tools`], starting after `cursor` (the `nextCursor`
+    /// of the previous page). An unknown cursor gives the `-32602`
+    /// error response.
+    pub async fn list_tools_page(&self, id: &RequestId, cursor: Option<&str>) -> String {
+        let page = super::ListPageModel {
+            cursor: cursor.map(|cursor| cursor.to_string()),
+            page_size: None,
+        };
+        let response = self
+            .compile_tools_list(id, None, &page)
+            .await
+            .unwrap_or_else(|err| invalid_params_response(err.as_str(), id));
+        super::mcp_output_contract::unframe(response)
+    }
+
+    /// Same as [`Self::list_tools`] for `prompts/list`.
+    pub async fn list_prompts(&self, id: &RequestId) -> String {
+        self.list_prompts_page(id, None).await
+    }
+
+    /// Same as [`Self::list_tools_page`] for `prompts/list`.
+    pub async fn list_prompts_page(&self, id: &RequestId, cursor: Option<&str>) -> String {
+        let page = super::ListPageModel {
+            cursor: cursor.map(|cursor| cursor.to_string()),
+            page_size: None,
+        };
+        let response = self
+            .compile_prompts_list(id, &page)
+            .await
+            .unwrap_or_else(|err| invalid_params_response(err.as_str(), id));
+        super::mcp_output_contract::unframe(response)
+    }
+
+    /// Same as [`Self::list_tools_page`] for `resources/list`: static
+    /// and dynamic resources together, ordered by URI.
+    pub async fn list_resources(&self, id: &RequestId, cursor: Option<&str>) -> String {
+        super::mcp_output_contract::unframe(self.compile_resources_list(id, cursor, None).await)
+    }
+
+    /// `protocol_version` is the session's, for tools with
+    /// version-conditioned output schemas; `None` lists the default ones.
+    /// `Err` is an unknown cursor.
+    async fn compile_tools_list(
+        &self,
+        id: &RequestId,
+        protocol_version: Option<&str>,
+        page: &super::ListPageModel,
+    ) -> Result<String, String> {
+        let list = self.tool_calls.read().await.get_list().await;
+
+        // The first list a client sees is what later changes are
+        // measured against.
+        {
+            let mut announced = self.announced_tools_hash.lock();
+            if announced.is_none() {
+                *announced = Some(super::tools_list_hash(&list));
+            }
+        }
+
+        let page_size = self.page_size(page.page_size, super::LIST_PAGE_SIZE);
+        let (list, next_cursor) = page_by_name(
+            list,
+            |tool| tool.mcp.get_fn_name(),
+            page.cursor.as_deref(),
+            page_size,
+        )?;
+
+        let list = match protocol_version {
+            Some(protocol_version) => super::tools_list_for_version(list, protocol_version).await,
+            None => list,
+        };
+
+        Ok(super::mcp_output_contract::compile_tool_calls_page(
+            list,
+            self.structured_content,
+            id,
+            next_cursor.as_deref(),
+        ))
+    }
+
+    /// `Err` is an unknown cursor.
+    async fn compile_prompts_list(
+        &self,
+        id: &RequestId,
+        page: &super::ListPageModel,
+    ) -> Result<String, String> {
+        // Not listed under the lock: a slow provider would hold up
+        // `register_prompt` and `reset`.
+        let prompts = self.prompts.read().await.clone();
+        let list = prompts.get_list().await;
+
+        let page_size = self.page_size(page.page_size, super::LIST_PAGE_SIZE);
+        let (list, next_cursor) = page_by_name(
+            list,
+            |prompt| prompt.prompt.get_prompt_name(),
+            page.cursor.as_deref(),
+            page_size,
+        )?;
+
+        Ok(super::mcp_output_contract::compile_prompts_list_page(
+            list,
+            self.explicit_required_false,
+            id,
+            next_cursor.as_deref(),
+        ))
+    }
+
+    /// The page size a client asked for, or `default`, within
+    /// [`Self::with_max_page_size`].
+    fn page_size(&self, requested: Option<usize>, default: usize) -> usize {
+        requested.unwrap_or(default).clamp(1, self.max_page_size)
+    }
+
+    async fn compile_resources_list(
+        &self,
+        id: &RequestId,
+        cursor: Option<&str>,
+        page_size: Option<usize>,
+    ) -> String {
+        let page_size = self.page_size(page_size, super::LIST_PAGE_SIZE);
+
+        // Both registries are ordered by URI and the cursor is the last
+        // URI listed, so a page is the first `page_size` of both merged.
+        let (static_list, static_next) = self.resources.read().await.get_list(cursor, page_size);
+        let (dynamic_list, dynamic_next) = self
+            .dynamic_resources
+            .read()
+            .await
+            .get_list(cursor, page_size);
+        let more = static_next.is_some() || dynamic_next.is_some();
+
+        let mut list: Vec<super::ResourceSchemaData> = Vec::with_capacity(page_size);
+        let mut static_list = static_list.into_iter().peekable();
+        let mut dynamic_list = dynamic_list.into_iter().peekable();
+        loop {
+            let next = match (static_list.peek(), dynamic_list.peek()) {
+                (Some(a), Some(b)) => {
+                    let a = a.resource.get_resource_uri();
+                    let b = b.resource.get_resource_uri();
+                    if a == b {
+                        // Reads go to the static one.
+                        dynamic_list.next();
+                        static_list.next()
+                    } else if a < b {
+                        static_list.next()
+                    } else {
+                        dynamic_list.next()
+                    }
+                }
+                (Some(_), None) => static_list.next(),
+                (None, _) => dynamic_list.next(),
+            };
+
+            match next {
+                Some(resource) => list.push(resource),
+                None => break,
+            }
+        }
+
+        let next_cursor = if list.len() > page_size || more {
+            list.truncate(page_size);
+            list.last()
+                .map(|resource| resource.resource.get_resource_uri().to_string())
+        } else {
+            None
+        };
+
+        super::mcp_output_contract::compile_resources_list(list, id, next_cursor.as_deref())
+    }
+
     /// Pushes `notifications/resources/updated` for `uri` to every live
-    /// session that subscribed to it via `resources/subscribe`. Call it
-    /// whenever the content behind a resource changes.
+    /// session that subscribed to it via `resources/subscribe`, with the
+    /// resource's title, if it has one, next to the URI. Call it
+    /// whenever the content behind a resource changes. With
+    /// [`Self::with_resource_change_detection`] a call that finds the
+    /// content unchanged since the last notification sends nothing.
     pub async fn notify_resource_updated(&self, uri: &str) {
-        self.sessions.notify_resource_updated(uri).await;
+        if let Some(notified_hashes) = &self.notified_resource_hashes {
+            if self.sessions.get_subscribers(uri).is_empty() {
+                return;
+            }
+
+            // Read as `resources/read` reads it: a slow or panicking
+            // handler must not hold up or unwind the notifier. A read
+            // that fails is notified as a change.
+            if let Ok(Ok(Ok(result))) = self.read_resource_guarded(uri).await {
+                let hash = result.content_hash();
+                let mut notified_hashes = notified_hashes.lock();
+
+                if notified_hashes.get(uri) == Some(&hash) {
+                    return;
+                }
+
+                notified_hashes.insert(uri.to_string(), hash);
+            }
+        }
+
+        let title = self.resource_title(uri).await;
+        self.sessions
+            .notify_resource_updated(uri, title.as_deref())
+            .await;
+    }
+
+    /// The title `resources/list` shows for `uri`, from the static
+    /// registry or the dynamic one.
+    async fn resource_title(&self, uri: &str) -> Option<String> {
+        if let Some(resource) = self.resources.read().await.get(uri) {
+            return resource.get_title().map(|title| title.to_string());
+        }
+
+        self.dynamic_resources.read().await.title(uri)
+    }
+
+    /// Graceful shutdown: tells every session with a live SSE stream
+    /// that the server is going down (`notifications/shutdown` with
+    /// `reason`) and then closes those streams. Call it before stopping
+    /// the HTTP server, so clients see a clean goodbye instead of a
+    /// dropped connection. Returns how many streams were notified.
+    pub async fn notify_shutdown(&self, reason: &str) -> usize {
+        self.sessions.shutdown(reason).await
+    }
+
+    /// Flushes a group of notifications to every live stream as one
+    /// contiguous write, in the order they were added — clients never
+    /// see another event land in the middle of the group.
+    pub async fn notify_batch(&self, batch: super::McpNotificationBatch) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut events = batch.into_events();
+        for event in events.iter_mut() {
+            if let super::McpSocketUpdateEvent::ResourceUpdated { uri, title } = event {
+                if title.is_none() {
+                    *title = self.resource_title(uri).await;
+                }
+            }
+        }
+
+        self.sessions.broadcast_batch(events).await;
     }
 
+    /// Sends `notifications/tools/list_changed` to every live session —
+    /// unless the tool list is the same as the one clients last saw,
+    /// judged by a hash of what `tools/list` would show. Re-registering
+    /// an identical tool, or a burst of calls after one change, thus
+    /// announces nothing or once.
     pub async fn notify_tools_changed(&self) {
+        let hash = super::tools_list_hash(&self.tool_calls.read().await.get_list().await);
+
+        {
+            let mut announced = self.announced_tools_hash.lock();
+            if *announced == Some(hash) {
+                return;
+            }
+            *announced = Some(hash);
+        }
+
         self.sessions
             .broadcast(super::McpSocketUpdateEvent::ToolsListChanged)
             .await;
     }
 
+    /// Sends a notification of any `method` — a custom one, or a spec
+    /// notification the middleware has no typed call for — to the live
+    /// stream of `session_id`: `{"jsonrpc":"2.0","method":...,"params":...}`
+    /// with no id. `params`, when given, is an object or an array.
+    /// `Err` when the session has no live stream or the stream's
+    /// [`super::SlowClientPolicy`] gave up on it.
+    pub async fn send_notification(
+        &self,
+        session_id: &str,
+        method: &str,
+        params: Option<serde_json::Value>,
+    ) -> Result<(), String> {
+        if method.is_empty() {
+            return Err("Notification method is empty".to_string());
+        }
+
+        let params = match params {
+            None => None,
+            Some(params) if params.is_object() || params.is_array() => Some(params.to_string()),
+            Some(params) => {
+                return Err(format!(
+                    "Notification params must be an object or an array, got {}",
+                    params
+                ));
+            }
+        };
+
+        let event = super::McpSocketUpdateEvent::Notification {
+            method: method.to_string(),
+            params,
+        };
+        self.sessions.send_to_session(session_id, event).await
+    }
+
     pub async fn notify_resources_changed(&self) {
         self.sessions
             .broadcast(super::McpSocketUpdateEvent::ResourcesListChanged)
@@ -138,6 +994,56 @@ impl McpMiddleware {
             .await;
     }
 
+    /// Drops every tool, prompt and resource — static and dynamic, prompt
+    /// providers included — and tells live sessions their lists changed
+    /// (`notifications/*/list_changed` for all three). For test setups
+    /// and hot reload — on a mounted middleware too: register the new
+    /// set afterwards. The resource
+    /// fallback and per-URI read limits are configuration, not entries,
+    /// and stay as they are.
+    pub async fn reset(&self) {
+        self.tool_calls.write().await.clear();
+        self.prompts.write().await.clear();
+        self.resources.write().await.clear();
+        self.dynamic_resources.write().await.clear();
+
+        self.notify_tools_changed().await;
+        self.notify_prompts_changed().await;
+        self.notify_resources_changed().await;
+    }
+
+    /// Drops the tool registered as `fn_name` and, if there was one,
+    /// tells live sessions with `notifications/tools/list_changed`.
+    /// Returns whether a tool was removed. For hot reload and plugin
+    /// unload, on a running server as well.
+    pub async fn unregister_tool_call(&self, fn_name: &str) -> bool {
+        let removed = self.tool_calls.write().await.remove(fn_name);
+        if removed {
+            self.notify_tools_changed().await;
+        }
+        removed
+    }
+
+    /// Same as [`Self::unregister_tool_call`], for a prompt.
+    pub async fn unregister_prompt(&self, name: &str) -> bool {
+        let removed = self.prompts.write().await.remove(name);
+        if removed {
+            self.notify_prompts_changed().await;
+        }
+        removed
+    }
+
+    /// Same as [`Self::unregister_tool_call`], for a resource registered
+    /// with [`Self::register_resource`]. Dynamic resources go with
+    /// [`Self::unregister_dynamic_resource`].
+    pub async fn unregister_resource(&self, uri: &str) -> bool {
+        let removed = self.resources.write().await.remove(uri);
+        if removed {
+            self.notify_resources_changed().await;
+        }
+        removed
+    }
+
     pub fn register_tool_call<
         InputData: JsonTypeDescription + Sized + Send + Sync + 'static + Serialize + DeserializeOwned,
         OutputData: JsonTypeDescription + Sized + Send + Sync + 'static + Serialize + DeserializeOwned,
@@ -156,7 +1062,7 @@ impl McpMiddleware {
             holder: service,
         };
 
-        self.tool_calls.add(Arc::new(executor));
+        self.tool_calls.get_mut().add(Arc::new(executor));
     }
 
     /// Same as [`Self::register_tool_call`] but for tools that need
@@ -182,7 +1088,18 @@ impl McpMiddleware {
             holder: service,
         };
 
-        self.tool_calls.add(Arc::new(executor));
+        self.tool_calls.get_mut().add(Arc::new(executor));
+    }
+
+    /// Registers a tool that implements [`McpToolCallAbstract`] itself:
+    /// it writes its own schemas and parses its own arguments, and can
+    /// fail with any [`McpToolCallError`] — including
+    /// [`McpToolCallError::ServerError`] with an application error code.
+    pub fn register_raw_tool_call(
+        &mut self,
+        tool: Arc<dyn McpToolCallAbstract + Send + Sync + 'static>,
+    ) {
+        self.tool_calls.get_mut().add(tool);
     }
 
     pub fn register_prompt<
@@ -191,14 +1108,20 @@ impl McpMiddleware {
         &mut self,
         service: Arc<TMcpPromptService>,
     ) {
-        let executor = PromptExecutor {
-            prompt_name: TMcpPromptService::PROMPT_NAME,
-            description: TMcpPromptService::DESCRIPTION,
-            argument_descriptions: TMcpPromptService::get_argument_descriptions(),
-            holder: service,
-        };
+        let executor = PromptExecutor::from_definition::<TMcpPromptService>(service);
+
+        self.prompts.get_mut().add(Arc::new(executor));
+    }
 
-        self.prompts.add(Arc::new(executor));
+    /// Registers a source of prompts generated at runtime. See
+    /// [`DynamicPromptProvider`]. Several providers can be registered;
+    /// a prompt registered with [`Self::register_prompt`] shadows a
+    /// provider's of the same name.
+    pub fn register_prompt_provider(
+        &mut self,
+        provider: Arc<dyn DynamicPromptProvider + Send + Sync + 'static>,
+    ) {
+        self.prompts.get_mut().add_provider(provider);
     }
 
     pub fn register_resource<
@@ -210,7 +1133,11 @@ impl McpMiddleware {
         // Extract optional values before moving service - convert to owned values
         let title = service.get_title().map(|s| s.to_string());
         let size = service.get_size();
-        let icons = service.get_icons();
+        let icons =
+            ResourceIcon::normalize_icons(TMcpResourceService::RESOURCE_URI, service.get_icons());
+        let cache_control = service.get_cache_control();
+
+        warn_on_missing_mime_type(TMcpResourceService::RESOURCE_URI, TMcpResourceService::MIME_TYPE);
 
         let executor = ResourceExecutor {
             resource_uri: TMcpResourceService::RESOURCE_URI,
@@ -220,10 +1147,37 @@ impl McpMiddleware {
             title,
             size,
             icons,
+            cache_control,
             holder: service,
         };
 
-        self.resources.add(Arc::new(executor));
+        self.resources.get_mut().add(Arc::new(executor));
+    }
+
+    /// Installs the handler `resources/read` falls back to when the URI
+    /// is neither a static nor a dynamic resource — a "not found"
+    /// document, or a lookup into a store too large to register
+    /// resource by resource. It gets the requested URI; `Ok` content is
+    /// served as is, `Err` goes out as `-32002` with its message. The
+    /// fallback is not listed in `resources/list`, and a later
+    /// registration replaces an earlier one.
+    pub fn register_resource_fallback(
+        &mut self,
+        service: Arc<dyn McpResourceService + Send + Sync + 'static>,
+    ) {
+        self.resource_fallback = Some(service);
+    }
+
+    /// Installs the handler of `completion/complete`, which suggests
+    /// values for prompt and resource-template arguments as the user
+    /// types. `initialize` advertises the `completions` capability only
+    /// once one is registered; without it the method is answered with
+    /// `-32601`. A later registration replaces an earlier one.
+    pub fn register_completion_provider(
+        &mut self,
+        provider: Arc<dyn McpCompletionProvider + Send + Sync + 'static>,
+    ) {
+        self.completion_provider = Some(provider);
     }
 
     /// Register a resource minted at runtime. URI is whatever caller
@@ -260,6 +1214,9 @@ impl McpMiddleware {
         icons: Vec<ResourceIcon>,
         service: Arc<dyn McpResourceService + Send + Sync + 'static>,
     ) {
+        warn_on_missing_mime_type(uri.as_str(), mime_type.as_str());
+        let icons = ResourceIcon::normalize_icons(uri.as_str(), icons);
+
         let executor = DynamicResourceExecutor {
             resource_uri: uri,
             resource_name: name,
@@ -274,6 +1231,29 @@ impl McpMiddleware {
         w.add(Arc::new(executor));
     }
 
+    /// Registers a dynamic resource whose content is produced as a
+    /// stream of text chunks — a log being tailed — by
+    /// [`super::McpResourceTail`]. The tail is opened on the first read,
+    /// which returns the first chunk. Every later chunk is appended and
+    /// announced with [`Self::notify_resource_updated`]; a read returns
+    /// the last [`super::TAIL_WINDOW_BYTES`], and one with
+    /// `range: {start: <bytes already seen>}` only what is new.
+    /// Unregistering the resource, or [`Self::reset`], stops the tail.
+    /// Takes the middleware in an `Arc`, which the tail announces
+    /// through.
+    pub async fn register_tailing_resource(
+        self: &Arc<Self>,
+        uri: String,
+        name: String,
+        description: String,
+        mime_type: String,
+        tail: Arc<dyn super::McpResourceTail + Send + Sync + 'static>,
+    ) {
+        let service = super::TailedResource::new(tail, mime_type.clone(), Arc::downgrade(self));
+        self.register_dynamic_resource(uri, name, description, mime_type, Arc::new(service))
+            .await
+    }
+
     /// Drop a dynamic resource. Returns true if a resource with that
     /// URI was actually present. Callers that want clients to refresh
     /// their resource list should follow up with
@@ -283,14 +1263,242 @@ impl McpMiddleware {
         w.remove(uri)
     }
 
-    /// Shared by both the with-session and the without-session POST
-    /// paths: `initialize` always mints a fresh session, even when the
+    /// One read of `uri` with everything around it: the concurrency
+    /// limit of each URI read, redirects, a panicking handler caught and
+    /// the `resources/read` deadline — which a wait for a permit counts
+    /// toward.
+    async fn read_resource_guarded(
+        &self,
+        uri: &str,
+    ) -> Result<Result<Result<ResourceReadResult, ResourceReadError>, String>, Duration> {
+        let read = super::catch_panic(self.read_resource_following_redirects(uri));
+        within_timeout(self.method_timeout(McpMethod::ResourcesRead.as_str()), read).await
+    }
+
+    /// The reads of a `uris` batch, one after the other under a single
+    /// `resources/read` deadline: a URI that can not be read is an error
+    /// block among the contents of the others.
+    async fn read_resources_batch(&self, uris: &[String]) -> Result<ResourceReadResult, Duration> {
+        let reads = async {
+            let mut contents = Vec::new();
+
+            for uri in uris {
+                let read = super::catch_panic(self.read_resource_following_redirects(uri)).await;
+                let failure = match read {
+                    Ok(Ok(result)) => {
+                        contents.extend(result.contents);
+                        continue;
+                    }
+                    Err(panic) => handler_panicked(&McpMethod::ResourcesRead, uri, &panic),
+                    Ok(Err(ResourceReadError::NotFound(err))) => err,
+                    Ok(Err(ResourceReadError::Failed(err))) => {
+                        eprintln!("Error reading resource with URI {}. Err: {}", uri, err);
+                        err
+                    }
+                };
+                contents.push(super::ResourceContent::error(uri.as_str(), failure));
+            }
+
+            ResourceReadResult { contents }
+        };
+
+        within_timeout(self.method_timeout(McpMethod::ResourcesRead.as_str()), reads).await
+    }
+
+    /// The deadline of one `method` request: its own entry, else the
+    /// global default.
+    fn method_timeout(&self, method: &str) -> Option<Duration> {
+        self.method_timeouts
+            .get(method)
+            .copied()
+            .or(self.request_timeout)
+    }
+
+    /// Reads `uri` from the static registry, the dynamic one or the
+    /// fallback, in that order.
+    async fn read_any_resource(&self, uri: &str) -> Result<ResourceReadResult, ResourceReadError> {
+        // Neither registry is read under its lock: a slow resource
+        // would hold up `reset` and the `unregister_*` calls.
+        let resource = self.resources.read().await.get(uri);
+        if let Some(resource) = resource {
+            return resource.read(uri).await.map_err(ResourceReadError::Failed);
+        }
+
+        let resource = self.dynamic_resources.read().await.get(uri);
+        if let Some(resource) = resource {
+            return resource.read(uri).await.map_err(ResourceReadError::Failed);
+        }
+
+        // A fallback miss stays a miss, whatever the fallback's wording
+        // of it.
+        match &self.resource_fallback {
+            Some(fallback) => fallback
+                .read_resource(uri)
+                .await
+                .map_err(ResourceReadError::NotFound),
+            None => Err(ResourceReadError::NotFound(format!(
+                "Resource not found: {}",
+                uri
+            ))),
+        }
+    }
+
+    /// [`Self::read_any_resource`] under the concurrency limit of `uri`,
+    /// if it has one.
+    async fn read_resource_limited(
+        &self,
+        uri: &str,
+    ) -> Result<ResourceReadResult, ResourceReadError> {
+        // Held until this read is done; the semaphore is never closed,
+        // so acquiring only ever waits.
+        let _read_permit = match self.resource_read_limits.get(uri) {
+            Some(limit) => limit.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+
+        self.read_any_resource(uri).await
+    }
+
+    /// [`Self::read_resource_limited`] plus alias resolution: while the read
+    /// is a lone [`super::ResourceContent::redirect`], the target is read
+    /// instead. A URI seen twice, or a chain longer than
+    /// [`MAX_RESOURCE_REDIRECTS`], fails the read. Every hop takes the
+    /// permit of the URI it reads, and gives it back before the next:
+    /// two chains pointing at each other's start can not deadlock.
+    async fn read_resource_following_redirects(
+        &self,
+        uri: &str,
+    ) -> Result<ResourceReadResult, ResourceReadError> {
+        let mut result = self.read_resource_limited(uri).await?;
+
+        if self.follow_resource_redirects {
+            let mut chain = vec![uri.to_string()];
+
+            while let Some(target) = result.redirect_target().map(|t| t.to_string()) {
+                if chain.contains(&target) {
+                    return Err(ResourceReadError::Failed(format!(
+                        "Resource redirect loop: {} -> {}",
+                        chain.join(" -> "),
+                        target
+                    )));
+                }
+
+                if chain.len() > MAX_RESOURCE_REDIRECTS {
+                    return Err(ResourceReadError::Failed(format!(
+                        "Too many resource redirects starting at {}",
+                        uri
+                    )));
+                }
+
+                result = self.read_resource_limited(&target).await?;
+                chain.push(target);
+            }
+        }
+
+        // Whether or not redirects are followed.
+        self.check_json_contents(&mut result)?;
+
+        Ok(result)
+    }
+
+    /// Applies [`Self::with_unknown_notification_policy`].
+    fn on_unknown_notification(&self, session_id: Option<&str>, method: &str) {
+        match &self.unknown_notifications {
+            super::UnknownNotificationPolicy::Ignore => {}
+            super::UnknownNotificationPolicy::Log => match session_id {
+                Some(session_id) => eprintln!(
+                    "MCP session {} sent an unknown notification: {}",
+                    session_id, method
+                ),
+                None => eprintln!(
+                    "A stateless client sent an unknown notification: {}",
+                    method
+                ),
+            },
+            super::UnknownNotificationPolicy::Count(counter) => {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Applies [`Self::with_json_resource_validation`] to the text
+    /// contents of `result` typed `application/json`.
+    fn check_json_contents(&self, result: &mut ResourceReadResult) -> Result<(), ResourceReadError> {
+        if self.json_resources == JsonResourceCheck::Off {
+            return Ok(());
+        }
+
+        for content in result.contents.iter_mut() {
+            let mime_type = content.effective_mime_type();
+            let essence = mime_type.split(';').next().unwrap_or_default().trim();
+            if !essence.eq_ignore_ascii_case("application/json") {
+                continue;
+            }
+
+            let ResourceBody::Text(text) = &mut content.body else {
+                continue;
+            };
+
+            if let Err(err) = serde_json::from_str::<serde::de::IgnoredAny>(text) {
+                return Err(ResourceReadError::Failed(format!(
+                    "Resource {} is not valid JSON: {}",
+                    content.uri, err
+                )));
+            }
+
+            if self.json_resources == JsonResourceCheck::Pretty {
+                *text = super::mcp_output_contract::pretty_print_json(text);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `instructions` cut down to [`Self::with_max_instructions_len`],
+    /// warning the operator the first time it has to cut.
+    fn capped_instructions(&self, instructions: String) -> String {
+        let Some(max_len) = self.max_instructions_len else {
+            return instructions;
+        };
+        let Some(truncated) = super::truncate_instructions(&instructions, max_len) else {
+            return instructions;
+        };
+
+        if !self
+            .instructions_truncation_warned
+            .swap(true, Ordering::Relaxed)
+        {
+            eprintln!(
+                "MCP server instructions are {} bytes, over the limit of {}. Sent truncated to {} bytes",
+                instructions.len(),
+                max_len,
+                truncated.len()
+            );
+        }
+        truncated
+    }
+
+    /// Starts the idle-session GC once. Lazy because it needs the tokio
+    /// runtime, which `new()` can not guarantee.
+    fn ensure_gc_started(&self) {
+        if !self.gc_started.swap(true, Ordering::Relaxed) {
+            super::spawn_session_gc(
+                Arc::downgrade(&self.sessions),
+                self.session_idle_timeout,
+                self.clock.clone(),
+            );
+        }
+    }
+
+    /// Shared by both the with-session and the without-session POST
+    /// paths: `initialize` always mints a fresh session, even when the
     /// client sends a stale `mcp-session-id` header.
     async fn handle_initialize(
         &self,
         contract: InitializeMpcContract,
         now: DateTimeAsMicroseconds,
         id: &RequestId,
+        mut pending: PendingResponse,
         ctx: Option<&mut HttpContext>,
     ) -> Result<HttpOkResult, HttpFailResult> {
         let protocol_version = super::mcp_output_contract::negotiate_protocol_version(
@@ -298,20 +1506,37 @@ impl McpMiddleware {
         )
         .to_string();
 
-        let response = super::mcp_output_contract::compile_init_response(
-            &self.name,
-            &self.version,
-            &self.instructions,
-            protocol_version.as_str(),
-            id,
-            self.tool_calls.has_tools(),
-            self.prompts.has_prompts(),
-        );
+        let capabilities = self.server_capabilities().await;
+
+        let instructions = if self.capability_instructions {
+            super::instructions_with_capability_guidance(&self.instructions, &capabilities)
+        } else {
+            self.instructions.clone()
+        };
+        let instructions = self.capped_instructions(instructions);
+
+        let response = super::InitializeResult {
+            protocol_version: protocol_version.clone(),
+            capabilities: capabilities.clone(),
+            server_info: super::ServerInfo {
+                name: self.name.to_string(),
+                version: self.version.clone(),
+                meta: self.server_meta.clone(),
+            },
+            instructions,
+        }
+        .to_json_rpc(id);
+
+        if self.stateless {
+            return send_response_as_stream(response, None, now, pending);
+        }
 
         let supports_elicitation = contract.capabilities.elicitation.is_some();
         let session = self
             .sessions
             .generate_session(protocol_version, now, supports_elicitation);
+        self.sessions
+            .set_advertised_capabilities(session.id.as_str(), capabilities);
 
         // A session appeared. `ctx` is None only when the middleware is
         // driven directly from a unit test; on the wire `initialize`
@@ -320,74 +1545,187 @@ impl McpMiddleware {
             self.sessions.notify_connected(&session, ctx).await;
         }
 
-        send_response_as_stream(response, session.id.as_str(), now)
+        pending.set_audit_session_id(session.id.as_str());
+        send_response_as_stream(response, Some(session.id.as_str()), now, pending)
     }
 
+    /// `session_id` is `None` in stateless mode.
     async fn handle_authorized_request(
         &self,
-        session_id: &str,
+        session_id: Option<&str>,
         data: McpInputData,
         now: DateTimeAsMicroseconds,
         id: &RequestId,
+        pending: PendingResponse,
         ctx: Option<&mut HttpContext>,
     ) -> Result<HttpOkResult, HttpFailResult> {
+        if self.capability_gating {
+            if let Some(method) = data.method() {
+                let advertised = session_id
+                    .and_then(|session_id| self.sessions.advertised_capabilities(session_id));
+                let capabilities = match advertised {
+                    Some(capabilities) => capabilities,
+                    None => self.server_capabilities().await,
+                };
+
+                if let Err(err) = capabilities.allows(&method) {
+                    return send_jsonrpc_error_as_stream(
+                        super::mcp_output_contract::JSONRPC_METHOD_NOT_FOUND,
+                        err.as_str(),
+                        id,
+                        session_id,
+                        now,
+                        pending,
+                    );
+                }
+            }
+        }
+
         match data {
             super::McpInputData::Initialize(contract) => {
-                return self.handle_initialize(contract, now, id, ctx).await;
+                return self.handle_initialize(contract, now, id, pending, ctx).await;
             }
 
             super::McpInputData::ResourcesList(params) => {
-                let (mut list, next_cursor) =
-                    self.resources.get_list(params.cursor.as_deref());
-
-                // Append every dynamic resource. Pagination cursor is
-                // driven by the static registry; once the static list
-                // is exhausted (next_cursor = None) we surface the
-                // dynamic ones on the same page.
-                if next_cursor.is_none() {
-                    let guard = self.dynamic_resources.read().await;
-                    list.extend(guard.list());
-                }
-
-                let response = super::mcp_output_contract::compile_resources_list(
-                    list,
-                    id,
-                    next_cursor.as_deref(),
-                );
-
-                return send_response_as_stream(response, session_id, now);
+                let response = self
+                    .compile_resources_list(id, params.cursor.as_deref(), params.page_size)
+                    .await;
+                return send_response_as_stream(response, session_id, now, pending);
             }
 
             super::McpInputData::ResourceTemplatesList => {
                 // No URI-template support — an empty list keeps clients
                 // that call this unconditionally (Inspector, Claude) happy.
                 let response = super::mcp_output_contract::compile_resource_templates_list(id);
-                return send_response_as_stream(response, session_id, now);
+                return send_response_as_stream(response, session_id, now, pending);
             }
 
             super::McpInputData::ReadResource(params) => {
-                let read_result = if self.resources.get(&params.uri).is_some() {
-                    self.resources.read(&params.uri).await
-                } else {
-                    let guard = self.dynamic_resources.read().await;
-                    if !guard.contains(&params.uri) {
-                        return send_jsonrpc_error_as_stream(
-                            super::mcp_output_contract::JSONRPC_RESOURCE_NOT_FOUND,
-                            format!("Resource not found: {}", params.uri).as_str(),
-                            id,
-                            session_id,
-                            now,
-                        );
+                let read_result = match params.uris.as_deref() {
+                    // Several URIs in one read, at most a page of them.
+                    Some(uris) => {
+                        if uris.len() > self.max_page_size {
+                            return send_jsonrpc_error_as_stream(
+                                super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                                format!(
+                                    "`uris` names {} resources; at most {} are read at once",
+                                    uris.len(),
+                                    self.max_page_size
+                                )
+                                .as_str(),
+                                id,
+                                session_id,
+                                now,
+                                pending,
+                            );
+                        }
+
+                        match self.read_resources_batch(uris).await {
+                            Ok(result) => Ok(result),
+                            Err(timeout) => {
+                                return send_jsonrpc_error_as_stream(
+                                    super::mcp_output_contract::JSONRPC_REQUEST_TIMEOUT,
+                                    timed_out_message(&McpMethod::ResourcesRead, timeout).as_str(),
+                                    id,
+                                    session_id,
+                                    now,
+                                    pending,
+                                );
+                            }
+                        }
                     }
-                    guard.read(&params.uri).await
+                    None => match self.read_resource_guarded(&params.uri).await {
+                        Err(timeout) => {
+                            return send_jsonrpc_error_as_stream(
+                                super::mcp_output_contract::JSONRPC_REQUEST_TIMEOUT,
+                                timed_out_message(&McpMethod::ResourcesRead, timeout).as_str(),
+                                id,
+                                session_id,
+                                now,
+                                pending,
+                            );
+                        }
+                        Ok(Err(panic)) => {
+                            return send_jsonrpc_error_as_stream(
+                                super::mcp_output_contract::JSONRPC_INTERNAL_ERROR,
+                                handler_panicked(&McpMethod::ResourcesRead, &params.uri, &panic)
+                                    .as_str(),
+                                id,
+                                session_id,
+                                now,
+                                pending,
+                            );
+                        }
+                        Ok(Ok(Ok(result))) => Ok(result),
+                        Ok(Ok(Err(ResourceReadError::NotFound(err)))) => {
+                            return send_jsonrpc_error_as_stream(
+                                super::mcp_output_contract::JSONRPC_RESOURCE_NOT_FOUND,
+                                err.as_str(),
+                                id,
+                                session_id,
+                                now,
+                                pending,
+                            );
+                        }
+                        Ok(Ok(Err(ResourceReadError::Failed(err)))) => Err(err),
+                    },
                 };
 
                 match read_result {
-                    Ok(response) => {
+                    Ok(mut response) => {
+                        // Error placeholders are plain text by design.
+                        for content in response
+                            .contents
+                            .iter()
+                            .filter(|c| !matches!(c.body, ResourceBody::Error(_)))
+                        {
+                            warn_on_missing_mime_type(content.uri.as_str(), &content.mime_type);
+                        }
+
+                        if let Some(range) = params.range {
+                            let sliced: Result<Vec<_>, String> = response
+                                .contents
+                                .into_iter()
+                                .map(|content| content.slice(range.start, range.end))
+                                .collect();
+
+                            response.contents = match sliced {
+                                Ok(contents) => contents,
+                                Err(err) => {
+                                    return send_jsonrpc_error_as_stream(
+                                        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                                        err.as_str(),
+                                        id,
+                                        session_id,
+                                        now,
+                                        pending,
+                                    );
+                                }
+                            };
+                        }
+
+                        let page_size = self.page_size(params.page_size, super::READ_PAGE_SIZE);
+                        let (page, next_cursor) =
+                            match response.page_of(params.cursor.as_deref(), page_size) {
+                                Ok(page) => page,
+                                Err(err) => {
+                                    return send_jsonrpc_error_as_stream(
+                                        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                                        err.as_str(),
+                                        id,
+                                        session_id,
+                                        now,
+                                        pending,
+                                    );
+                                }
+                            };
+
                         let response = super::mcp_output_contract::compile_read_resource_response(
-                            response, id,
+                            page,
+                            id,
+                            next_cursor.as_deref(),
                         );
-                        return send_response_as_stream(response, session_id, now);
+                        return send_response_as_stream(response, session_id, now, pending);
                     }
                     Err(err) => {
                         eprintln!("Error reading resource with URI {}. Err: {}", params.uri, err);
@@ -398,13 +1736,14 @@ impl McpMiddleware {
                             id,
                             session_id,
                             now,
+                            pending,
                         );
                     }
                 }
             }
 
             super::McpInputData::SubscribeResource(params) => {
-                let known = self.resources.get(&params.uri).is_some()
+                let known = self.resources.read().await.get(&params.uri).is_some()
                     || self.dynamic_resources.read().await.contains(&params.uri);
 
                 if !known {
@@ -414,60 +1753,181 @@ impl McpMiddleware {
                         id,
                         session_id,
                         now,
+                        pending,
                     );
                 }
 
-                self.sessions.subscribe(session_id, params.uri);
+                if let Some(session_id) = session_id {
+                    self.sessions.subscribe(session_id, params.uri);
+                }
 
                 // Per spec the subscribe response carries an empty result;
                 // updates arrive later as `notifications/resources/updated`.
                 let response = super::mcp_output_contract::compile_empty_result_response(id);
-                return send_response_as_stream(response, session_id, now);
+                return send_response_as_stream(response, session_id, now, pending);
             }
 
             super::McpInputData::UnsubscribeResource(params) => {
                 // Idempotent: unsubscribing from an unknown URI is a no-op.
-                self.sessions.unsubscribe(session_id, &params.uri);
+                if let Some(session_id) = session_id {
+                    self.sessions.unsubscribe(session_id, &params.uri);
+                }
 
                 let response = super::mcp_output_contract::compile_empty_result_response(id);
-                return send_response_as_stream(response, session_id, now);
+                return send_response_as_stream(response, session_id, now, pending);
             }
 
             super::McpInputData::Ping => {
                 let response = super::mcp_output_contract::compile_empty_result_response(id);
-                return send_response_as_stream(response, session_id, now);
+                return send_response_as_stream(response, session_id, now, pending);
             }
 
             super::McpInputData::ExecuteToolCall(params) => {
                 // Unknown tool is a protocol-level error per spec, unlike
                 // runtime failures which are reported in-band (isError).
-                let Some(tool_call) = self.tool_calls.get(&params.name) else {
+                let Some(tool_call) = self.tool_calls.read().await.get(&params.name) else {
                     return send_jsonrpc_error_as_stream(
                         super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
                         format!("Unknown tool: {}", params.name).as_str(),
                         id,
                         session_id,
                         now,
+                        pending,
                     );
                 };
 
-                // serde(default) covers a missing `arguments` key; an
-                // explicit `"arguments": null` still needs this guard.
-                let arguments = if params.arguments.is_null() {
-                    "{}".to_string()
+                // Passed on as the client wrote them, never through a
+                // `Value`: that would turn big integers and long
+                // decimals into `f64`. A missing key and an explicit
+                // `"arguments": null` both mean no arguments.
+                let arguments = match &params.arguments {
+                    Some(raw) => raw.get().to_string(),
+                    None => "{}".to_string(),
+                };
+
+                let arguments = if self.coerce_string_arguments {
+                    unwrap_string_arguments(&arguments).unwrap_or(arguments)
                 } else {
-                    serde_json::to_string(&params.arguments).unwrap_or_else(|_| "{}".to_string())
+                    arguments
                 };
 
-                let ctx = ToolCallContext {
-                    session_id: session_id.to_string(),
-                    supports_elicitation: self
-                        .sessions
-                        .session_supports_elicitation(session_id),
-                    elicitations: self.elicitations.clone(),
-                    sessions: self.sessions.clone(),
+                let schema = if self.validate_arguments {
+                    self.tool_calls.read().await.get_schema(&params.name).await
+                } else {
+                    None
+                };
+
+                if let Some(schema) = schema {
+                    let errors = match (
+                        serde_json::from_str::<serde_json::Value>(&schema.input),
+                        serde_json::from_str::<serde_json::Value>(&arguments),
+                    ) {
+                        (Ok(schema), Ok(arguments)) => {
+                            super::validate_arguments(&schema, &arguments)
+                        }
+                        // Unparsable arguments are left to the tool's own
+                        // deserialization error.
+                        _ => Vec::new(),
+                    };
+
+                    if !errors.is_empty() {
+                        let response =
+                            super::mcp_output_contract::compile_jsonrpc_error_with_data(
+                                super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                                format!(
+                                    "Arguments of {} do not match its input schema",
+                                    params.name
+                                )
+                                .as_str(),
+                                super::argument_errors_json(&errors).as_str(),
+                                id,
+                            );
+                        return send_response_as_stream(response, session_id, now, pending);
+                    }
+                }
+
+                // One snapshot, so the context never mixes two handshakes.
+                let capabilities = session_id
+                    .and_then(|session_id| self.sessions.session_capabilities(session_id));
+                let ctx = ToolCallContext::new(
+                    session_id.unwrap_or_default().to_string(),
+                    capabilities
+                        .as_ref()
+                        .is_some_and(|c| c.supports_elicitation),
+                    arguments.clone(),
+                    self.elicitations.clone(),
+                    self.sessions.clone(),
+                );
+                let ctx = match capabilities {
+                    Some(capabilities) => ctx.with_protocol_version(capabilities.protocol_version),
+                    None => ctx,
                 };
 
+                // Dry run: the arguments are checked, the tool never runs.
+                if params.meta.dry_run {
+                    let response = match tool_call.dry_run(&ctx).await {
+                        Some(Ok(())) => {
+                            super::mcp_output_contract::compile_dry_run_response(id)
+                        }
+                        Some(Err(
+                            McpToolCallError::InvalidParams(err) | McpToolCallError::Failed(err),
+                        )) => super::mcp_output_contract::compile_jsonrpc_error(
+                            super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                            err.as_str(),
+                            id,
+                        ),
+                        Some(Err(McpToolCallError::ServerError { code, message })) => {
+                            compile_server_error(&params.name, code, &message, id)
+                        }
+                        None => super::mcp_output_contract::compile_jsonrpc_error(
+                            super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                            format!("Tool {} does not support dry run", params.name).as_str(),
+                            id,
+                        ),
+                    };
+                    return send_response_as_stream(response, session_id, now, pending);
+                }
+
+                let id = id.clone();
+                let tool_name = params.name;
+                let progress_token = params.meta.progress_token;
+
+                let deadline = self.method_timeout(McpMethod::ToolsCall.as_str());
+                let scheduler = self.tool_call_scheduler.clone();
+                let structured = self.structured_content;
+                let redaction = self.audit_redaction.clone();
+                // Stateless requests share one queue.
+                let scheduled_for = session_id.unwrap_or_default().to_string();
+
+                let mut execute = Box::pin(within_timeout(deadline, async move {
+                    // Held while the tool runs.
+                    let _slot = match &scheduler {
+                        Some(scheduler) => Some(scheduler.acquire(scheduled_for.as_str()).await),
+                        None => None,
+                    };
+                    super::catch_panic(tool_call.execute(ctx)).await
+                }));
+
+                // Fast path: a client that takes a plain JSON body gets
+                // one when the result is ready within the window. A
+                // slower call is upgraded to the stream below.
+                if let (true, Some(window)) = (pending.json_response(), self.json_response_window) {
+                    if let Ok(result) = tokio::time::timeout(window, execute.as_mut()).await {
+                        let response = compile_tool_call_result(
+                            result,
+                            &tool_name,
+                            &arguments,
+                            &redaction,
+                            super::ToolCallResponseOptions {
+                                progress_token: progress_token.as_ref(),
+                                structured,
+                            },
+                            &id,
+                        );
+                        return send_response_as_stream(response, session_id, now, pending);
+                    }
+                }
+
                 // The SSE response stream opens immediately and emits
                 // keepalive comments while the tool runs, so proxies do
                 // not cut long calls (elicitation can wait on a human
@@ -477,101 +1937,183 @@ impl McpMiddleware {
                 // are the tool's responsibility.
                 let (http_output, mut producer) = HttpOutput::as_stream(32);
 
-                let id = id.clone();
-                let tool_name = params.name;
+                let mut pending = pending;
 
                 tokio::spawn(async move {
-                    let execute = tool_call.execute(arguments.as_str(), ctx);
-                    tokio::pin!(execute);
-
                     let mut keepalive = tokio::time::interval(super::KEEPALIVE_INTERVAL);
                     // interval()'s first tick fires immediately — skip it.
                     keepalive.tick().await;
 
-                    loop {
+                    let response = loop {
                         tokio::select! {
                             result = &mut execute => {
-                                let response = match result {
-                                    Ok(executed) => {
-                                        super::mcp_output_contract::compile_execute_tool_call_response(
-                                            executed.structured_json,
-                                            executed.instruction,
-                                            &id,
-                                            false,
-                                        )
-                                    }
-                                    Err(err) => {
-                                        eprintln!(
-                                            "Error executing {} with params {}. Err: {}",
-                                            tool_name, arguments, err
-                                        );
-                                        super::mcp_output_contract::compile_execute_tool_call_response(
-                                            err, None, &id, true,
-                                        )
-                                    }
-                                };
-
-                                let _ = producer.send(response.into_bytes()).await;
-                                return;
+                                break compile_tool_call_result(
+                                    result,
+                                    &tool_name,
+                                    &arguments,
+                                    &redaction,
+                                    super::ToolCallResponseOptions {
+                                        progress_token: progress_token.as_ref(),
+                                        structured,
+                                    },
+                                    &id,
+                                );
                             }
                             _ = keepalive.tick() => {
-                                if producer.send(b": keepalive\n\n".to_vec()).await.is_err() {
+                                if producer.send(super::KEEPALIVE_FRAME.to_vec()).await.is_err() {
                                     return;
                                 }
                             }
                         }
+                    };
+
+                    // Ordered mode: a result that is ready before the
+                    // responses of earlier requests waits for them,
+                    // with the keepalive still ticking.
+                    {
+                        let turn = pending.wait_for_turn();
+                        tokio::pin!(turn);
+
+                        loop {
+                            tokio::select! {
+                                _ = &mut turn => break,
+                                _ = keepalive.tick() => {
+                                    if producer.send(super::KEEPALIVE_FRAME.to_vec()).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
                     }
+
+                    pending.finish_request();
+                    pending.audit_response(response.as_str());
+                    let response = pending.split_lines(response);
+                    let _ = producer.send(response.into_bytes()).await;
                 });
 
+                // No session in stateless mode, and no header.
+                let http_output = match session_id {
+                    Some(session_id) => http_output.with_header(SESSION_HEADER, session_id),
+                    None => http_output,
+                };
+
                 return http_output
-                    .with_header(SESSION_HEADER, session_id)
                     .with_header("cache-control", "no-cache")
-                    .with_header("content-type", "text/event-stream")
+                    .with_header("content-type", super::FRAME_CONTENT_TYPE)
                     .with_header("date", now.to_rfc7231())
                     .get_result();
             }
 
-            super::McpInputData::ToolsList => {
-                let list = self.tool_calls.get_list().await;
-                let response = super::mcp_output_contract::compile_tool_calls(list, id);
-
-                return send_response_as_stream(response, session_id, now);
+            super::McpInputData::ToolsList(params) => {
+                let protocol_version = session_id
+                    .and_then(|session_id| self.sessions.session_protocol_version(session_id));
+                let response = self
+                    .compile_tools_list(id, protocol_version.as_deref(), &params)
+                    .await;
+                return match response {
+                    Ok(response) => send_response_as_stream(response, session_id, now, pending),
+                    Err(err) => send_jsonrpc_error_as_stream(
+                        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                        err.as_str(),
+                        id,
+                        session_id,
+                        now,
+                        pending,
+                    ),
+                };
             }
 
-            super::McpInputData::PromptsList => {
-                let list = self.prompts.get_list();
-                let response = super::mcp_output_contract::compile_prompts_list(list, id);
-
-                return send_response_as_stream(response, session_id, now);
+            super::McpInputData::PromptsList(params) => {
+                let response = self.compile_prompts_list(id, &params).await;
+                return match response {
+                    Ok(response) => send_response_as_stream(response, session_id, now, pending),
+                    Err(err) => send_jsonrpc_error_as_stream(
+                        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                        err.as_str(),
+                        id,
+                        session_id,
+                        now,
+                        pending,
+                    ),
+                };
             }
 
             super::McpInputData::GetPrompt(params) => {
-                let arguments = match params.arguments {
+                let mut arguments = match params.arguments {
                     Some(args) => args,
                     None => Default::default(),
                 };
 
                 // Unknown prompt name → protocol-level Invalid params.
-                let Some(prompt) = self.prompts.get(&params.name) else {
+                // Resolved from a snapshot, as the list is.
+                let prompts = self.prompts.read().await.clone();
+                let Some(prompt) = prompts.resolve(&params.name).await else {
                     return send_jsonrpc_error_as_stream(
                         super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
                         format!("Unknown prompt: {}", params.name).as_str(),
                         id,
                         session_id,
                         now,
+                        pending,
+                    );
+                };
+
+                let descriptions = prompt.get_argument_descriptions();
+                let checked = super::apply_prompt_argument_defaults(&descriptions, &mut arguments)
+                    .and_then(|_| super::check_prompt_argument_sizes(&descriptions, &arguments));
+                if let Err(err) = checked {
+                    return send_jsonrpc_error_as_stream(
+                        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                        err.as_str(),
+                        id,
+                        session_id,
+                        now,
+                        pending,
                     );
+                }
+
+                let execute = super::catch_panic(prompt.execute(&arguments));
+                let deadline = self.method_timeout(McpMethod::PromptsGet.as_str());
+                let execute = within_timeout(deadline, execute);
+
+                let executed = match execute.await {
+                    Ok(Ok(executed)) => executed,
+                    Ok(Err(panic)) => {
+                        return send_jsonrpc_error_as_stream(
+                            super::mcp_output_contract::JSONRPC_INTERNAL_ERROR,
+                            handler_panicked(&McpMethod::PromptsGet, &params.name, &panic).as_str(),
+                            id,
+                            session_id,
+                            now,
+                            pending,
+                        );
+                    }
+                    Err(timeout) => {
+                        return send_jsonrpc_error_as_stream(
+                            super::mcp_output_contract::JSONRPC_REQUEST_TIMEOUT,
+                            timed_out_message(&McpMethod::PromptsGet, timeout).as_str(),
+                            id,
+                            session_id,
+                            now,
+                            pending,
+                        );
+                    }
                 };
 
-                match prompt.execute(&arguments).await {
+                match executed {
                     Ok(response) => {
-                        let response =
-                            super::mcp_output_contract::compile_get_prompt_response(response, id);
-                        return send_response_as_stream(response, session_id, now);
+                        let parts = super::mcp_output_contract::compile_get_prompt_response_parts(
+                            response, id,
+                        );
+                        return send_parts_as_stream(parts, session_id, now, pending);
                     }
                     Err(err) => {
                         eprintln!(
-                            "Error executing prompt {} with params {:?}. Err: {}",
-                            params.name, arguments, err
+                            "Error executing prompt {} with params {}. Err: {}",
+                            params.name,
+                            super::prompt_arguments_key(&arguments),
+                            err
                         );
 
                         return send_jsonrpc_error_as_stream(
@@ -580,20 +2122,88 @@ impl McpMiddleware {
                             id,
                             session_id,
                             now,
+                            pending,
                         );
                     }
                 }
             }
 
+            super::McpInputData::Complete(params) => {
+                let Some(provider) = self.completion_provider.as_ref() else {
+                    // Without a provider the capability is not
+                    // advertised: answer as capability gating would.
+                    let err = self
+                        .server_capabilities()
+                        .await
+                        .allows(&McpMethod::CompletionComplete)
+                        .err()
+                        .unwrap_or_default();
+                    return send_jsonrpc_error_as_stream(
+                        super::mcp_output_contract::JSONRPC_METHOD_NOT_FOUND,
+                        err.as_str(),
+                        id,
+                        session_id,
+                        now,
+                        pending,
+                    );
+                };
+
+                let complete = super::catch_panic(provider.complete(
+                    &params.reference,
+                    params.argument.name.as_str(),
+                    params.argument.value.as_str(),
+                ));
+                let deadline = self.method_timeout(McpMethod::CompletionComplete.as_str());
+
+                let (code, message) = match within_timeout(deadline, complete).await {
+                    Ok(Ok(Ok(completion))) => {
+                        let response = super::mcp_output_contract::compile_completion_response(
+                            &completion,
+                            id,
+                        );
+                        return send_response_as_stream(response, session_id, now, pending);
+                    }
+                    Ok(Ok(Err(err))) => (super::mcp_output_contract::JSONRPC_INVALID_PARAMS, err),
+                    Ok(Err(panic)) => (
+                        super::mcp_output_contract::JSONRPC_INTERNAL_ERROR,
+                        handler_panicked(
+                            &McpMethod::CompletionComplete,
+                            params.argument.name.as_str(),
+                            &panic,
+                        ),
+                    ),
+                    Err(timeout) => (
+                        super::mcp_output_contract::JSONRPC_REQUEST_TIMEOUT,
+                        timed_out_message(&McpMethod::CompletionComplete, timeout),
+                    ),
+                };
+
+                return send_jsonrpc_error_as_stream(
+                    code,
+                    message.as_str(),
+                    id,
+                    session_id,
+                    now,
+                    pending,
+                );
+            }
+
             super::McpInputData::NotificationsInitialize => {
+                // A repeated `initialized` changes nothing.
+                if let Some(session_id) = session_id {
+                    self.sessions.mark_initialized(session_id);
+                }
                 return accepted_response(now);
             }
 
-            super::McpInputData::Notification { method: _ } => {
+            super::McpInputData::Notification { method } => {
                 // Per the Streamable HTTP transport every accepted
                 // notification gets 202; ones we have no handler for
                 // (notifications/cancelled, roots/list_changed, ...)
                 // are simply ignored.
+                if !super::KNOWN_CLIENT_NOTIFICATIONS.contains(&method.as_str()) {
+                    self.on_unknown_notification(session_id, method.as_str());
+                }
                 return accepted_response(now);
             }
 
@@ -605,41 +2215,98 @@ impl McpMiddleware {
                     result_json.as_deref(),
                     error_json.as_deref(),
                 );
-                if let Some(request_id) = id.as_int() {
-                    self.elicitations.resolve(request_id, response);
+                let resolved = match id.as_int() {
+                    Some(request_id) => self.elicitations.resolve(request_id, response),
+                    None => false,
+                };
+
+                // A late reply to a timed-out elicitation, a duplicate, a
+                // confused client: nothing waits for it, and a response
+                // can not be answered with an error.
+                if !resolved {
+                    eprintln!(
+                        "Response with id {:?} matches no pending server request. Ignored",
+                        id
+                    );
                 }
                 return accepted_response(now);
             }
 
-            super::McpInputData::Other { method, data } => {
-                eprintln!("Unsupported MCP method: {}. Data: `{}`", method, data);
+            super::McpInputData::InvalidParams { method: _, message } => {
+                if id.is_null() {
+                    return accepted_response(now);
+                }
+
+                return send_jsonrpc_error_as_stream(
+                    super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                    message.as_str(),
+                    id,
+                    session_id,
+                    now,
+                    pending,
+                );
+            }
 
+            super::McpInputData::Other { method, data } => {
                 // Requests (id present) get a JSON-RPC error; id-less
                 // inputs are notifications by definition → 202.
                 if id.is_null() {
+                    self.on_unknown_notification(session_id, method.as_str());
                     return accepted_response(now);
                 }
 
+                eprintln!("Unsupported MCP method: {}. Data: `{}`", method, data);
+
                 return send_jsonrpc_error_as_stream(
                     super::mcp_output_contract::JSONRPC_METHOD_NOT_FOUND,
                     format!("Method not found: {}", method).as_str(),
                     id,
                     session_id,
                     now,
+                    pending,
                 );
             }
         }
     }
 
+    /// Header-less entry point the unit tests drive the middleware by.
+    #[cfg(test)]
     async fn handle_post_request(
         &self,
         session_id: Option<&str>,
         body: &[u8],
-        mut ctx: Option<&mut HttpContext>,
+        ctx: Option<&mut HttpContext>,
     ) -> Result<HttpOkResult, HttpFailResult> {
-        let now = DateTimeAsMicroseconds::now();
+        self.handle_post_request_with_headers(
+            session_id,
+            &McpRequestHeaders::default(),
+            body,
+            ctx,
+        )
+        .await
+    }
 
-        let payload = match super::McpInputPayload::try_parse(body) {
+    async fn handle_post_request_with_headers(
+        &self,
+        session_id: Option<&str>,
+        headers: &McpRequestHeaders,
+        body: &[u8],
+        mut ctx: Option<&mut HttpContext>,
+    ) -> Result<HttpOkResult, HttpFailResult> {
+        let now = self.clock.now();
+
+        // Recorded when the response is written; dropped unused, it
+        // records an exchange without one.
+        let mut audit = self.audit_sink.as_ref().map(|sink| {
+            AuditTrail::new(
+                sink.clone(),
+                session_id.unwrap_or_default(),
+                body,
+                self.audit_redaction.clone(),
+            )
+        });
+
+        let payload = match super::McpInputPayload::parse_untrusted(body) {
             Ok(payload) => payload,
             Err(err) => {
                 // Malformed JSON-RPC → HTTP 400 with a standard Parse
@@ -649,6 +2316,9 @@ impl McpMiddleware {
                     format!("Parse error: {}", err).as_str(),
                     &RequestId::Null,
                 );
+                if let Some(audit) = audit {
+                    audit.record(body.clone());
+                }
                 return HttpOutput::from_builder()
                     .set_content(body.into_bytes())
                     .set_content_type(WebContentType::Json)
@@ -663,10 +2333,32 @@ impl McpMiddleware {
         // `initialize` is valid both with and without a session header —
         // a stale header must not block a client from re-initializing.
         if let super::McpInputData::Initialize(contract) = data {
-            return self.handle_initialize(contract, now, &id, ctx).await;
+            let pending = PendingResponse::unordered().with_audit(audit);
+            return self.handle_initialize(contract, now, &id, pending, ctx).await;
+        }
+
+        if self.stateless {
+            return self
+                .handle_authorized_request(
+                    None,
+                    data,
+                    now,
+                    &id,
+                    PendingResponse::unordered().with_audit(audit),
+                    ctx,
+                )
+                .await;
         }
 
         let Some(session_id) = session_id else {
+            // `initialized` before `initialize`: there is no session to
+            // mark ready yet, and a notification can not be answered
+            // with an error — log it and move on.
+            if let super::McpInputData::NotificationsInitialize = data {
+                eprintln!("notifications/initialized received before initialize. Ignored");
+                return accepted_response(now);
+            }
+
             // Spec: every non-initialize request must carry the session
             // header once the server has issued one.
             return Err(HttpFailResult::as_validation_error(
@@ -674,6 +2366,10 @@ impl McpMiddleware {
             ));
         };
 
+        if session_id.is_empty() {
+            return Err(empty_session_header());
+        }
+
         if !self
             .sessions
             .check_session_and_update_last_used(session_id, now)
@@ -704,9 +2400,247 @@ impl McpMiddleware {
             }
         }
 
-        self.handle_authorized_request(session_id, data, now, &id, ctx)
+        // Only the client's requests are tracked and ordered;
+        // notifications and replies to our own requests have nothing
+        // to write back.
+        let is_request =
+            !id.is_null() && !matches!(data, super::McpInputData::ServerResponse { .. });
+
+        let in_flight = if is_request && self.duplicate_request_id_check {
+            let Some(in_flight) = self.sessions.begin_request(session_id, &id) else {
+                return send_jsonrpc_error_as_stream(
+                    super::mcp_output_contract::JSONRPC_INVALID_REQUEST,
+                    "Request id is already in use by a pending request",
+                    &id,
+                    Some(session_id),
+                    now,
+                    PendingResponse::unordered().with_audit(audit.take()),
+                );
+            };
+            Some(in_flight)
+        } else {
+            None
+        };
+
+        let compression = match self.compression_threshold {
+            Some(threshold) => headers
+                .preferred_encoding()
+                .map(|encoding| (encoding, threshold)),
+            None => None,
+        };
+
+        let pending = if is_request && self.ordered_responses {
+            self.sessions.take_response_turn(session_id)
+        } else {
+            PendingResponse::unordered()
+        }
+        .with_in_flight(in_flight)
+        .with_compression(compression)
+        .with_json_response(self.json_response_window.is_some() && headers.accepts_json())
+        .with_max_line(self.max_sse_line_size)
+        .with_audit(audit);
+
+        self.handle_authorized_request(Some(session_id), data, now, &id, pending, ctx)
+            .await
+    }
+}
+
+/// Resources and contents without a MIME type go out with a default
+/// one (see [`super::ResourceContent::effective_mime_type`]); the
+/// handler most likely forgot it.
+fn warn_on_missing_mime_type(uri: &str, mime_type: &str) {
+    if mime_type.is_empty() {
+        eprintln!(
+            "MCP resource {} has no MIME type. A default one is sent instead",
+            uri
+        );
+    }
+}
+
+/// The object text inside `arguments` when they are a JSON string
+/// holding a JSON object. See
+/// [`McpMiddleware::with_string_arguments_coercion`].
+fn unwrap_string_arguments(arguments: &str) -> Option<String> {
+    if !arguments.trim_start().starts_with('"') {
+        return None;
+    }
+
+    let inner: String = serde_json::from_str(arguments).ok()?;
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&inner).ok()?;
+
+    Some(inner)
+}
+
+/// The framed `-32602` response to a list request for `id`.
+fn invalid_params_response(message: &str, id: &RequestId) -> String {
+    super::mcp_output_contract::compile_jsonrpc_error(
+        super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+        message,
+        id,
+    )
+}
+
+/// One page of `list` for `tools/list` or `prompts/list`: at most
+/// `page_size` items, starting after the one named `cursor`, and the
+/// cursor of the next page — the name of the last item on this one. A
+/// cursor naming no item is an `Err`.
+fn page_by_name<T>(
+    list: Vec<T>,
+    name_of: impl Fn(&T) -> &str,
+    cursor: Option<&str>,
+    page_size: usize,
+) -> Result<(Vec<T>, Option<String>), String> {
+    let start = match cursor {
+        Some(cursor) => match list.iter().position(|item| name_of(item) == cursor) {
+            Some(position) => position + 1,
+            None => return Err(format!("Invalid cursor: {}", cursor)),
+        },
+        None => 0,
+    };
+
+    let end = (start + page_size.max(1)).min(list.len());
+    let next_cursor = if end < list.len() {
+        Some(name_of(&list[end - 1]).to_string())
+    } else {
+        None
+    };
+
+    let page = list.into_iter().skip(start).take(end - start).collect();
+    Ok((page, next_cursor))
+}
+
+/// Runs `work` under `deadline`, if there is one. `Err` carries the
+/// deadline that passed; the work is dropped at that point.
+async fn within_timeout<T>(
+    deadline: Option<Duration>,
+    work: impl std::future::Future<Output = T>,
+) -> Result<T, Duration> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(deadline, work)
             .await
+            .map_err(|_| deadline),
+        None => Ok(work.await),
+    }
+}
+
+fn timed_out_message(method: &McpMethod, timeout: Duration) -> String {
+    format!("{} timed out after {:?}", method, timeout)
+}
+
+/// Logs a handler panic and returns the `-32603` message for the client.
+/// The panic text stays in the server log: it may carry internals.
+fn handler_panicked(method: &McpMethod, name: &str, panic: &str) -> String {
+    eprintln!("{} handler {} panicked: {}", method, name, panic);
+    format!("Internal error: the {} handler panicked", method)
+}
+
+/// The JSON-RPC response to a `tools/call`, from how its execution
+/// ended. `arguments` are logged through `redaction`. A result —
+/// failed or not — is written with `options`.
+fn compile_tool_call_result(
+    result: Result<Result<Result<ExecutedToolCall, McpToolCallError>, String>, Duration>,
+    tool_name: &str,
+    arguments: &str,
+    redaction: &AuditRedaction,
+    options: super::ToolCallResponseOptions,
+    id: &RequestId,
+) -> String {
+    match result {
+        Err(timeout) => {
+            eprintln!(
+                "Tool {} timed out after {:?} with params {}",
+                tool_name,
+                timeout,
+                redaction.apply_to_tool_arguments(arguments)
+            );
+            super::mcp_output_contract::compile_jsonrpc_error(
+                super::mcp_output_contract::JSONRPC_REQUEST_TIMEOUT,
+                timed_out_message(&McpMethod::ToolsCall, timeout).as_str(),
+                id,
+            )
+        }
+        Ok(Err(panic)) => super::mcp_output_contract::compile_jsonrpc_error(
+            super::mcp_output_contract::JSONRPC_INTERNAL_ERROR,
+            handler_panicked(&McpMethod::ToolsCall, tool_name, &panic).as_str(),
+            id,
+        ),
+        Ok(Ok(Ok(executed))) => super::ToolCallResult::from(executed).to_json_rpc(options, id),
+        // The tool ran and failed: a normal result with isError, so the
+        // model sees why.
+        Ok(Ok(Err(McpToolCallError::Failed(err)))) => {
+            eprintln!(
+                "Error executing {} with params {}. Err: {}",
+                tool_name,
+                redaction.apply_to_tool_arguments(arguments),
+                err
+            );
+            super::ToolCallResult::failed(err).to_json_rpc(options, id)
+        }
+        // The call never reached the tool: a protocol-level error, like
+        // an unknown name.
+        Ok(Ok(Err(McpToolCallError::InvalidParams(err)))) => {
+            super::mcp_output_contract::compile_jsonrpc_error(
+                super::mcp_output_contract::JSONRPC_INVALID_PARAMS,
+                err.as_str(),
+                id,
+            )
+        }
+        // An application error with the host's own code in the
+        // server-error range.
+        Ok(Ok(Err(McpToolCallError::ServerError { code, message }))) => {
+            compile_server_error(tool_name, code, &message, id)
+        }
+    }
+}
+
+/// The JSON-RPC error for a tool's [`McpToolCallError::ServerError`].
+/// A code built without [`McpToolCallError::server_error`] can still be
+/// off-range or reserved; that is a host bug, answered with `-32603`.
+fn compile_server_error(tool_name: &str, code: i64, message: &str, id: &RequestId) -> String {
+    if let Err(err) = super::mcp_output_contract::check_server_error_code(code) {
+        eprintln!("Tool {} returned an unusable error code. {}", tool_name, err);
+        return super::mcp_output_contract::compile_jsonrpc_error(
+            super::mcp_output_contract::JSONRPC_INTERNAL_ERROR,
+            message,
+            id,
+        );
     }
+
+    super::mcp_output_contract::compile_jsonrpc_error(code, message, id)
+}
+
+/// How a `resources/read` failed — decides between `-32002` and `-32603`.
+enum ResourceReadError {
+    NotFound(String),
+    Failed(String),
+}
+
+/// See [`McpMiddleware::with_json_resource_validation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JsonResourceCheck {
+    Off,
+    Validate,
+    Pretty,
+}
+
+/// Longest alias chain a `resources/read` follows. See
+/// [`McpMiddleware::disabled_resource_redirects`].
+const MAX_RESOURCE_REDIRECTS: usize = 8;
+
+/// `400` for an `mcp-session-id` header that is there but empty: no
+/// session has an empty id.
+fn empty_session_header() -> HttpFailResult {
+    HttpFailResult::as_validation_error("Empty mcp-session-id header")
+}
+
+/// `405` for the session-bound methods (GET stream, DELETE) in
+/// stateless mode.
+fn method_not_allowed_response() -> Result<HttpOkResult, HttpFailResult> {
+    HttpOutput::from_builder()
+        .add_header("date", DateTimeAsMicroseconds::now().to_rfc7231())
+        .add_header("allow", "POST")
+        .set_status_code(405)
+        .into_ok_result(false)
 }
 
 fn accepted_response(now: DateTimeAsMicroseconds) -> Result<HttpOkResult, HttpFailResult> {
@@ -720,29 +2654,101 @@ fn send_jsonrpc_error_as_stream(
     code: i64,
     message: &str,
     id: &RequestId,
-    session_id: &str,
+    session_id: Option<&str>,
     now: DateTimeAsMicroseconds,
+    pending: PendingResponse,
 ) -> Result<HttpOkResult, HttpFailResult> {
     let response = super::mcp_output_contract::compile_jsonrpc_error(code, message, id);
-    send_response_as_stream(response, session_id, now)
+    send_response_as_stream(response, session_id, now, pending)
 }
 
 fn send_response_as_stream(
     response: String,
-    session_id: &str,
+    session_id: Option<&str>,
     now: DateTimeAsMicroseconds,
+    mut pending: PendingResponse,
 ) -> Result<HttpOkResult, HttpFailResult> {
+    let (response, content_type) = if pending.json_response() {
+        (super::unframe(response), "application/json")
+    } else {
+        (response, super::FRAME_CONTENT_TYPE)
+    };
+
+    // A single-frame stream is compressed as a whole: the frame is
+    // complete before the first byte goes out.
+    let encoding = pending.encoding_for(response.len());
+
     let (http_output, mut producer) = HttpOutput::as_stream(1024);
     tokio::spawn(async move {
-        let payload = response.into_bytes();
+        pending.wait_for_turn().await;
+        pending.finish_request();
+        pending.audit_response(response.as_str());
+        // A plain JSON body has no `data:` line and is left alone.
+        let response = pending.split_lines(response);
+        let payload = match encoding {
+            Some(encoding) => encoding.encode(response.as_bytes()),
+            None => response.into_bytes(),
+        };
         // Client may disconnect before reading the response — nothing to do.
         let _ = producer.send(payload).await;
     });
 
+    // No session in stateless mode, and no header.
+    let http_output = match session_id {
+        Some(session_id) => http_output.with_header(SESSION_HEADER, session_id),
+        None => http_output,
+    };
+
+    let http_output = http_output
+        .with_header("cache-control", "no-cache")
+        .with_header("content-type", content_type)
+        .with_header("date", now.to_rfc7231());
+
+    match encoding {
+        Some(encoding) => http_output
+            .with_header("content-encoding", encoding.as_str())
+            .with_header("vary", "accept-encoding")
+            .get_result(),
+        None => http_output.get_result(),
+    }
+}
+
+/// Writes a response that comes in parts (see
+/// [`super::compile_get_prompt_response_parts`]) one part at a time, so
+/// the serialized response is never held whole. A response that must
+/// be handled whole — compressed, line-split, sent as plain JSON or
+/// audited — is joined and sent by [`send_response_as_stream`] instead.
+fn send_parts_as_stream(
+    parts: impl Iterator<Item = String> + Send + 'static,
+    session_id: Option<&str>,
+    now: DateTimeAsMicroseconds,
+    mut pending: PendingResponse,
+) -> Result<HttpOkResult, HttpFailResult> {
+    if pending.needs_whole_response() {
+        return send_response_as_stream(parts.collect(), session_id, now, pending);
+    }
+
+    let (http_output, mut producer) = HttpOutput::as_stream(1024);
+    tokio::spawn(async move {
+        pending.wait_for_turn().await;
+        pending.finish_request();
+        for part in parts {
+            // Client disconnected — the rest has nowhere to go.
+            if producer.send(part.into_bytes()).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // No session in stateless mode, and no header.
+    let http_output = match session_id {
+        Some(session_id) => http_output.with_header(SESSION_HEADER, session_id),
+        None => http_output,
+    };
+
     http_output
-        .with_header(SESSION_HEADER, session_id)
         .with_header("cache-control", "no-cache")
-        .with_header("content-type", "text/event-stream")
+        .with_header("content-type", super::FRAME_CONTENT_TYPE)
         .with_header("date", now.to_rfc7231())
         .get_result()
 }
@@ -761,11 +2767,8 @@ impl HttpServerMiddleware for McpMiddleware {
             return None;
         }
 
-        // Lazy GC start: handle_request always runs inside the tokio
-        // runtime, which `new()` can not guarantee.
-        if !self.gc_started.swap(true, Ordering::Relaxed) {
-            super::spawn_session_gc(Arc::downgrade(&self.sessions), self.session_idle_timeout);
-        }
+        // handle_request always runs inside the tokio runtime.
+        self.ensure_gc_started();
 
         let session_id = ctx
             .request
@@ -774,6 +2777,9 @@ impl HttpServerMiddleware for McpMiddleware {
             .and_then(|itm| itm.as_str().ok().map(|s| s.to_string()));
 
         match ctx.request.method {
+            Method::GET | Method::DELETE if self.stateless => {
+                return Some(method_not_allowed_response());
+            }
             Method::GET => {
                 let Some(session_id) = session_id else {
                     return Some(
@@ -782,7 +2788,11 @@ impl HttpServerMiddleware for McpMiddleware {
                     );
                 };
 
-                let now = DateTimeAsMicroseconds::now();
+                if session_id.is_empty() {
+                    return Some(empty_session_header().into_err());
+                }
+
+                let now = self.clock.now();
 
                 if let Some(receiver) = self
                     .sessions
@@ -798,7 +2808,7 @@ impl HttpServerMiddleware for McpMiddleware {
 
                     return Some(
                         stream
-                            .with_header("content-type", "text/event-stream")
+                            .with_header("content-type", super::FRAME_CONTENT_TYPE)
                             .with_header("cache-control", "no-cache")
                             .with_header("date", now.to_rfc7231())
                             .get_result(),
@@ -815,7 +2825,13 @@ impl HttpServerMiddleware for McpMiddleware {
                 // reference returned by `get_body()` is alive — so for
                 // listening hosts the body is copied once. With no hook
                 // the zero-copy path is untouched.
-                if self.sessions.has_connection_info() {
+                // The same goes for a request filter, which looks at
+                // the body and the context together.
+                let headers = McpRequestHeaders::from_http_context(ctx);
+
+                let has_connection_info = self.sessions.has_connection_info();
+
+                if has_connection_info || self.request_filter.is_some() {
                     let body = match ctx.request.get_body().await {
                         Ok(body) => body.as_slice().to_vec(),
                         Err(err) => {
@@ -823,8 +2839,19 @@ impl HttpServerMiddleware for McpMiddleware {
                         }
                     };
 
+                    if let Some(filter) = &self.request_filter {
+                        if let Some(result) = filter.filter(ctx, body.as_slice()).await {
+                            return Some(result);
+                        }
+                    }
+
                     let result = self
-                        .handle_post_request(session_id.as_deref(), body.as_slice(), Some(ctx))
+                        .handle_post_request_with_headers(
+                            session_id.as_deref(),
+                            &headers,
+                            body.as_slice(),
+                            if has_connection_info { Some(ctx) } else { None },
+                        )
                         .await;
                     return Some(result);
                 }
@@ -837,7 +2864,12 @@ impl HttpServerMiddleware for McpMiddleware {
                 };
 
                 let result = self
-                    .handle_post_request(session_id.as_deref(), body.as_slice(), None)
+                    .handle_post_request_with_headers(
+                        session_id.as_deref(),
+                        &headers,
+                        body.as_slice(),
+                        None,
+                    )
                     .await;
                 return Some(result);
             }
@@ -849,6 +2881,10 @@ impl HttpServerMiddleware for McpMiddleware {
                     );
                 };
 
+                if session_id.is_empty() {
+                    return Some(empty_session_header().into_err());
+                }
+
                 let removed = self.sessions.delete_session(session_id.as_str()).await;
 
                 if !removed {
@@ -857,7 +2893,7 @@ impl HttpServerMiddleware for McpMiddleware {
                     );
                 }
 
-                let now = DateTimeAsMicroseconds::now();
+                let now = self.clock.now();
                 return Some(
                     HttpOutput::from_builder()
                         .add_header("date", now.to_rfc7231())
@@ -876,7 +2912,10 @@ impl HttpServerMiddleware for McpMiddleware {
 mod tests {
     use super::*;
     use crate::ToolDefinition;
-    use crate::mcp_middleware::{McpSession, McpToolCall};
+    use crate::mcp_middleware::{
+        ExecutedToolCall, McpPromptAbstract, McpSession, McpToolCall, McpToolCallAbstract, PromptArgumentDescription, PromptExecutionResult,
+        ResourceContent, ResourceReadResult, SessionCapabilities, VoidOutput,
+    };
     use my_ai_agent::json_schema::JsonTypeDescription;
 
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -928,9 +2967,24 @@ mod tests {
         }
     }
 
+    struct FailingTool;
+
+    impl ToolDefinition for FailingTool {
+        const FUNC_NAME: &'static str = "fail";
+        const DESCRIPTION: &'static str = "Always fails";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, EchoOutput> for FailingTool {
+        async fn execute_tool_call(&self, _model: EchoInput) -> Result<EchoOutput, String> {
+            Err("database is down".to_string())
+        }
+    }
+
     fn middleware_with_echo_tool() -> McpMiddleware {
         let mut mcp = McpMiddleware::new("/mcp", "test-server", "0.0.1", "test instructions");
         mcp.register_tool_call(Arc::new(EchoTool));
+        mcp.register_tool_call(Arc::new(FailingTool));
         mcp
     }
 
@@ -987,6 +3041,22 @@ mod tests {
         }
     }
 
+    /// Posts `body` and parses the framed JSON-RPC response, as
+    /// `(status, response, session id)`.
+    async fn post_json(
+        mcp: &McpMiddleware,
+        session_id: Option<&str>,
+        body: &[u8],
+    ) -> (u16, serde_json::Value, Option<String>) {
+        let result = mcp.handle_post_request(session_id, body, None).await;
+        let (status, body, session_id) = read_sse_response(result).await;
+        let json = body
+            .trim()
+            .strip_prefix(crate::mcp_middleware::FRAME_PREFIX)
+            .expect("framed response");
+        (status, serde_json::from_str(json).expect("valid json"), session_id)
+    }
+
     async fn initialize_session(mcp: &McpMiddleware) -> String {
         let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
         let result = mcp.handle_post_request(None, body, None).await;
@@ -995,6 +3065,61 @@ mod tests {
         session_id.expect("initialize must return mcp-session-id header")
     }
 
+    #[tokio::test]
+    async fn a_non_semver_version_is_warned_about_but_served() {
+        // `new` logs exactly this warning.
+        assert!(crate::mcp_middleware::non_semver_version_warning("build-42").is_some());
+
+        let mcp = McpMiddleware::new("/mcp", "test-server", "build-42", "test instructions");
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""version":"build-42""#), "{}", body);
+
+        let mcp = mcp.with_version(ServerVersion::parse("1.4.0-rc.1").unwrap());
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""version":"1.4.0-rc.1""#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn initialize_sends_capped_instructions() {
+        let mcp = McpMiddleware::new(
+            "/mcp",
+            "test-server",
+            "0.0.1",
+            "Call search before read. Keep answers short. Cite the file you used.",
+        )
+        .with_max_instructions_len(60);
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(
+            body.contains(r#""instructions":"Call search before read. [Instructions truncated.]""#),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn the_instructions_cap_covers_the_capability_guidance() {
+        let mcp = McpMiddleware::new("/mcp", "test-server", "0.0.1", "Call search before read.")
+            .with_capability_instructions()
+            .with_max_instructions_len(60);
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(
+            body.contains(r#""instructions":"Call search before read. [Instructions truncated.]""#),
+            "{}",
+            body
+        );
+    }
+
     #[tokio::test]
     async fn initialize_returns_session_and_capabilities() {
         let mcp = middleware_with_echo_tool();
@@ -1050,6 +3175,62 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn an_unknown_notification_is_counted() {
+        let counter = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let mcp = middleware_with_echo_tool().with_unknown_notification_policy(
+            crate::mcp_middleware::UnknownNotificationPolicy::Count(counter.clone()),
+        );
+        let session_id = initialize_session(&mcp).await;
+
+        for body in [
+            br#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#.as_slice(),
+            br#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":1}}"#
+                .as_slice(),
+            br#"{"jsonrpc":"2.0","method":"notifications/acme/typo"}"#.as_slice(),
+            br#"{"jsonrpc":"2.0","method":"acme/fire_and_forget"}"#.as_slice(),
+        ] {
+            let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+            let ok = result.expect("notification must be accepted");
+            assert_eq!(ok.output.get_status_code(), 202);
+        }
+
+        assert_eq!(counter.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+        // An unknown request is not a notification.
+        let body = br#"{"jsonrpc":"2.0","method":"acme/query","id":5}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32601"#), "{}", body);
+        assert_eq!(counter.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn a_stray_client_response_is_ignored() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+        let (pending_id, mut waiter) = mcp.elicitations.allocate();
+
+        for body in [
+            br#"{"jsonrpc":"2.0","id":999,"result":{"action":"accept"}}"#.as_slice(),
+            br#"{"jsonrpc":"2.0","id":"not-ours","error":{"code":-32000,"message":"x"}}"#
+                .as_slice(),
+            br#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error"}}"#
+                .as_slice(),
+        ] {
+            let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+            let ok = result.expect("a stray response must not fail the request");
+            assert_eq!(ok.output.get_status_code(), 202);
+        }
+
+        // The elicitation really pending is left alone.
+        assert!(waiter.try_recv().is_err());
+        assert!(
+            mcp.elicitations
+                .resolve(pending_id, parse_elicitation_response(None, None))
+        );
+    }
+
     #[tokio::test]
     async fn unknown_method_with_id_gets_method_not_found() {
         let mcp = middleware_with_echo_tool();
@@ -1076,6 +3257,19 @@ mod tests {
         assert_eq!(err.output.get_status_code(), 400);
     }
 
+    #[tokio::test]
+    async fn empty_session_header_is_400_and_adopts_nothing() {
+        let mcp = middleware_with_echo_tool();
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/list","id":1}"#;
+        let result = mcp.handle_post_request(Some(""), body, None).await;
+        let Err(err) = result else {
+            panic!("must be rejected");
+        };
+        assert_eq!(err.output.get_status_code(), 400);
+        assert!(mcp.get_sessions().is_empty());
+    }
+
     #[tokio::test]
     async fn unknown_session_is_404_when_lazy_creation_is_disabled() {
         let mcp = middleware_with_echo_tool().disabled_lazy_session_creation();
@@ -1142,6 +3336,32 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn trailing_junk_is_a_parse_error_but_a_trailing_newline_is_not() {
+        let mcp = middleware_with_echo_tool();
+
+        let body = b"{\"jsonrpc\":\"2.0\",\"method\":\"initialize\",\"id\":1,\"params\":{\"protocolVersion\":\"2025-06-18\",\"capabilities\":{}}}x";
+        let result = mcp.handle_post_request(None, body, None).await;
+        match result.expect("400 is returned as ok-result with JSON body").output {
+            HttpOutput::Content {
+                status_code,
+                content,
+                ..
+            } => {
+                assert_eq!(status_code, 400);
+                let body = String::from_utf8(content).unwrap();
+                assert!(body.contains(r#""code":-32700"#), "{}", body);
+            }
+            other => panic!("expected Content output, got {:?}", other),
+        }
+
+        let body = b"{\"jsonrpc\":\"2.0\",\"method\":\"initialize\",\"id\":1,\"params\":{\"protocolVersion\":\"2025-06-18\",\"capabilities\":{}}}\n";
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (status, _, session_id) = read_sse_response(result).await;
+        assert_eq!(status, 200);
+        assert!(session_id.is_some());
+    }
+
     #[tokio::test]
     async fn unknown_tool_gets_invalid_params() {
         let mcp = middleware_with_echo_tool();
@@ -1156,69 +3376,322 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn tool_call_without_arguments_succeeds_and_streams_result() {
+    async fn tool_call_with_array_params_is_invalid_params() {
         let mcp = middleware_with_echo_tool();
         let session_id = initialize_session(&mcp).await;
 
-        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":6,"params":{"name":"echo"}}"#;
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":5,"params":["echo",{"text":"hi"}]}"#;
         let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
         let (status, body, _) = read_sse_response(result).await;
 
         assert_eq!(status, 200);
-        assert!(body.contains(r#""isError":false"#));
-        assert!(body.contains(r#""echoed":"""#));
+        assert!(body.contains(r#""code":-32602"#), "{}", body);
+        assert!(body.contains(r#""id":5"#), "{}", body);
+        assert!(body.contains("Positional params"), "{}", body);
     }
 
     #[tokio::test]
-    async fn resource_templates_list_returns_empty_array() {
+    async fn tool_call_without_arguments_succeeds_and_streams_result() {
         let mcp = middleware_with_echo_tool();
         let session_id = initialize_session(&mcp).await;
 
-        let body = br#"{"jsonrpc":"2.0","method":"resources/templates/list","id":8}"#;
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":6,"params":{"name":"echo"}}"#;
         let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
-        let (_, body, _) = read_sse_response(result).await;
+        let (status, body, _) = read_sse_response(result).await;
 
-        assert!(body.contains(r#""resourceTemplates":[]"#));
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""isError":false"#));
+        assert!(body.contains(r#""echoed":"""#));
     }
 
     #[tokio::test]
-    async fn subscribe_unknown_resource_is_resource_not_found() {
+    async fn stringified_arguments_are_only_unwrapped_when_coercion_is_on() {
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":7,"params":{"name":"echo","arguments":"{\"text\":\"hi\"}"}}"#;
+
         let mcp = middleware_with_echo_tool();
         let session_id = initialize_session(&mcp).await;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, response, _) = read_sse_response(result).await;
+        assert!(response.contains(r#""code":-32602"#), "{}", response);
 
-        let body = br#"{"jsonrpc":"2.0","method":"resources/subscribe","id":9,"params":{"uri":"res://missing"}}"#;
+        let mcp = middleware_with_echo_tool().with_string_arguments_coercion();
+        let session_id = initialize_session(&mcp).await;
         let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
-        let (_, body, _) = read_sse_response(result).await;
+        let (_, response, _) = read_sse_response(result).await;
+        assert!(response.contains(r#""echoed":"hi""#), "{}", response);
+    }
 
-        assert!(body.contains(r#""code":-32002"#));
+    #[test]
+    fn only_a_string_holding_an_object_is_unwrapped() {
+        assert_eq!(
+            unwrap_string_arguments(r#""{\"n\":12345678901234567890}""#).as_deref(),
+            Some(r#"{"n":12345678901234567890}"#)
+        );
+        assert_eq!(unwrap_string_arguments(r#"{"text":"hi"}"#), None);
+        assert_eq!(unwrap_string_arguments(r#""[1,2]""#), None);
+        assert_eq!(unwrap_string_arguments(r#""plain text""#), None);
     }
 
     #[tokio::test]
-    async fn delete_reports_the_session_as_gone_exactly_once() {
-        let (mcp, recorder) = middleware_with_recorder();
+    async fn the_progress_token_comes_back_on_the_final_result() {
+        let mcp = middleware_with_echo_tool();
         let session_id = initialize_session(&mcp).await;
 
-        assert!(mcp.sessions.delete_session(session_id.as_str()).await);
-        assert_eq!(recorder.disconnected(), vec![session_id.clone()]);
-
-        // The session is gone — a repeated DELETE must not fire again.
-        assert!(!mcp.sessions.delete_session(session_id.as_str()).await);
-        assert_eq!(recorder.disconnected(), vec![session_id]);
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":6,"params":{"name":"echo","arguments":{"text":"hi"},"_meta":{"progressToken":"job-17"}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+        let result = &parsed["result"];
+        assert_eq!(result["_meta"]["progressToken"], "job-17");
+        assert_eq!(result["structuredContent"]["echoed"], "hi");
+
+        // Numbers stay numbers.
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":7,"params":{"name":"echo","arguments":{"text":"hi"},"_meta":{"progressToken":42}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+        let result = &parsed["result"];
+        assert_eq!(result["_meta"]["progressToken"], 42);
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":8,"params":{"name":"echo","arguments":{"text":"hi"}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+        let result = &parsed["result"];
+        assert!(result.get("_meta").is_none(), "{}", result);
     }
 
     #[tokio::test]
-    async fn deleting_an_unknown_session_reports_nothing() {
-        let (mcp, recorder) = middleware_with_recorder();
+    async fn stateless_mode_serves_tool_calls_without_initialize() {
+        let mcp = middleware_with_echo_tool().with_stateless_mode();
 
-        assert!(!mcp.sessions.delete_session("never-existed").await);
-        assert!(recorder.disconnected().is_empty());
-    }
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":6,"params":{"name":"echo","arguments":{"text":"hi"}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (status, body, session_id) = read_sse_response(result).await;
 
-    #[tokio::test]
-    async fn gc_reports_every_session_it_collects() {
-        let (mcp, recorder) = middleware_with_recorder();
-        let expired = initialize_session(&mcp).await;
-        let fresh = initialize_session(&mcp).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""echoed":"hi""#), "{}", body);
+        assert!(session_id.is_none());
+
+        // `initialize` is still answered, but mints no session.
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (status, body, session_id) = read_sse_response(result).await;
+
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""protocolVersion":"2025-06-18""#));
+        assert!(session_id.is_none());
+        assert!(mcp.get_sessions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn capability_gating_enforces_what_initialize_advertised() {
+        let init = r#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+
+        // Tools registered, no prompts.
+        let mcp = middleware_with_echo_tool()
+            .with_capability_gating()
+            .with_experimental_capability("batching", serde_json::json!({"maxSize": 8}));
+        let (_, parsed, session_id) = post_json(&mcp, None, init.as_bytes()).await;
+        let session_id = session_id.unwrap();
+        let capabilities = &parsed["result"]["capabilities"];
+        assert_eq!(capabilities["experimental"]["batching"]["maxSize"], 8);
+
+        let advertised = mcp.server_capabilities().await;
+        assert_eq!(
+            mcp.sessions.advertised_capabilities(session_id.as_str()),
+            Some(advertised.clone())
+        );
+
+        for (method, capability) in [("tools/list", "tools"), ("prompts/list", "prompts")] {
+            let body = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":2}}"#, method);
+            let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body.as_bytes()).await;
+
+            let is_advertised = capabilities.get(capability).is_some();
+            let is_allowed = advertised.allows(&McpMethod::parse(method)).is_ok();
+            assert_eq!(is_advertised, is_allowed, "{}", method);
+            assert_eq!(
+                parsed.get("error").is_none(),
+                is_allowed,
+                "{}: {}",
+                method,
+                parsed
+            );
+        }
+
+        let (_, parsed, _) = post_json(
+            &mcp,
+            Some(session_id.as_str()),
+            br#"{"jsonrpc":"2.0","method":"prompts/get","id":3,"params":{"name":"greet"}}"#,
+        )
+        .await;
+        assert_eq!(parsed["error"]["code"], -32601, "{}", parsed);
+
+        // Stateless mode advertises no subscriptions, and refuses them.
+        let mcp = middleware_with_echo_tool()
+            .with_stateless_mode()
+            .with_capability_gating();
+        let (_, parsed, _) = post_json(&mcp, None, init.as_bytes()).await;
+        assert_eq!(
+            parsed["result"]["capabilities"]["resources"]["subscribe"],
+            false
+        );
+
+        let (_, parsed, _) = post_json(
+            &mcp,
+            None,
+            br#"{"jsonrpc":"2.0","method":"resources/subscribe","id":4,"params":{"uri":"notes://1"}}"#,
+        )
+        .await;
+        assert_eq!(parsed["error"]["code"], -32601, "{}", parsed);
+    }
+
+    #[tokio::test]
+    async fn completions_are_advertised_and_served_only_with_a_provider() {
+        struct Cities;
+
+        #[async_trait::async_trait]
+        impl crate::mcp_middleware::McpCompletionProvider for Cities {
+            async fn complete(
+                &self,
+                reference: &crate::mcp_middleware::CompletionReference,
+                argument_name: &str,
+                value: &str,
+            ) -> Result<crate::mcp_middleware::CompletionValues, String> {
+                let crate::mcp_middleware::CompletionReference::Prompt { name } = reference else {
+                    return Err("Only prompts are completed".to_string());
+                };
+                assert_eq!(name, "weather");
+                assert_eq!(argument_name, "city");
+
+                let values = ["Paris", "Parma", "Prague"]
+                    .iter()
+                    .filter(|city| city.starts_with(value))
+                    .map(|city| city.to_string())
+                    .collect();
+                Ok(crate::mcp_middleware::CompletionValues::new(values))
+            }
+        }
+
+        let init = r#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let complete = r#"{"jsonrpc":"2.0","method":"completion/complete","id":2,"params":{"ref":{"type":"ref/prompt","name":"weather"},"argument":{"name":"city","value":"Par"}}}"#;
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_completion_provider(Arc::new(Cities));
+        let (_, parsed, session_id) = post_json(&mcp, None, init.as_bytes()).await;
+        let session_id = session_id.unwrap();
+        assert_eq!(
+            parsed["result"]["capabilities"]["completions"],
+            serde_json::json!({})
+        );
+
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), complete.as_bytes()).await;
+        assert_eq!(
+            parsed["result"]["completion"],
+            serde_json::json!({"values": ["Paris", "Parma"], "hasMore": false})
+        );
+
+        // A reference the provider does not complete.
+        let (_, parsed, _) = post_json(
+            &mcp,
+            Some(session_id.as_str()),
+            br#"{"jsonrpc":"2.0","method":"completion/complete","id":3,"params":{"ref":{"type":"ref/resource","uri":"file:///{path}"},"argument":{"name":"path","value":""}}}"#,
+        )
+        .await;
+        assert_eq!(parsed["error"]["code"], -32602, "{}", parsed);
+
+        // No provider: not advertised, and not answered.
+        let mcp = middleware_with_echo_tool();
+        let (_, parsed, session_id) = post_json(&mcp, None, init.as_bytes()).await;
+        let session_id = session_id.unwrap();
+        assert!(
+            parsed["result"]["capabilities"]
+                .get("completions")
+                .is_none()
+        );
+
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), complete.as_bytes()).await;
+        assert_eq!(parsed["error"]["code"], -32601, "{}", parsed);
+        assert_eq!(
+            parsed["error"]["message"],
+            "Method not supported: completion/complete needs the completions capability, which this server does not advertise"
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_tool_is_an_is_error_result_not_a_protocol_error() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":10,"params":{"name":"fail","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""isError":true"#));
+        assert!(body.contains("database is down"));
+        assert!(!body.contains(r#""error":"#));
+    }
+
+    #[tokio::test]
+    async fn arguments_not_matching_the_input_type_are_invalid_params() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":11,"params":{"name":"echo","arguments":{"text":5}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""code":-32602"#));
+        assert!(body.contains(r#""id":11"#));
+        assert!(!body.contains(r#""isError""#));
+    }
+
+    #[tokio::test]
+    async fn resource_templates_list_returns_empty_array() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"resources/templates/list","id":8}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+
+        assert!(body.contains(r#""resourceTemplates":[]"#));
+    }
+
+    #[tokio::test]
+    async fn subscribe_unknown_resource_is_resource_not_found() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"resources/subscribe","id":9,"params":{"uri":"res://missing"}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+
+        assert!(body.contains(r#""code":-32002"#));
+    }
+
+    #[tokio::test]
+    async fn delete_reports_the_session_as_gone_exactly_once() {
+        let (mcp, recorder) = middleware_with_recorder();
+        let session_id = initialize_session(&mcp).await;
+
+        assert!(mcp.sessions.delete_session(session_id.as_str()).await);
+        assert_eq!(recorder.disconnected(), vec![session_id.clone()]);
+
+        // The session is gone — a repeated DELETE must not fire again.
+        assert!(!mcp.sessions.delete_session(session_id.as_str()).await);
+        assert_eq!(recorder.disconnected(), vec![session_id]);
+    }
+
+    #[tokio::test]
+    async fn deleting_an_unknown_session_reports_nothing() {
+        let (mcp, recorder) = middleware_with_recorder();
+
+        assert!(!mcp.sessions.delete_session("never-existed").await);
+        assert!(recorder.disconnected().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gc_reports_every_session_it_collects() {
+        let (mcp, recorder) = middleware_with_recorder();
+        let expired = initialize_session(&mcp).await;
+        let fresh = initialize_session(&mcp).await;
 
         let mut later = DateTimeAsMicroseconds::now();
         later.add_seconds(3600);
@@ -1338,6 +3811,132 @@ mod tests {
         assert!(only_session(&mcp).last_access.get_unix_microseconds() > before);
     }
 
+    #[tokio::test]
+    async fn ping_with_junk_params_gets_an_empty_result() {
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"ping","id":3,"params":{"unexpected":[1,{"x":null}],"cursor":42}}"#;
+        let (status, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+        assert_eq!(status, 200);
+        assert_eq!(parsed["id"], 3);
+        assert_eq!(parsed["result"], serde_json::json!({}));
+        assert!(parsed.get("error").is_none());
+    }
+
+    #[derive(Default)]
+    struct RecordingAuditSink {
+        exchanges: parking_lot::Mutex<Vec<crate::mcp_middleware::McpAuditedExchange>>,
+    }
+
+    impl McpAuditSink for RecordingAuditSink {
+        fn on_exchange(&self, exchange: &crate::mcp_middleware::McpAuditedExchange) {
+            self.exchanges.lock().push(exchange.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_sink_gets_the_redacted_request_and_the_response() {
+        let sink = Arc::new(RecordingAuditSink::default());
+        let mut mcp = middleware_with_echo_tool().with_audit_redaction("password");
+        mcp.register_audit_sink(sink.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo","arguments":{"text":"hi","password":"hunter2"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""echoed":"hi""#), "{}", body);
+
+        let body = br#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        mcp.handle_post_request(Some(session_id.as_str()), body, None)
+            .await
+            .unwrap();
+
+        let exchanges = sink.exchanges.lock().clone();
+        assert_eq!(exchanges.len(), 3, "{:?}", exchanges);
+
+        // initialize is audited under the session it created.
+        assert_eq!(exchanges[0].session_id, session_id);
+        assert!(exchanges[0].response.as_deref().unwrap().contains("protocolVersion"));
+
+        let call = &exchanges[1];
+        assert_eq!(call.session_id, session_id);
+        let request: serde_json::Value = serde_json::from_str(&call.request).unwrap();
+        assert_eq!(request["params"]["arguments"]["password"], "***");
+        assert_eq!(request["params"]["arguments"]["text"], "hi");
+        assert!(!call.request.contains("hunter2"));
+
+        let response: serde_json::Value =
+            serde_json::from_str(call.response.as_deref().unwrap()).expect("unframed json");
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"]["structuredContent"]["echoed"], "hi");
+
+        // A notification has nothing to answer with.
+        assert!(exchanges[2].request.contains("notifications/initialized"));
+        assert!(exchanges[2].response.is_none());
+    }
+
+    #[tokio::test]
+    async fn audit_redaction_takes_pointers_and_covers_the_response() {
+        let sink = Arc::new(RecordingAuditSink::default());
+        let mut mcp = middleware_with_echo_tool()
+            .with_audit_redaction("/params/arguments/token")
+            .with_audit_redaction("echoed");
+        mcp.register_audit_sink(sink.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo","arguments":{"text":"hi","token":"s3cr3t","meta":{"token":"kept"}}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""echoed":"hi""#), "{}", body);
+
+        let exchanges = sink.exchanges.lock().clone();
+        let call = &exchanges[1];
+
+        let request: serde_json::Value = serde_json::from_str(&call.request).unwrap();
+        assert_eq!(request["params"]["arguments"]["token"], "***");
+        assert_eq!(request["params"]["arguments"]["meta"]["token"], "kept");
+        assert!(!call.request.contains("s3cr3t"));
+
+        let response: serde_json::Value =
+            serde_json::from_str(call.response.as_deref().unwrap()).expect("unframed json");
+        assert_eq!(response["result"]["structuredContent"]["echoed"], "***");
+        assert_eq!(response["result"]["content"][0]["text"], r#"{"echoed":"***"}"#);
+    }
+
+    #[tokio::test]
+    async fn mock_clock_expires_a_session_without_sleeping() {
+        let clock = Arc::new(crate::mcp_middleware::MockClock::starting_now());
+        let mcp = middleware_with_echo_tool().with_clock(clock.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        // Stamped with the mock time, not the wall clock.
+        assert_eq!(
+            only_session(&mcp).create.unix_microseconds,
+            clock.now().unix_microseconds
+        );
+
+        // One second short of the idle timeout: the GC leaves it alone.
+        clock.advance(DEFAULT_SESSION_IDLE_TIMEOUT - Duration::from_secs(1));
+        assert_eq!(
+            mcp.sessions
+                .remove_idle_sessions(mcp.clock.now(), mcp.session_idle_timeout)
+                .await,
+            0
+        );
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(
+            mcp.sessions
+                .remove_idle_sessions(mcp.clock.now(), mcp.session_idle_timeout)
+                .await,
+            1
+        );
+        assert!(!mcp
+            .sessions
+            .check_session_and_update_last_used(session_id.as_str(), mcp.clock.now()));
+    }
+
     #[tokio::test]
     async fn get_sessions_reflects_deleted_and_collected_sessions() {
         let mcp = middleware_with_echo_tool();
@@ -1377,4 +3976,3007 @@ mod tests {
 
         assert!(body.contains(r#""id":"req-42""#));
     }
+
+    struct NoteResource;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for NoteResource {
+        async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+            Ok(ResourceReadResult { contents: vec![] })
+        }
+    }
+
+    #[tokio::test]
+    async fn capability_summary_counts_every_registry() {
+        let mcp = middleware_with_echo_tool();
+        let summary = mcp.get_capability_summary().await;
+        assert_eq!(summary.tools, 2);
+        assert_eq!(summary.prompts, 0);
+        assert_eq!(summary.resources, 0);
+        assert!(summary.has_tools());
+        assert!(!summary.has_resources());
+
+        for id in ["1", "2"] {
+            mcp.register_dynamic_resource(
+                format!("notes://{}", id),
+                format!("note-{}", id),
+                "A note".to_string(),
+                "text/plain".to_string(),
+                Arc::new(NoteResource),
+            )
+            .await;
+        }
+        assert_eq!(mcp.get_capability_summary().await.resources, 2);
+
+        assert!(mcp.unregister_dynamic_resource("notes://1").await);
+        assert_eq!(mcp.get_capability_summary().await.resources, 1);
+    }
+
+    /// A resource whose text the test changes at will.
+    #[derive(Default)]
+    struct EditableResource {
+        text: parking_lot::Mutex<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl McpResourceService for EditableResource {
+        async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            Ok(ResourceReadResult {
+                contents: vec![crate::mcp_middleware::ResourceContent::text(
+                    uri,
+                    "text/plain",
+                    self.text.lock().clone(),
+                )],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn resource_updated_names_the_uri_and_the_title() {
+        struct Changelog;
+
+        impl ResourceDefinition for Changelog {
+            const RESOURCE_URI: &'static str = "docs://changelog";
+            const RESOURCE_NAME: &'static str = "changelog";
+            const DESCRIPTION: &'static str = "What changed, release by release";
+            const MIME_TYPE: &'static str = "text/markdown";
+
+            fn get_title(&self) -> Option<&str> {
+                Some("Changelog")
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl McpResourceService for Changelog {
+            async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+                Ok(ResourceReadResult::builder()
+                    .add_text(uri, Self::MIME_TYPE, "# 1.0.0")
+                    .build())
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource(Arc::new(Changelog));
+        mcp.register_dynamic_resource(
+            "notes://untitled".to_string(),
+            "untitled".to_string(),
+            "A note without a title".to_string(),
+            "text/plain".to_string(),
+            Arc::new(NotesFolder),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+        let mut rx = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+        assert!(
+            mcp.sessions
+                .subscribe(session_id.as_str(), "docs://changelog".to_string())
+        );
+        assert!(
+            mcp.sessions
+                .subscribe(session_id.as_str(), "notes://untitled".to_string())
+        );
+
+        mcp.notify_resource_updated("docs://changelog").await;
+        match rx.try_recv() {
+            Ok(crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated { uri, title }) => {
+                assert_eq!(uri, "docs://changelog");
+                assert_eq!(title.as_deref(), Some("Changelog"));
+            }
+            other => panic!("expected ResourceUpdated, got {:?}", other),
+        }
+
+        mcp.notify_resource_updated("notes://untitled").await;
+        match rx.try_recv() {
+            Ok(crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated { uri, title }) => {
+                assert_eq!(uri, "notes://untitled");
+                assert!(title.is_none());
+            }
+            other => panic!("expected ResourceUpdated, got {:?}", other),
+        }
+
+        // A batch entry gets the title too.
+        mcp.notify_batch(
+            crate::mcp_middleware::McpNotificationBatch::new().resource_updated("docs://changelog"),
+        )
+        .await;
+        match rx.try_recv() {
+            Ok(crate::mcp_middleware::McpSocketUpdateEvent::Batch(events)) => {
+                assert!(matches!(
+                    events.as_slice(),
+                    [crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated {
+                        title: Some(title),
+                        ..
+                    }] if title == "Changelog"
+                ));
+            }
+            other => panic!("expected a batch, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn change_detection_only_notifies_changed_content() {
+        let resource = Arc::new(EditableResource::default());
+        *resource.text.lock() = "v1".to_string();
+
+        let mcp = middleware_with_echo_tool().with_resource_change_detection();
+        mcp.register_dynamic_resource(
+            "notes://live".to_string(),
+            "live".to_string(),
+            "A note that changes".to_string(),
+            "text/plain".to_string(),
+            resource.clone(),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+        let mut rx = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+        assert!(mcp.sessions.subscribe(session_id.as_str(), "notes://live".to_string()));
+
+        let updated = |rx: &mut tokio::sync::mpsc::Receiver<_>| match rx.try_recv() {
+            Ok(crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated { uri, .. }) => {
+                assert_eq!(uri, "notes://live");
+                true
+            }
+            Err(_) => false,
+            Ok(other) => panic!("unexpected event {:?}", other),
+        };
+
+        // Nothing was notified yet, so the first call always goes out.
+        mcp.notify_resource_updated("notes://live").await;
+        assert!(updated(&mut rx));
+
+        mcp.notify_resource_updated("notes://live").await;
+        assert!(!updated(&mut rx), "identical content must not be notified");
+
+        *resource.text.lock() = "v2".to_string();
+        mcp.notify_resource_updated("notes://live").await;
+        assert!(updated(&mut rx));
+
+        mcp.notify_resource_updated("notes://live").await;
+        assert!(!updated(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn change_detection_reads_within_the_resources_read_guards() {
+        struct PanickingResource;
+
+        #[async_trait::async_trait]
+        impl McpResourceService for PanickingResource {
+            async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+                panic!("the backing store is gone")
+            }
+        }
+
+        let mcp = middleware_with_echo_tool()
+            .with_resource_change_detection()
+            .with_method_timeout("resources/read", Duration::from_millis(50));
+        let resources: [(&str, Arc<dyn McpResourceService + Send + Sync>); 2] = [
+            ("notes://panics", Arc::new(PanickingResource)),
+            ("notes://slow", Arc::new(SlowResource)),
+        ];
+        for (uri, resource) in resources {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "Can not be hashed".to_string(),
+                "text/plain".to_string(),
+                resource,
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+        let mut rx = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+
+        for uri in ["notes://panics", "notes://slow"] {
+            assert!(mcp.sessions.subscribe(session_id.as_str(), uri.to_string()));
+
+            // Well within the 300ms the slow resource takes.
+            let notify = mcp.notify_resource_updated(uri);
+            tokio::time::timeout(Duration::from_millis(200), notify)
+                .await
+                .expect("the read is cut off at the resources/read timeout");
+
+            // A content that can not be hashed counts as changed.
+            match rx.try_recv() {
+                Ok(crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated {
+                    uri: updated,
+                    ..
+                }) => assert_eq!(updated, uri),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn tools_list_changed_is_only_sent_for_a_different_list() {
+        let mut mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+        let mut rx = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        read_sse_response(result).await;
+
+        // Same name, same description, same schemas: nothing to say.
+        mcp.register_tool_call(Arc::new(EchoTool));
+        mcp.notify_tools_changed().await;
+        assert!(rx.try_recv().is_err());
+
+        mcp.register_tool_call(Arc::new(SlowTool));
+        mcp.notify_tools_changed().await;
+        mcp.notify_tools_changed().await;
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(crate::mcp_middleware::McpSocketUpdateEvent::ToolsListChanged)
+        ));
+        assert!(rx.try_recv().is_err(), "a repeated call announces nothing");
+    }
+
+    #[tokio::test]
+    async fn reset_empties_every_list_and_announces_it() {
+        use futures_core::Stream;
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        mcp.register_dynamic_resource(
+            "notes://1".to_string(),
+            "note-1".to_string(),
+            "A note".to_string(),
+            "text/plain".to_string(),
+            Arc::new(NoteResource),
+        )
+        .await;
+        // Mounted: from here on only `&self` is at hand.
+        let mcp = Arc::new(mcp);
+        let session_id = initialize_session(&mcp).await;
+        let mut stream = mcp.open_session_stream(session_id.as_str()).unwrap();
+
+        tokio::spawn({
+            let mcp = mcp.clone();
+            async move { mcp.reset().await }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mcp.get_capability_summary().await,
+            McpCapabilitySummary {
+                tools: 0,
+                prompts: 0,
+                resources: 0,
+            }
+        );
+
+        let mut announced = Vec::new();
+        while announced.len() < 3 {
+            let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx))
+                .await
+                .expect("the stream stays open");
+            let frame = String::from_utf8(frame.into_bytes()).unwrap();
+            // Past the `retry:` preamble of SSE framing.
+            if frame.starts_with("retry: ") {
+                continue;
+            }
+            let notification: serde_json::Value =
+                serde_json::from_str(crate::mcp_middleware::unframe(frame).trim_end()).unwrap();
+            announced.push(notification["method"].as_str().unwrap().to_string());
+        }
+        assert_eq!(
+            announced,
+            [
+                "notifications/tools/list_changed",
+                "notifications/prompts/list_changed",
+                "notifications/resources/list_changed",
+            ]
+        );
+
+        for (method, key) in [
+            ("tools/list", "tools"),
+            ("prompts/list", "prompts"),
+            ("resources/list", "resources"),
+        ] {
+            let body = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":1}}"#, method);
+            let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body.as_bytes()).await;
+            assert_eq!(parsed["result"][key], serde_json::json!([]), "{}", method);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_unregistered_prompt_leaves_the_list() {
+        use futures_core::Stream;
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        // Mounted: a plugin unloads through the shared middleware.
+        let mcp = Arc::new(mcp);
+        let session_id = initialize_session(&mcp).await;
+        let mut stream = mcp.open_session_stream(session_id.as_str()).unwrap();
+
+        assert!(mcp.unregister_prompt("greet").await);
+        // Nothing left to remove, nothing to announce.
+        assert!(!mcp.unregister_prompt("greet").await);
+
+        let mut announced = Vec::new();
+        loop {
+            let next = std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx));
+            let Ok(Some(frame)) = tokio::time::timeout(Duration::from_millis(100), next).await
+            else {
+                break;
+            };
+            let frame = String::from_utf8(frame.into_bytes()).unwrap();
+            // Past the `retry:` preamble of SSE framing.
+            if frame.starts_with("retry: ") {
+                continue;
+            }
+            let notification: serde_json::Value =
+                serde_json::from_str(crate::mcp_middleware::unframe(frame).trim_end()).unwrap();
+            announced.push(notification["method"].as_str().unwrap().to_string());
+        }
+        assert_eq!(announced, ["notifications/prompts/list_changed"]);
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/list","id":2}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(!body.contains(r#""name":"greet""#), "{}", body);
+        assert!(body.contains(r#""name":"report""#), "{}", body);
+    }
+
+    /// Greets `name` in a `tone` that defaults to "friendly".
+    struct GreetPrompt;
+
+    impl PromptDefinition for GreetPrompt {
+        const PROMPT_NAME: &'static str = "greet";
+        const DESCRIPTION: &'static str = "Greets someone";
+
+        fn get_argument_descriptions() -> Vec<PromptArgumentDescription> {
+            vec![
+                PromptArgumentDescription {
+                    name: "name".to_string(),
+                    description: "Who to greet".to_string(),
+                    required: true,
+                    schema: None,
+                    default: None,
+                    max_size: Some(32),
+                },
+                PromptArgumentDescription {
+                    name: "tone".to_string(),
+                    description: "How to sound".to_string(),
+                    required: false,
+                    schema: None,
+                    default: Some("friendly".to_string()),
+                    max_size: None,
+                },
+            ]
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl McpPromptService for GreetPrompt {
+        async fn execute_prompt(
+            &self,
+            arguments: &std::collections::HashMap<String, String>,
+        ) -> Result<PromptExecutionResult, String> {
+            Ok(PromptExecutionResult {
+                description: "Greeting".to_string(),
+                message: format!(
+                    "Greet {} in a {} tone",
+                    arguments.get("name").map(String::as_str).unwrap_or("?"),
+                    arguments.get("tone").map(String::as_str).unwrap_or("?"),
+                ),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn an_optional_prompt_argument_omits_required_by_default() {
+        let arguments = |list: String| {
+            let parsed: serde_json::Value = serde_json::from_str(list.as_str()).unwrap();
+            parsed["result"]["prompts"][0]["arguments"].clone()
+        };
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let listed = arguments(mcp.list_prompts(&RequestId::Int(1)).await);
+        assert_eq!(listed[0]["name"], "name");
+        assert_eq!(listed[0]["required"], true);
+        assert_eq!(listed[1]["name"], "tone");
+        assert!(listed[1].get("required").is_none(), "{}", listed);
+
+        let mut mcp = middleware_with_echo_tool().with_explicit_required_false();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let listed = arguments(mcp.list_prompts(&RequestId::Int(1)).await);
+        assert_eq!(listed[0]["required"], true);
+        assert_eq!(listed[1]["required"], false);
+    }
+
+    #[tokio::test]
+    async fn omitted_prompt_argument_gets_its_default() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/get","id":1,"params":{"name":"greet","arguments":{"name":"Ann"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains("Greet Ann in a friendly tone"), "{}", body);
+
+        // A value the client does send wins over the default.
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/get","id":2,"params":{"name":"greet","arguments":{"name":"Ann","tone":"formal"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains("Greet Ann in a formal tone"), "{}", body);
+    }
+
+    /// Supplies `greet`, as if built from a stored template.
+    struct GreetProvider;
+
+    #[async_trait::async_trait]
+    impl DynamicPromptProvider for GreetProvider {
+        async fn list(&self) -> Vec<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
+            vec![Arc::new(PromptExecutor::from(Arc::new(GreetPrompt)))]
+        }
+
+        async fn get(
+            &self,
+            name: &str,
+        ) -> Option<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
+            match name {
+                "greet" => Some(Arc::new(PromptExecutor::from(Arc::new(GreetPrompt)))),
+                _ => None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_provided_prompt_is_listed_and_served() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        mcp.register_prompt_provider(Arc::new(GreetProvider));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/list","id":2}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""name":"report""#), "{}", body);
+        assert!(body.contains(r#""name":"greet""#), "{}", body);
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/get","id":3,"params":{"name":"greet","arguments":{"name":"Ann"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains("Greet Ann in a friendly tone"), "{}", body);
+
+        // Provided prompts are not part of the registered count.
+        assert_eq!(mcp.get_capability_summary().await.prompts, 1);
+    }
+
+    /// A provider that answers only once [`Self::release`] is notified.
+    #[derive(Default)]
+    struct StalledProvider {
+        release: tokio::sync::Notify,
+    }
+
+    #[async_trait::async_trait]
+    impl DynamicPromptProvider for StalledProvider {
+        async fn list(&self) -> Vec<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
+            self.release.notified().await;
+            vec![]
+        }
+
+        async fn get(
+            &self,
+            _name: &str,
+        ) -> Option<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
+            self.release.notified().await;
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stalled_provider_does_not_hold_the_prompt_registry() {
+        let provider = Arc::new(StalledProvider::default());
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        mcp.register_prompt_provider(provider.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        let list = r#"{"jsonrpc":"2.0","method":"prompts/list","id":2}"#;
+        let get = r#"{"jsonrpc":"2.0","method":"prompts/get","id":3,"params":{"name":"missing"}}"#;
+        let removals = async {
+            // Both requests are parked in the provider by now.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let removed = tokio::time::timeout(Duration::from_secs(1), async {
+                mcp.unregister_prompt("greet").await && mcp.unregister_prompt("report").await
+            })
+            .await;
+            provider.release.notify_waiters();
+            removed
+        };
+        let (_, _, removed) = tokio::join!(
+            mcp.handle_post_request(Some(session_id.as_str()), list.as_bytes(), None),
+            mcp.handle_post_request(Some(session_id.as_str()), get.as_bytes(), None),
+            removals,
+        );
+
+        assert_eq!(
+            removed,
+            Ok(true),
+            "the registry was locked by a provider call"
+        );
+    }
+
+    #[tokio::test]
+    async fn omitted_required_prompt_argument_is_invalid_params() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/get","id":1,"params":{"name":"greet"}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32602"#), "{}", body);
+        assert!(body.contains("Missing required argument: name"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn oversized_prompt_argument_is_invalid_params() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        let get = |name: String| {
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"prompts/get","id":1,"params":{{"name":"greet","arguments":{{"name":"{}"}}}}}}"#,
+                name
+            )
+        };
+
+        // Exactly at the limit is fine.
+        let body = get("a".repeat(32));
+        let result = mcp
+            .handle_post_request(Some(session_id.as_str()), body.as_bytes(), None)
+            .await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""messages""#), "{}", body);
+
+        let body = get("a".repeat(33));
+        let result = mcp
+            .handle_post_request(Some(session_id.as_str()), body.as_bytes(), None)
+            .await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32602"#), "{}", body);
+        assert!(
+            body.contains("Argument name is 33 bytes, over its limit of 32 bytes"),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn list_apis_return_the_bare_jsonrpc_results() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        mcp.register_dynamic_resource(
+            "notes://1".to_string(),
+            "note-1".to_string(),
+            "A note".to_string(),
+            "text/plain".to_string(),
+            Arc::new(NoteResource),
+        )
+        .await;
+
+        let tools: serde_json::Value =
+            serde_json::from_str(&mcp.list_tools(&RequestId::Int(1)).await).expect("valid json");
+        assert_eq!(tools["jsonrpc"], "2.0");
+        assert_eq!(tools["id"], 1);
+        let names: Vec<&str> = tools["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["echo", "fail"]);
+
+        let prompts: serde_json::Value = serde_json::from_str(
+            &mcp.list_prompts(&RequestId::Str("p".to_string())).await,
+        )
+        .expect("valid json");
+        assert_eq!(prompts["id"], "p");
+        assert_eq!(prompts["result"]["prompts"][0]["name"], "greet");
+
+        let resources: serde_json::Value =
+            serde_json::from_str(&mcp.list_resources(&RequestId::Int(3), None).await)
+                .expect("valid json");
+        assert_eq!(resources["id"], 3);
+        assert_eq!(resources["result"]["resources"][0]["uri"], "notes://1");
+        assert!(resources["result"].get("nextCursor").is_none());
+    }
+
+    struct UndescribedTool;
+
+    impl ToolDefinition for UndescribedTool {
+        const FUNC_NAME: &'static str = "undescribed";
+        const DESCRIPTION: &'static str = "";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, EchoOutput> for UndescribedTool {
+        async fn execute_tool_call(&self, model: EchoInput) -> Result<EchoOutput, String> {
+            Ok(EchoOutput {
+                echoed: model.text.unwrap_or_default(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn an_empty_description_is_left_out_of_the_list() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(UndescribedTool));
+
+        let tools: serde_json::Value =
+            serde_json::from_str(&mcp.list_tools(&RequestId::Int(1)).await).expect("valid json");
+        let tools = tools["result"]["tools"].as_array().unwrap();
+
+        let echo = tools.iter().find(|tool| tool["name"] == "echo").unwrap();
+        assert!(echo["description"].is_string());
+
+        let undescribed = tools.iter().find(|tool| tool["name"] == "undescribed").unwrap();
+        assert!(undescribed.get("description").is_none(), "{}", undescribed);
+    }
+
+    struct TemplatesPrompt {
+        templates: std::sync::atomic::AtomicUsize,
+    }
+
+    impl PromptDefinition for TemplatesPrompt {
+        const PROMPT_NAME: &'static str = "templates";
+        const DESCRIPTION: &'static str = "Fills a template";
+
+        fn get_argument_descriptions() -> Vec<PromptArgumentDescription> {
+            vec![]
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl McpPromptService for TemplatesPrompt {
+        async fn execute_prompt(
+            &self,
+            _arguments: &std::collections::HashMap<String, String>,
+        ) -> Result<PromptExecutionResult, String> {
+            Err("not used".to_string())
+        }
+
+        async fn get_dynamic_description(&self) -> Option<String> {
+            let templates = self.templates.load(Ordering::SeqCst);
+            if templates == 0 {
+                return None;
+            }
+            Some(format!("Fills one of {} templates", templates))
+        }
+    }
+
+    #[tokio::test]
+    async fn prompts_list_reports_the_description_of_the_moment() {
+        let prompt = Arc::new(TemplatesPrompt {
+            templates: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(prompt.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/list","id":1}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""description":"Fills a template""#));
+
+        prompt.templates.store(3, Ordering::SeqCst);
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/list","id":2}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""description":"Fills one of 3 templates""#));
+    }
+
+    struct ReportPrompt;
+
+    impl PromptDefinition for ReportPrompt {
+        const PROMPT_NAME: &'static str = "report";
+        const DESCRIPTION: &'static str = "Builds a daily report";
+
+        fn get_argument_descriptions() -> Vec<PromptArgumentDescription> {
+            vec![
+                PromptArgumentDescription {
+                    name: "day".to_string(),
+                    description: "Day to report on".to_string(),
+                    required: true,
+                    schema: Some(serde_json::json!({"type": "string", "format": "date"})),
+                    default: None,
+                    max_size: None,
+                },
+                PromptArgumentDescription {
+                    name: "notes".to_string(),
+                    description: "Free text".to_string(),
+                    required: false,
+                    schema: None,
+                    default: None,
+                    max_size: None,
+                },
+            ]
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl McpPromptService for ReportPrompt {
+        async fn execute_prompt(
+            &self,
+            _arguments: &std::collections::HashMap<String, String>,
+        ) -> Result<PromptExecutionResult, String> {
+            Err("not used".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_prompt_written_in_parts_arrives_whole() {
+        struct DumpPrompt;
+
+        impl PromptDefinition for DumpPrompt {
+            const PROMPT_NAME: &'static str = "dump";
+            const DESCRIPTION: &'static str = "Embeds a large dump";
+
+            fn get_argument_descriptions() -> Vec<PromptArgumentDescription> {
+                vec![]
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl McpPromptService for DumpPrompt {
+            async fn execute_prompt(
+                &self,
+                _arguments: &std::collections::HashMap<String, String>,
+            ) -> Result<PromptExecutionResult, String> {
+                Ok(PromptExecutionResult {
+                    description: "A dump".to_string(),
+                    message: "Look at this".to_string(),
+                    embedded_resources: vec![ResourceContent::text(
+                        "file:///dump.txt",
+                        "",
+                        "x".repeat(1_000_000),
+                    )],
+                })
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(DumpPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/get","id":2,"params":{"name":"dump","arguments":{}}}"#;
+        let (status, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+        assert_eq!(status, 200);
+        assert_eq!(parsed["id"], 2);
+        assert_eq!(parsed["result"]["description"], "A dump");
+
+        let messages = parsed["result"]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["content"]["text"], "Look at this");
+        let resource = &messages[1]["content"]["resource"];
+        assert_eq!(resource["uri"], "file:///dump.txt");
+        assert_eq!(resource["text"], "x".repeat(1_000_000));
+    }
+
+    #[tokio::test]
+    async fn prompts_list_carries_argument_schemas() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"prompts/list","id":1}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+
+        let arguments = &parsed["result"]["prompts"][0]["arguments"];
+        assert_eq!(arguments[0]["schema"]["type"], "string");
+        assert_eq!(arguments[0]["schema"]["format"], "date");
+        assert!(arguments[1].get("schema").is_none());
+    }
+
+    /// Supports dry run and counts the times it actually ran.
+    #[derive(Default)]
+    struct DeleteTool {
+        runs: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ToolDefinition for DeleteTool {
+        const FUNC_NAME: &'static str = "delete";
+        const DESCRIPTION: &'static str = "Deletes an entry";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, EchoOutput> for DeleteTool {
+        async fn execute_tool_call(&self, model: EchoInput) -> Result<EchoOutput, String> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            Ok(EchoOutput {
+                echoed: model.text.unwrap_or_default(),
+            })
+        }
+
+        fn supports_dry_run(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run_validates_arguments_without_running_the_tool() {
+        let tool = Arc::new(DeleteTool::default());
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(tool.clone());
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"delete","arguments":{"text":"a"},"_meta":{"dryRun":true}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""isError":false"#), "{}", body);
+        assert!(body.contains(r#""dryRun":true"#), "{}", body);
+        assert!(!body.contains("structuredContent"), "{}", body);
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"delete","arguments":{"text":5},"_meta":{"dryRun":true}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32602"#), "{}", body);
+
+        assert_eq!(tool.runs.load(Ordering::SeqCst), 0);
+
+        // A tool that did not opt in rejects the dry run.
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":3,"params":{"name":"echo","arguments":{},"_meta":{"dryRun":true}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains("does not support dry run"), "{}", body);
+    }
+
+    struct ForgetTool;
+
+    impl ToolDefinition for ForgetTool {
+        const FUNC_NAME: &'static str = "forget";
+        const DESCRIPTION: &'static str = "Drops a cached entry";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, VoidOutput> for ForgetTool {
+        async fn execute_tool_call(&self, _model: EchoInput) -> Result<VoidOutput, String> {
+            Ok(VoidOutput)
+        }
+    }
+
+    #[tokio::test]
+    async fn void_tool_streams_an_empty_content_result() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(ForgetTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":5,"params":{"name":"forget","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""content":[]"#), "{}", body);
+        assert!(body.contains(r#""isError":false"#), "{}", body);
+    }
+
+    struct SlowTool;
+
+    impl ToolDefinition for SlowTool {
+        const FUNC_NAME: &'static str = "slow";
+        const DESCRIPTION: &'static str = "Takes its time";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, EchoOutput> for SlowTool {
+        async fn execute_tool_call(&self, _model: EchoInput) -> Result<EchoOutput, String> {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(EchoOutput {
+                echoed: "slow".to_string(),
+            })
+        }
+    }
+
+    struct PanickingTool;
+
+    impl ToolDefinition for PanickingTool {
+        const FUNC_NAME: &'static str = "panics";
+        const DESCRIPTION: &'static str = "Always panics";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCall<EchoInput, EchoOutput> for PanickingTool {
+        async fn execute_tool_call(&self, _model: EchoInput) -> Result<EchoOutput, String> {
+            panic!("secret connection string leaked in a panic");
+        }
+    }
+
+    /// Echoes, remembering every answer: a repeated text is a cache hit.
+    #[derive(Default)]
+    struct CachingEchoTool {
+        cache: parking_lot::Mutex<HashMap<String, String>>,
+    }
+
+    impl ToolDefinition for CachingEchoTool {
+        const FUNC_NAME: &'static str = "cached_echo";
+        const DESCRIPTION: &'static str = "Echoes the input back, from cache when it can";
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCallWithInstruction<EchoInput, EchoOutput> for CachingEchoTool {
+        async fn execute_tool_call_with_instruction(
+            &self,
+            model: EchoInput,
+        ) -> Result<crate::mcp_middleware::ToolCallOutput<EchoOutput>, String> {
+            let text = model.text.unwrap_or_default();
+
+            if let Some(echoed) = self.cache.lock().get(&text).cloned() {
+                return Ok(crate::mcp_middleware::ToolCallOutput::new(EchoOutput { echoed })
+                    .from_cache());
+            }
+
+            self.cache.lock().insert(text.clone(), text.clone());
+            Ok(crate::mcp_middleware::ToolCallOutput::new(EchoOutput { echoed: text }))
+        }
+    }
+
+    #[tokio::test]
+    async fn only_a_cache_hit_is_marked_cached() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(CachingEchoTool::default()));
+        let session_id = initialize_session(&mcp).await;
+
+        let mut results = Vec::new();
+        for id in [1, 2] {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","method":"tools/call","id":{},"params":{{"name":"cached_echo","arguments":{{"text":"hi"}}}}}}"#,
+                id
+            );
+            let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body.as_bytes()).await;
+            results.push(parsed["result"].clone());
+        }
+
+        assert_eq!(results[0]["structuredContent"]["echoed"], "hi");
+        assert!(results[0].get("_meta").is_none());
+
+        assert_eq!(results[1]["structuredContent"]["echoed"], "hi");
+        assert_eq!(results[1]["_meta"]["cached"], true);
+    }
+
+    #[tokio::test]
+    async fn a_tool_text_and_its_structured_content_go_out_separately() {
+        struct ForecastTool;
+
+        impl ToolDefinition for ForecastTool {
+            const FUNC_NAME: &'static str = "forecast";
+            const DESCRIPTION: &'static str = "Tells the weather";
+        }
+
+        #[async_trait::async_trait]
+        impl McpToolCallWithInstruction<EchoInput, EchoOutput> for ForecastTool {
+            async fn execute_tool_call_with_instruction(
+                &self,
+                _model: EchoInput,
+            ) -> Result<crate::mcp_middleware::ToolCallOutput<EchoOutput>, String> {
+                Ok(crate::mcp_middleware::ToolCallOutput::new(EchoOutput {
+                    echoed: "sunny".to_string(),
+                })
+                .with_text("It will be sunny all day."))
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(ForecastTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"forecast","arguments":{}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+
+        assert_eq!(parsed["result"]["content"][0]["text"], "It will be sunny all day.");
+        assert_eq!(
+            parsed["result"]["structuredContent"],
+            serde_json::json!({"echoed": "sunny"})
+        );
+    }
+
+    #[tokio::test]
+    async fn tool_content_blocks_keep_the_order_they_were_added_in() {
+        use crate::mcp_middleware::{ToolBinaryContent, ToolCallOutput};
+
+        struct ChartTool;
+
+        impl ToolDefinition for ChartTool {
+            const FUNC_NAME: &'static str = "chart";
+            const DESCRIPTION: &'static str = "Draws and narrates a chart";
+        }
+
+        #[async_trait::async_trait]
+        impl McpToolCallWithInstruction<EchoInput, EchoOutput> for ChartTool {
+            async fn execute_tool_call_with_instruction(
+                &self,
+                _model: EchoInput,
+            ) -> Result<ToolCallOutput<EchoOutput>, String> {
+                Ok(ToolCallOutput::new(EchoOutput {
+                    echoed: "chart".to_string(),
+                })
+                .with_binary(ToolBinaryContent::image("image/png", b"png"))
+                .with_text("Sales doubled in March.")
+                .with_binary(ToolBinaryContent::audio("audio/wav", b"wav"))
+                .with_binary(ToolBinaryContent::image("image/jpeg", b"jpg")))
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(ChartTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"chart","arguments":{}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+
+        let blocks: Vec<(String, String)> = parsed["result"]["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| {
+                let kind = block["type"].as_str().unwrap().to_string();
+                let what = match block["mimeType"].as_str() {
+                    Some(mime_type) => mime_type.to_string(),
+                    None => block["text"].as_str().unwrap().to_string(),
+                };
+                (kind, what)
+            })
+            .collect();
+
+        let expected = [
+            ("image", "image/png"),
+            ("text", "Sales doubled in March."),
+            ("audio", "audio/wav"),
+            ("image", "image/jpeg"),
+        ];
+        assert_eq!(
+            blocks,
+            expected.map(|(kind, what)| (kind.to_string(), what.to_string()))
+        );
+    }
+
+    /// Takes as long as [`SlowTool`].
+    struct SlowResource;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for SlowResource {
+        async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok(ResourceReadResult { contents: vec![] })
+        }
+    }
+
+    /// Answers a read only once [`Self::release`] is notified.
+    #[derive(Default)]
+    struct StalledResource {
+        release: tokio::sync::Notify,
+    }
+
+    #[async_trait::async_trait]
+    impl McpResourceService for StalledResource {
+        async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+            self.release.notified().await;
+            Ok(ResourceReadResult { contents: vec![] })
+        }
+    }
+
+    #[tokio::test]
+    async fn a_stalled_dynamic_read_does_not_hold_the_registry() {
+        let resource = Arc::new(StalledResource::default());
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "remote://stalled".to_string(),
+            "stalled".to_string(),
+            "Never in a hurry".to_string(),
+            "text/plain".to_string(),
+            resource.clone(),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let removal = async {
+            // The read is parked in the resource by now.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let removed = tokio::time::timeout(
+                Duration::from_secs(1),
+                mcp.unregister_dynamic_resource("remote://stalled"),
+            )
+            .await;
+            resource.release.notify_waiters();
+            removed
+        };
+        let (parsed, removed) = tokio::join!(
+            read_page(&mcp, session_id.as_str(), r#"{"uri":"remote://stalled"}"#),
+            removal,
+        );
+
+        assert_eq!(removed, Ok(true), "the registry was locked by the read");
+        // The read that was under way still finishes.
+        assert!(parsed.get("result").is_some(), "{}", parsed);
+    }
+
+    #[tokio::test]
+    async fn resources_read_and_tools_call_get_their_own_timeouts() {
+        let mut mcp = middleware_with_echo_tool()
+            .with_request_timeout(Duration::from_secs(5))
+            .with_method_timeout("resources/read", Duration::from_millis(50));
+        mcp.register_tool_call(Arc::new(SlowTool));
+        mcp.register_dynamic_resource(
+            "remote://slow".to_string(),
+            "slow".to_string(),
+            "Takes its time".to_string(),
+            "text/plain".to_string(),
+            Arc::new(SlowResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        assert_eq!(
+            mcp.method_timeout("resources/read"),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(mcp.method_timeout("tools/call"), Some(Duration::from_secs(5)));
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"remote://slow"}"#).await;
+        assert_eq!(parsed["error"]["code"], -32001);
+        assert_eq!(
+            parsed["error"]["message"],
+            "resources/read timed out after 50ms"
+        );
+
+        // Just as slow, but within the global default.
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"slow","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""echoed":"slow""#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_panicking_tool_yields_an_internal_error() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(PanickingTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":7,"params":{"name":"panics","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""id":7"#), "{}", body);
+        assert!(body.contains(r#""code":-32603"#), "{}", body);
+        assert!(
+            body.contains("Internal error: the tools/call handler panicked"),
+            "{}",
+            body
+        );
+        // The panic text is logged, never sent.
+        assert!(!body.contains("secret"), "{}", body);
+
+        // The session is unharmed.
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":8,"params":{"name":"echo","arguments":{"text":"still here"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains("still here"), "{}", body);
+    }
+
+    /// Fails with the server error `code`, built unchecked.
+    struct QuotaTool {
+        code: i64,
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCallAbstract for QuotaTool {
+        async fn execute(
+            &self,
+            _ctx: ToolCallContext,
+        ) -> Result<ExecutedToolCall, McpToolCallError> {
+            Err(McpToolCallError::ServerError {
+                code: self.code,
+                message: "Quota exceeded".to_string(),
+            })
+        }
+
+        fn get_fn_name(&self) -> &str {
+            "quota"
+        }
+
+        fn get_description(&self) -> &str {
+            "Always over quota"
+        }
+
+        async fn get_input_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+
+        async fn get_output_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+    }
+
+    /// Reports `{"total": n}`, and `{"count": n}` to clients still on
+    /// 2025-03-26.
+    struct TotalTool;
+
+    const OLD_PROTOCOL_VERSION: &str = "2025-03-26";
+
+    #[async_trait::async_trait]
+    impl McpToolCallAbstract for TotalTool {
+        async fn execute(
+            &self,
+            ctx: ToolCallContext,
+        ) -> Result<ExecutedToolCall, McpToolCallError> {
+            let field = match ctx.protocol_version() {
+                OLD_PROTOCOL_VERSION => "count",
+                _ => "total",
+            };
+            Ok(ExecutedToolCall {
+                structured_json: format!(r#"{{"{}":3}}"#, field),
+                instruction: None,
+                content_meta: None,
+                citations: Vec::new(),
+                cached: false,
+                content: Vec::new(),
+                void_output: false,
+            })
+        }
+
+        fn get_fn_name(&self) -> &str {
+            "total"
+        }
+
+        fn get_description(&self) -> &str {
+            "Counts things"
+        }
+
+        async fn get_input_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+
+        async fn get_output_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new()
+                .write("type", "object")
+                .write_json_object("properties", |p| {
+                    p.write_json_object("total", |t| t.write("type", "integer"))
+                })
+        }
+
+        async fn get_output_params_for_version(
+            &self,
+            protocol_version: &str,
+        ) -> Option<my_ai_agent::my_json::json_writer::JsonObjectWriter> {
+            if protocol_version != OLD_PROTOCOL_VERSION {
+                return None;
+            }
+            Some(
+                my_ai_agent::my_json::json_writer::JsonObjectWriter::new()
+                    .write("type", "object")
+                    .write_json_object("properties", |p| {
+                        p.write_json_object("count", |t| t.write("type", "integer"))
+                    }),
+            )
+        }
+    }
+
+    /// Asks the user for a name and gives up after 200ms.
+    struct AskTool;
+
+    #[async_trait::async_trait]
+    impl McpToolCallAbstract for AskTool {
+        async fn execute(
+            &self,
+            ctx: ToolCallContext,
+        ) -> Result<ExecutedToolCall, McpToolCallError> {
+            let schema = serde_json::json!({"type": "object", "properties": {"name": {"type": "string"}}});
+            let err = ctx
+                .elicit("Your name?", schema, Duration::from_millis(200))
+                .await
+                .err()
+                .unwrap_or_default();
+            Err(McpToolCallError::Failed(err))
+        }
+
+        fn get_fn_name(&self) -> &str {
+            "ask"
+        }
+
+        fn get_description(&self) -> &str {
+            "Asks the user"
+        }
+
+        async fn get_input_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+
+        async fn get_output_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+            my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+    }
+
+    #[tokio::test]
+    async fn capabilities_read_during_re_initialize_stay_whole() {
+        let mcp = middleware_with_echo_tool();
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{"elicitation":{}}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, _, session_id) = read_sse_response(result).await;
+        let session_id = session_id.unwrap();
+
+        let expected = SessionCapabilities {
+            protocol_version: "2025-06-18".to_string(),
+            supports_elicitation: true,
+        };
+        assert_eq!(mcp.get_session_capabilities(&session_id), Some(expected.clone()));
+
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let sessions = mcp.sessions.clone();
+                let session_id = session_id.clone();
+                let expected = expected.clone();
+                let stop = stop.clone();
+                std::thread::spawn(move || {
+                    let mut reads = 0;
+                    while !stop.load(std::sync::atomic::Ordering::Relaxed) || reads == 0 {
+                        assert_eq!(sessions.session_capabilities(&session_id), Some(expected.clone()));
+                        reads += 1;
+                    }
+                })
+            })
+            .collect();
+
+        // The client re-initializes over and over with the old header and
+        // other capabilities.
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":2,"params":{"protocolVersion":"2025-03-26","capabilities":{}}}"#;
+        for _ in 0..50 {
+            let result = mcp
+                .handle_post_request(Some(session_id.as_str()), body, None)
+                .await;
+            let (_, _, new_session_id) = read_sse_response(result).await;
+            let new_session_id = new_session_id.unwrap();
+            assert_ne!(new_session_id, session_id);
+            assert_eq!(
+                mcp.get_session_capabilities(&new_session_id),
+                Some(SessionCapabilities {
+                    protocol_version: "2025-03-26".to_string(),
+                    supports_elicitation: false,
+                })
+            );
+        }
+
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        for reader in readers {
+            reader.join().expect("no torn capabilities");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_session_stream_yields_the_frames_a_tool_call_sends() {
+        use futures_core::Stream;
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_raw_tool_call(Arc::new(AskTool));
+        let mcp = Arc::new(mcp);
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{"elicitation":{}}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, _, session_id) = read_sse_response(result).await;
+        let session_id = session_id.unwrap();
+
+        assert!(mcp.open_session_stream("no-such-session").is_none());
+        let mut stream = mcp.open_session_stream(session_id.as_str()).unwrap();
+
+        let call = tokio::spawn({
+            let mcp = mcp.clone();
+            let session_id = session_id.clone();
+            async move {
+                let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"ask","arguments":{}}}"#;
+                let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+                read_sse_response(result).await.1
+            }
+        });
+
+        // Past the `retry:` preamble of SSE framing.
+        let frame = loop {
+            let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx))
+                .await
+                .expect("the stream stays open");
+            let frame = String::from_utf8(frame.into_bytes()).unwrap();
+            if !frame.starts_with("retry: ") {
+                break frame;
+            }
+        };
+
+        let elicitation: serde_json::Value =
+            serde_json::from_str(crate::mcp_middleware::unframe(frame).trim_end()).unwrap();
+        assert_eq!(elicitation["method"], "elicitation/create");
+        assert_eq!(elicitation["params"]["message"], "Your name?");
+
+        // Unanswered, the elicitation times out and the call fails.
+        let body = call.await.unwrap();
+        assert!(body.contains(r#""isError":true"#), "{}", body);
+
+        // Dropped early, the stream takes the session's channel with it.
+        drop(stream);
+        assert!(mcp.sessions.get_sender(session_id.as_str()).is_none());
+    }
+
+    #[tokio::test]
+    async fn a_custom_notification_reaches_the_session_stream() {
+        use futures_core::Stream;
+
+        let mcp = middleware_with_echo_tool();
+        let session_id = initialize_session(&mcp).await;
+
+        let err = mcp
+            .send_notification(session_id.as_str(), "notifications/progress", None)
+            .await
+            .unwrap_err();
+        assert!(err.contains("no live stream"), "{}", err);
+
+        let mut stream = mcp.open_session_stream(session_id.as_str()).unwrap();
+
+        let params = serde_json::json!({"progressToken": "t1", "progress": 3, "total": 10});
+        mcp.send_notification(session_id.as_str(), "notifications/progress", Some(params))
+            .await
+            .unwrap();
+        mcp.send_notification(session_id.as_str(), "acme/ping", None)
+            .await
+            .unwrap();
+        assert!(
+            mcp.send_notification(session_id.as_str(), "acme/ping", Some(serde_json::json!(7)))
+                .await
+                .is_err()
+        );
+
+        let mut notifications = Vec::new();
+        while notifications.len() < 2 {
+            let frame = std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx))
+                .await
+                .expect("the stream stays open");
+            let frame = String::from_utf8(frame.into_bytes()).unwrap();
+            // Past the `retry:` preamble of SSE framing.
+            if frame.starts_with("retry: ") {
+                continue;
+            }
+            let notification: serde_json::Value =
+                serde_json::from_str(crate::mcp_middleware::unframe(frame).trim_end()).unwrap();
+            notifications.push(notification);
+        }
+
+        assert_eq!(
+            notifications[0],
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": {"progressToken": "t1", "progress": 3, "total": 10}
+            })
+        );
+        assert_eq!(
+            notifications[1],
+            serde_json::json!({"jsonrpc": "2.0", "method": "acme/ping"})
+        );
+    }
+
+    #[tokio::test]
+    async fn negotiated_versions_get_their_own_output_shape() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_raw_tool_call(Arc::new(TotalTool));
+        let current = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-03-26","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, _, old) = read_sse_response(result).await;
+        let old = old.unwrap();
+
+        let list = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        let call = br#"{"jsonrpc":"2.0","method":"tools/call","id":3,"params":{"name":"total","arguments":{}}}"#;
+
+        let result = mcp.handle_post_request(Some(current.as_str()), list, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""outputSchema":{"type":"object","properties":{"total""#), "{}", body);
+
+        let result = mcp.handle_post_request(Some(old.as_str()), list, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""outputSchema":{"type":"object","properties":{"count""#), "{}", body);
+
+        let result = mcp.handle_post_request(Some(current.as_str()), call, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""structuredContent":{"total":3}"#), "{}", body);
+
+        let result = mcp.handle_post_request(Some(old.as_str()), call, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""structuredContent":{"count":3}"#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_typed_tool_can_advertise_a_per_version_output_schema() {
+        struct VersionedEchoTool;
+
+        impl ToolDefinition for VersionedEchoTool {
+            const FUNC_NAME: &'static str = "versioned_echo";
+            const DESCRIPTION: &'static str = "Echoes, with an older schema for older clients";
+        }
+
+        #[async_trait::async_trait]
+        impl McpToolCall<EchoInput, EchoOutput> for VersionedEchoTool {
+            async fn execute_tool_call(&self, model: EchoInput) -> Result<EchoOutput, String> {
+                Ok(EchoOutput {
+                    echoed: model.text.unwrap_or_default(),
+                })
+            }
+
+            async fn get_output_params_for_version(
+                &self,
+                protocol_version: &str,
+            ) -> Option<my_ai_agent::my_json::json_writer::JsonObjectWriter> {
+                if protocol_version != OLD_PROTOCOL_VERSION {
+                    return None;
+                }
+                Some(
+                    my_ai_agent::my_json::json_writer::JsonObjectWriter::new()
+                        .write("type", "object")
+                        .write("title", "legacy"),
+                )
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(VersionedEchoTool));
+        let current = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-03-26","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, _, old) = read_sse_response(result).await;
+        let old = old.unwrap();
+
+        let list = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        let legacy = r#""outputSchema":{"type":"object","title":"legacy"}"#;
+
+        let result = mcp
+            .handle_post_request(Some(current.as_str()), list, None)
+            .await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(!body.contains(legacy), "{}", body);
+
+        let result = mcp
+            .handle_post_request(Some(old.as_str()), list, None)
+            .await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(legacy), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_tool_server_error_keeps_its_code() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_raw_tool_call(Arc::new(QuotaTool { code: -32010 }));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":3,"params":{"name":"quota","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""id":3"#), "{}", body);
+        assert!(
+            body.contains(r#""error":{"code":-32010,"message":"Quota exceeded"}"#),
+            "{}",
+            body
+        );
+        assert!(!body.contains(r#""result""#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_reserved_tool_server_error_code_becomes_internal_error() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_raw_tool_call(Arc::new(QuotaTool { code: -32002 }));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":4,"params":{"name":"quota","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32603"#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_streaming_tool_is_flagged_in_tools_list() {
+        struct TailTool;
+
+        impl ToolDefinition for TailTool {
+            const FUNC_NAME: &'static str = "tail";
+            const DESCRIPTION: &'static str = "Follows a log";
+        }
+
+        #[async_trait::async_trait]
+        impl McpToolCall<EchoInput, EchoOutput> for TailTool {
+            async fn execute_tool_call(&self, model: EchoInput) -> Result<EchoOutput, String> {
+                Ok(EchoOutput {
+                    echoed: model.text.unwrap_or_default(),
+                })
+            }
+
+            fn supports_streaming(&self) -> bool {
+                true
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(TailTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/list","id":2}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+
+        let tools = parsed["result"]["tools"].as_array().unwrap();
+        let tool = |name: &str| tools.iter().find(|t| t["name"] == name).unwrap().clone();
+        assert_eq!(tool("tail")["annotations"]["streamingHint"], true);
+        assert!(tool("echo").get("annotations").is_none());
+    }
+
+    #[tokio::test]
+    async fn every_schema_failure_comes_back_with_its_pointer() {
+        /// Takes `{name: string, age: integer}`, both required.
+        struct FormTool;
+
+        #[async_trait::async_trait]
+        impl McpToolCallAbstract for FormTool {
+            async fn execute(
+                &self,
+                _ctx: ToolCallContext,
+            ) -> Result<ExecutedToolCall, McpToolCallError> {
+                Ok(ExecutedToolCall {
+                    structured_json: r#"{"saved":true}"#.to_string(),
+                    instruction: None,
+                    content_meta: None,
+                    citations: Vec::new(),
+                    cached: false,
+                    content: Vec::new(),
+                    void_output: false,
+                })
+            }
+
+            fn get_fn_name(&self) -> &str {
+                "form"
+            }
+
+            fn get_description(&self) -> &str {
+                "Saves a form"
+            }
+
+            async fn get_input_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+                my_ai_agent::my_json::json_writer::JsonObjectWriter::new()
+                    .write("type", "object")
+                    .write_json_object("properties", |p| {
+                        p.write_json_object("name", |t| t.write("type", "string"))
+                            .write_json_object("age", |t| t.write("type", "integer"))
+                    })
+                    .write_json_array("required", |arr| arr.write("name").write("age"))
+            }
+
+            async fn get_output_params(&self) -> my_ai_agent::my_json::json_writer::JsonObjectWriter {
+                my_ai_agent::my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool().with_input_schema_validation();
+        mcp.register_raw_tool_call(Arc::new(FormTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"form","arguments":{"age":"old"}}}"#;
+        let (_, parsed, _) = post_json(&mcp, Some(session_id.as_str()), body).await;
+
+        assert_eq!(parsed["error"]["code"], -32602, "{}", parsed);
+        let mut pointers: Vec<&str> = parsed["error"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["pointer"].as_str().unwrap())
+            .collect();
+        pointers.sort();
+        assert_eq!(pointers, vec!["/age", "/name"]);
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":3,"params":{"name":"form","arguments":{"name":"Ann","age":30}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""structuredContent":{"saved":true}"#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn disabled_structured_content_leaves_the_text_block_only() {
+        let mcp = middleware_with_echo_tool().disabled_structured_content();
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo","arguments":{"text":"hi"}}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (status, body, _) = read_sse_response(result).await;
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""type":"text""#), "{}", body);
+        assert!(body.contains(r#"\"echoed\":\"hi\""#), "{}", body);
+        assert!(!body.contains("structuredContent"), "{}", body);
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/list","id":3}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""inputSchema""#), "{}", body);
+        assert!(!body.contains("outputSchema"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn a_flooding_session_does_not_starve_another() {
+        let mut mcp = middleware_with_echo_tool().with_max_concurrent_tool_calls(2);
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let noisy = initialize_session(&mcp).await;
+        let quiet = initialize_session(&mcp).await;
+
+        let mut flood = Vec::new();
+        for id in 1..=10 {
+            let body = format!(
+                r#"{{"jsonrpc":"2.0","method":"tools/call","id":{},"params":{{"name":"slow","arguments":{{}}}}}}"#,
+                id
+            );
+            flood.push(
+                mcp.handle_post_request(Some(noisy.as_str()), body.as_bytes(), None)
+                    .await,
+            );
+        }
+        // Let the flood take both slots and queue up.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let load = mcp.get_tool_call_load().unwrap();
+        assert_eq!((load.in_flight, load.queued), (2, 8));
+
+        let started = tokio::time::Instant::now();
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"slow","arguments":{}}}"#;
+        let result = mcp.handle_post_request(Some(quiet.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""echoed":"slow""#), "{}", body);
+
+        // Its turn comes as soon as a slot frees up, then it runs for
+        // 300ms; first come, first served it would wait for all ten.
+        let waited = started.elapsed();
+        assert!(waited < Duration::from_millis(900), "{:?}", waited);
+
+        for result in flood {
+            let (_, body, _) = read_sse_response(result).await;
+            assert!(body.contains(r#""echoed":"slow""#), "{}", body);
+        }
+        assert_eq!(mcp.get_tool_call_load(), Some(ToolCallLoad::default()));
+    }
+
+    #[tokio::test]
+    async fn ordered_mode_holds_a_fast_response_behind_a_slow_one() {
+        let mut mcp = middleware_with_echo_tool().with_ordered_responses();
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let session_id = initialize_session(&mcp).await;
+        let started = tokio::time::Instant::now();
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"slow","arguments":{}}}"#;
+        let slow = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let body = br#"{"jsonrpc":"2.0","method":"ping","id":2}"#;
+        let fast = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+
+        let (_, fast_body, _) = read_sse_response(fast).await;
+        assert!(fast_body.contains(r#""id":2"#));
+        assert!(
+            started.elapsed() >= Duration::from_millis(300),
+            "the ping answer must wait for the slow tool"
+        );
+
+        let (_, slow_body, _) = read_sse_response(slow).await;
+        assert!(slow_body.contains(r#""echoed":"slow""#));
+    }
+
+    #[tokio::test]
+    async fn unordered_mode_answers_a_fast_request_right_away() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let session_id = initialize_session(&mcp).await;
+        let started = tokio::time::Instant::now();
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"slow","arguments":{}}}"#;
+        let _slow = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let body = br#"{"jsonrpc":"2.0","method":"ping","id":2}"#;
+        let fast = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+
+        let (_, fast_body, _) = read_sse_response(fast).await;
+        assert!(fast_body.contains(r#""id":2"#));
+        assert!(started.elapsed() < Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn an_id_in_flight_is_rejected_and_free_after_completion() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"slow","arguments":{}}}"#;
+        let slow = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+
+        let ping = br#"{"jsonrpc":"2.0","method":"ping","id":1}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), ping, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""code":-32600"#), "{}", body);
+
+        let (_, slow_body, _) = read_sse_response(slow).await;
+        assert!(slow_body.contains(r#""echoed":"slow""#));
+
+        let result = mcp.handle_post_request(Some(session_id.as_str()), ping, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""result":{}"#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn the_same_id_on_another_session_is_not_a_duplicate() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let first = initialize_session(&mcp).await;
+        let second = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"slow","arguments":{}}}"#;
+        let _slow = mcp.handle_post_request(Some(first.as_str()), body, None).await;
+
+        let ping = br#"{"jsonrpc":"2.0","method":"ping","id":1}"#;
+        let result = mcp.handle_post_request(Some(second.as_str()), ping, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""result":{}"#), "{}", body);
+    }
+
+    struct ManyPartsResource;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for ManyPartsResource {
+        async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+            let contents = (0..150)
+                .map(|part| {
+                    ResourceContent::text("doc://big", "text/plain", format!("part-{}", part))
+                })
+                .collect();
+            Ok(ResourceReadResult { contents })
+        }
+    }
+
+    #[cfg(feature = "sse-framing")]
+    #[tokio::test]
+    async fn a_long_response_line_is_split_into_data_lines() {
+        let mcp = middleware_with_echo_tool().with_max_sse_line_size(256);
+        mcp.register_dynamic_resource(
+            "doc://big".to_string(),
+            "big".to_string(),
+            "A big document".to_string(),
+            "text/plain".to_string(),
+            Arc::new(ManyPartsResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"resources/read","id":1,"params":{"uri":"doc://big"}}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+
+        let lines: Vec<&str> = body.trim_end().split('\n').collect();
+        assert!(lines.len() > 1, "{}", body);
+        for line in lines.iter() {
+            assert!(line.starts_with("data: "), "{}", line);
+            assert!(line.len() <= 256, "{}", line);
+        }
+
+        let json: Vec<&str> = lines.iter().map(|line| &line["data: ".len()..]).collect();
+        let parsed: serde_json::Value = serde_json::from_str(&json.join("\n")).expect("valid json");
+        assert_eq!(parsed["result"]["contents"][99]["text"], "part-99");
+    }
+
+    async fn read_page(mcp: &McpMiddleware, session_id: &str, params: &str) -> serde_json::Value {
+        request_json(mcp, session_id, "resources/read", params).await
+    }
+
+    /// The parsed response to one `method` request with `params`, id 1.
+    async fn request_json(
+        mcp: &McpMiddleware,
+        session_id: &str,
+        method: &str,
+        params: &str,
+    ) -> serde_json::Value {
+        let body = format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","id":1,"params":{}}}"#,
+            method, params
+        );
+        let (_, parsed, _) = post_json(mcp, Some(session_id), body.as_bytes()).await;
+        parsed
+    }
+
+    #[tokio::test]
+    async fn read_result_with_many_blocks_is_paged() {
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "doc://big".to_string(),
+            "big".to_string(),
+            "A big document".to_string(),
+            "text/plain".to_string(),
+            Arc::new(ManyPartsResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let first = read_page(&mcp, &session_id, r#"{"uri":"doc://big"}"#).await;
+        let contents = first["result"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 100);
+        assert_eq!(contents[0]["text"], "part-0");
+        let cursor = first["result"]["nextCursor"].as_str().unwrap().to_string();
+
+        let params = format!(r#"{{"uri":"doc://big","cursor":"{}"}}"#, cursor);
+        let second = read_page(&mcp, &session_id, params.as_str()).await;
+        let contents = second["result"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 50);
+        assert_eq!(contents[0]["text"], "part-100");
+        assert!(second["result"].get("nextCursor").is_none());
+
+        let bad = read_page(&mcp, &session_id, r#"{"uri":"doc://big","cursor":"nope"}"#).await;
+        assert_eq!(bad["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn a_page_size_over_the_cap_is_clamped() {
+        let mcp = middleware_with_echo_tool().with_max_page_size(40);
+        mcp.register_dynamic_resource(
+            "doc://big".to_string(),
+            "big".to_string(),
+            "A big document".to_string(),
+            "text/plain".to_string(),
+            Arc::new(ManyPartsResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let page = read_page(&mcp, &session_id, r#"{"uri":"doc://big","pageSize":1000}"#).await;
+        assert_eq!(page["result"]["contents"].as_array().unwrap().len(), 40);
+        assert_eq!(page["result"]["nextCursor"], "40");
+
+        // Without a pageSize the default page is clamped too.
+        let page = read_page(&mcp, &session_id, r#"{"uri":"doc://big"}"#).await;
+        assert_eq!(page["result"]["contents"].as_array().unwrap().len(), 40);
+
+        // Under the cap the client gets what it asked for.
+        let params = r#"{"uri":"doc://big","cursor":"140","pageSize":5}"#;
+        let page = read_page(&mcp, &session_id, params).await;
+        let contents = page["result"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 5);
+        assert_eq!(contents[0]["text"], "part-140");
+        assert_eq!(page["result"]["nextCursor"], "145");
+    }
+
+    #[tokio::test]
+    async fn static_and_dynamic_resources_are_listed_in_one_paged_order() {
+        struct Middle;
+
+        impl ResourceDefinition for Middle {
+            const RESOURCE_URI: &'static str = "notes://b";
+            const RESOURCE_NAME: &'static str = "b";
+            const DESCRIPTION: &'static str = "A static note";
+            const MIME_TYPE: &'static str = "text/plain";
+        }
+
+        #[async_trait::async_trait]
+        impl McpResourceService for Middle {
+            async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+                Ok(ResourceReadResult::builder()
+                    .add_text(uri, Self::MIME_TYPE, "b")
+                    .build())
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource(Arc::new(Middle));
+        for uri in ["notes://a", "notes://c", "notes://d"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "A dynamic note".to_string(),
+                "text/plain".to_string(),
+                Arc::new(NoteResource),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        let uris = |page: &serde_json::Value| -> Vec<String> {
+            page["result"]["resources"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|resource| resource["uri"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let page = request_json(&mcp, &session_id, "resources/list", r#"{"pageSize":2}"#).await;
+        assert_eq!(uris(&page), ["notes://a", "notes://b"]);
+        assert_eq!(page["result"]["nextCursor"], "notes://b");
+
+        let params = r#"{"pageSize":2,"cursor":"notes://b"}"#;
+        let page = request_json(&mcp, &session_id, "resources/list", params).await;
+        assert_eq!(uris(&page), ["notes://c", "notes://d"]);
+        assert!(page["result"].get("nextCursor").is_none(), "{}", page);
+    }
+
+    #[tokio::test]
+    async fn tools_and_prompts_lists_are_paged() {
+        let mut mcp = middleware_with_echo_tool().with_max_page_size(1);
+        mcp.register_prompt(Arc::new(ReportPrompt));
+        mcp.register_prompt(Arc::new(GreetPrompt));
+        let session_id = initialize_session(&mcp).await;
+
+        for (method, key, first, second) in [
+            ("tools/list", "tools", "echo", "fail"),
+            ("prompts/list", "prompts", "greet", "report"),
+        ] {
+            // The cap holds without a pageSize and against a bigger one.
+            for params in ["{}", r#"{"pageSize":50}"#] {
+                let page = request_json(&mcp, &session_id, method, params).await;
+                let items = page["result"][key].as_array().unwrap();
+                assert_eq!(items.len(), 1, "{}", page);
+                assert_eq!(items[0]["name"], first);
+                assert_eq!(page["result"]["nextCursor"], first);
+            }
+
+            let params = format!(r#"{{"cursor":"{}"}}"#, first);
+            let page = request_json(&mcp, &session_id, method, params.as_str()).await;
+            assert_eq!(page["result"][key][0]["name"], second);
+            assert!(page["result"].get("nextCursor").is_none(), "{}", page);
+
+            let page = request_json(&mcp, &session_id, method, r#"{"cursor":"gone"}"#).await;
+            assert_eq!(page["error"]["code"], -32602, "{}", page);
+        }
+
+        let page: serde_json::Value =
+            serde_json::from_str(&mcp.list_tools_page(&RequestId::Int(1), Some("echo")).await)
+                .expect("valid json");
+        assert_eq!(page["result"]["tools"][0]["name"], "fail");
+    }
+
+    #[tokio::test]
+    async fn resource_without_mime_type_is_listed_as_text() {
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "notes://plain".to_string(),
+            "plain".to_string(),
+            "A note".to_string(),
+            String::new(),
+            Arc::new(NoteResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let body = br#"{"jsonrpc":"2.0","method":"resources/list","id":1}"#;
+        let result = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+        assert!(body.contains(r#""mimeType":"text/plain""#), "{}", body);
+    }
+
+    /// Records how many reads run at the same time.
+    #[derive(Default)]
+    struct ExpensiveResource {
+        running: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl McpResourceService for ExpensiveResource {
+        async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ResourceReadResult { contents: vec![] })
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_over_the_resource_limit_are_queued() {
+        // `read_page` always sends id 1.
+        let mcp = middleware_with_echo_tool()
+            .disabled_duplicate_request_id_check()
+            .with_resource_read_limit("remote://feed", 1);
+        let resource = Arc::new(ExpensiveResource::default());
+        for uri in ["remote://feed", "remote://other"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                "feed".to_string(),
+                "A remote feed".to_string(),
+                "text/plain".to_string(),
+                resource.clone(),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        let params = r#"{"uri":"remote://feed"}"#;
+        let (first, second, third) = tokio::join!(
+            read_page(&mcp, &session_id, params),
+            read_page(&mcp, &session_id, params),
+            read_page(&mcp, &session_id, params),
+        );
+        for page in [first, second, third] {
+            assert!(page.get("result").is_some(), "{}", page);
+        }
+        assert_eq!(resource.peak.load(Ordering::SeqCst), 1);
+
+        // Other URIs are not limited.
+        let params = r#"{"uri":"remote://other"}"#;
+        tokio::join!(
+            read_page(&mcp, &session_id, params),
+            read_page(&mcp, &session_id, params),
+        );
+        assert_eq!(resource.peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_a_resource_permit_counts_toward_the_deadline() {
+        let mcp = middleware_with_echo_tool()
+            .with_method_timeout("resources/read", Duration::from_millis(50))
+            .with_resource_read_limit("remote://feed", 1);
+        mcp.register_dynamic_resource(
+            "remote://feed".to_string(),
+            "feed".to_string(),
+            "A remote feed".to_string(),
+            "text/plain".to_string(),
+            Arc::new(ExpensiveResource::default()),
+        )
+        .await;
+        register_alias(&mcp, "alias://feed", "remote://feed").await;
+        let session_id = initialize_session(&mcp).await;
+
+        let permit = mcp.resource_read_limits["remote://feed"]
+            .clone()
+            .acquire_owned()
+            .await
+            .unwrap();
+
+        // Through the alias too: the permit is the target's.
+        for params in [r#"{"uri":"remote://feed"}"#, r#"{"uri":"alias://feed"}"#] {
+            let parsed = tokio::time::timeout(
+                Duration::from_secs(1),
+                read_page(&mcp, session_id.as_str(), params),
+            )
+            .await
+            .expect("the wait is cut by the deadline");
+            assert_eq!(parsed["error"]["code"], -32001, "{}", parsed);
+        }
+
+        drop(permit);
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"alias://feed"}"#).await;
+        assert!(parsed.get("result").is_some(), "{}", parsed);
+    }
+
+    #[tokio::test]
+    async fn initialized_before_initialize_is_ignored() {
+        let mcp = middleware_with_echo_tool();
+        let initialized = br#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+
+        let result = mcp.handle_post_request(None, initialized, None).await;
+        let ok = result.expect("notification must be accepted");
+        assert_eq!(ok.output.get_status_code(), 202);
+        assert!(mcp.get_sessions().is_empty());
+
+        let session_id = initialize_session(&mcp).await;
+        assert!(!only_session(&mcp).initialized);
+
+        for _ in 0..2 {
+            let result = mcp
+                .handle_post_request(Some(session_id.as_str()), initialized, None)
+                .await;
+            assert_eq!(result.unwrap().output.get_status_code(), 202);
+            assert!(only_session(&mcp).initialized);
+        }
+    }
+
+    #[tokio::test]
+    async fn large_response_is_gzipped_for_a_gzip_capable_client() {
+        use std::io::Read;
+
+        let mcp = middleware_with_echo_tool().with_response_compression(1024);
+        mcp.register_dynamic_resource(
+            "doc://big".to_string(),
+            "big".to_string(),
+            "A big document".to_string(),
+            "text/plain".to_string(),
+            Arc::new(ManyPartsResource),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let headers = McpRequestHeaders {
+            accept: None,
+            accept_encoding: Some("gzip, deflate".to_string()),
+        };
+        let body = br#"{"jsonrpc":"2.0","method":"resources/read","id":1,"params":{"uri":"doc://big"}}"#;
+        let result = mcp
+            .handle_post_request_with_headers(Some(session_id.as_str()), &headers, body, None)
+            .await;
+
+        let HttpOutput::Raw(response) = result.expect("expected Ok result").output else {
+            panic!("expected Raw stream output");
+        };
+        assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+        let compressed = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .expect("body collected")
+            .to_bytes()
+            .to_vec();
+
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decompressed)
+            .expect("valid gzip");
+        assert!(compressed.len() < decompressed.len());
+
+        let plain = mcp.handle_post_request(Some(session_id.as_str()), body, None).await;
+        let (_, plain, _) = read_sse_response(plain).await;
+        assert_eq!(decompressed, plain);
+    }
+
+    async fn read_response_with_content_type(
+        result: Result<HttpOkResult, HttpFailResult>,
+    ) -> (String, String) {
+        let HttpOutput::Raw(response) = result.expect("expected Ok result").output else {
+            panic!("expected Raw stream output");
+        };
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .map(|v| v.to_str().unwrap().to_string())
+            .unwrap();
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .expect("body collected")
+            .to_bytes()
+            .to_vec();
+        (content_type, String::from_utf8(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn fast_results_are_json_and_slow_tool_calls_upgrade_to_sse() {
+        let mut mcp = middleware_with_echo_tool().with_json_responses(Duration::from_millis(100));
+        mcp.register_tool_call(Arc::new(SlowTool));
+        let session_id = initialize_session(&mcp).await;
+
+        let dual = McpRequestHeaders {
+            accept: Some("application/json, text/event-stream".to_string()),
+            accept_encoding: None,
+        };
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo","arguments":{"text":"quick"}}}"#;
+        let result = mcp
+            .handle_post_request_with_headers(Some(session_id.as_str()), &dual, body, None)
+            .await;
+        let (content_type, body) = read_response_with_content_type(result).await;
+        assert_eq!(content_type, "application/json");
+        let parsed: serde_json::Value = serde_json::from_str(&body).expect("a bare JSON body");
+        assert_eq!(parsed["id"], 2);
+        assert!(body.contains("quick"), "{}", body);
+
+        let body = br#"{"jsonrpc":"2.0","method":"tools/call","id":3,"params":{"name":"slow","arguments":{}}}"#;
+        let result = mcp
+            .handle_post_request_with_headers(Some(session_id.as_str()), &dual, body, None)
+            .await;
+        let (content_type, body) = read_response_with_content_type(result).await;
+        assert_eq!(content_type, crate::mcp_middleware::FRAME_CONTENT_TYPE);
+        assert!(body.starts_with(crate::mcp_middleware::FRAME_PREFIX), "{}", body);
+        assert!(body.contains(r#""echoed":"slow""#), "{}", body);
+
+        // A client that takes only SSE is streamed to, however fast.
+        let sse_only = McpRequestHeaders {
+            accept: Some("text/event-stream".to_string()),
+            accept_encoding: None,
+        };
+        let body = br#"{"jsonrpc":"2.0","method":"tools/list","id":4}"#;
+        let result = mcp
+            .handle_post_request_with_headers(Some(session_id.as_str()), &sse_only, body, None)
+            .await;
+        let (content_type, body) = read_response_with_content_type(result).await;
+        assert_eq!(content_type, crate::mcp_middleware::FRAME_CONTENT_TYPE);
+        assert!(body.starts_with(crate::mcp_middleware::FRAME_PREFIX), "{}", body);
+    }
+
+    /// Backs every `notes://` URI with one instance.
+    struct NotesFolder;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for NotesFolder {
+        async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            Ok(ResourceReadResult {
+                contents: vec![ResourceContent::text(
+                    uri,
+                    "text/plain",
+                    format!("content of {}", uri),
+                )],
+            })
+        }
+    }
+
+    /// Serves `notes://valid` as JSON, anything else as broken JSON.
+    struct JsonNotes;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for JsonNotes {
+        async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            let text = match uri {
+                "notes://valid" => r#"{"b":1,"a":[]}"#,
+                _ => r#"{"b":1,"a":"#,
+            };
+            Ok(ResourceReadResult {
+                contents: vec![ResourceContent::text(
+                    uri,
+                    "application/json; charset=utf-8",
+                    text,
+                )],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_json_resource_text_fails_the_read() {
+        let mcp = middleware_with_echo_tool().with_pretty_json_resources();
+        for uri in ["notes://valid", "notes://broken"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "A JSON note".to_string(),
+                "application/json".to_string(),
+                Arc::new(JsonNotes),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://broken"}"#).await;
+        assert_eq!(parsed["error"]["code"], -32603, "{}", parsed);
+        assert!(
+            parsed["error"]["message"].as_str().unwrap().contains("not valid JSON"),
+            "{}",
+            parsed
+        );
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://valid"}"#).await;
+        assert_eq!(
+            parsed["result"]["contents"][0]["text"],
+            "{\n  \"b\": 1,\n  \"a\": []\n}"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_resource_text_is_checked_with_redirects_disabled() {
+        let mcp = middleware_with_echo_tool()
+            .with_pretty_json_resources()
+            .disabled_resource_redirects();
+        for uri in ["notes://valid", "notes://broken"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "A JSON note".to_string(),
+                "application/json".to_string(),
+                Arc::new(JsonNotes),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://broken"}"#).await;
+        assert_eq!(parsed["error"]["code"], -32603, "{}", parsed);
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://valid"}"#).await;
+        assert_eq!(
+            parsed["result"]["contents"][0]["text"],
+            "{\n  \"b\": 1,\n  \"a\": []\n}"
+        );
+    }
+
+    /// Hands out the chunk channel the test feeds.
+    struct LogTail {
+        chunks: parking_lot::Mutex<Option<tokio::sync::mpsc::Receiver<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::mcp_middleware::McpResourceTail for LogTail {
+        async fn open_tail(
+            &self,
+            _uri: &str,
+        ) -> Result<tokio::sync::mpsc::Receiver<String>, String> {
+            self.chunks
+                .lock()
+                .take()
+                .ok_or_else(|| "The log is tailed already".to_string())
+        }
+    }
+
+    /// `mcp` with `logs://app` tailing `receiver`.
+    async fn register_log_tail(
+        mcp: &Arc<McpMiddleware>,
+        receiver: tokio::sync::mpsc::Receiver<String>,
+    ) {
+        mcp.register_tailing_resource(
+            "logs://app".to_string(),
+            "app log".to_string(),
+            "The application log, as it grows".to_string(),
+            "text/plain".to_string(),
+            Arc::new(LogTail {
+                chunks: parking_lot::Mutex::new(Some(receiver)),
+            }),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn a_tailing_resource_serves_its_first_chunk_then_announces_the_next() {
+        let (chunks, receiver) = tokio::sync::mpsc::channel(8);
+        chunks.send("line 1\n".to_string()).await.unwrap();
+
+        let mcp = Arc::new(middleware_with_echo_tool());
+        register_log_tail(&mcp, receiver).await;
+        let session_id = initialize_session(&mcp).await;
+        let mut events = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+        assert!(mcp.sessions.subscribe(session_id.as_str(), "logs://app".to_string()));
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://app"}"#).await;
+        assert_eq!(parsed["result"]["contents"][0]["text"], "line 1\n");
+
+        chunks.send("line 2\n".to_string()).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .expect("the follow-up is announced");
+        match event {
+            Some(crate::mcp_middleware::McpSocketUpdateEvent::ResourceUpdated { uri, .. }) => {
+                assert_eq!(uri, "logs://app")
+            }
+            other => panic!("expected ResourceUpdated, got {:?}", other),
+        }
+
+        // A client that has the first 7 bytes reads only what is new.
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"logs://app","range":{"start":7}}"#,
+        )
+        .await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "line 2\n");
+        assert_eq!(
+            content["_meta"]["range"],
+            serde_json::json!({"start": 7, "end": 14, "total": 14})
+        );
+
+        // The tail is opened once; a full read has everything so far.
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://app"}"#).await;
+        assert_eq!(parsed["result"]["contents"][0]["text"], "line 1\nline 2\n");
+    }
+
+    #[tokio::test]
+    async fn a_tailing_resource_keeps_only_its_last_window() {
+        let (chunks, receiver) = tokio::sync::mpsc::channel(8);
+        chunks.send("line 1\n".to_string()).await.unwrap();
+
+        let mcp = Arc::new(middleware_with_echo_tool());
+        register_log_tail(&mcp, receiver).await;
+        let session_id = initialize_session(&mcp).await;
+        let mut events = mcp
+            .sessions
+            .subscribe_to_notifications(session_id.as_str(), DateTimeAsMicroseconds::now())
+            .unwrap();
+        assert!(
+            mcp.sessions
+                .subscribe(session_id.as_str(), "logs://app".to_string())
+        );
+
+        read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://app"}"#).await;
+        chunks
+            .send("x".repeat(crate::mcp_middleware::TAIL_WINDOW_BYTES))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), events.recv())
+            .await
+            .expect("the chunk is announced");
+
+        // The first line is gone; offsets still count it.
+        let total = 7 + crate::mcp_middleware::TAIL_WINDOW_BYTES as u64;
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://app"}"#).await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(
+            content["text"].as_str().unwrap().len(),
+            crate::mcp_middleware::TAIL_WINDOW_BYTES
+        );
+        assert_eq!(
+            content["_meta"]["range"],
+            serde_json::json!({"start": 7, "end": total, "total": total})
+        );
+
+        let params = format!(
+            r#"{{"uri":"logs://app","range":{{"start":{}}}}}"#,
+            total - 3
+        );
+        let parsed = read_page(&mcp, session_id.as_str(), params.as_str()).await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "xxx");
+        assert_eq!(
+            content["_meta"]["range"],
+            serde_json::json!({"start": total - 3, "end": total, "total": total})
+        );
+
+        // Bytes no longer held: the read starts where the window does.
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"logs://app","range":{"start":0,"end":10}}"#,
+        )
+        .await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "xxx");
+        assert_eq!(
+            content["_meta"]["range"],
+            serde_json::json!({"start": 7, "end": 10, "total": total})
+        );
+    }
+
+    #[tokio::test]
+    async fn unregistering_or_resetting_a_tailing_resource_stops_its_tail() {
+        for reset in [false, true] {
+            let (chunks, receiver) = tokio::sync::mpsc::channel(8);
+            chunks.send("line 1\n".to_string()).await.unwrap();
+
+            let mcp = Arc::new(middleware_with_echo_tool());
+            register_log_tail(&mcp, receiver).await;
+            let session_id = initialize_session(&mcp).await;
+            read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://app"}"#).await;
+            assert!(!chunks.is_closed());
+
+            if reset {
+                mcp.reset().await;
+            } else {
+                assert!(mcp.unregister_dynamic_resource("logs://app").await);
+            }
+
+            // The collector is gone, and with it the receiving end.
+            tokio::time::timeout(Duration::from_secs(1), chunks.closed())
+                .await
+                .expect("the tail is stopped");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_range_read_returns_the_slice_and_where_it_lies() {
+        /// A 1000-byte blob whose every byte is its offset mod 256.
+        struct BigBlob;
+
+        #[async_trait::async_trait]
+        impl McpResourceService for BigBlob {
+            async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+                let bytes: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+                Ok(ResourceReadResult::builder()
+                    .add_blob(uri, "application/octet-stream", bytes)
+                    .build())
+            }
+        }
+
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "files://big".to_string(),
+            "big".to_string(),
+            "A big file".to_string(),
+            "application/octet-stream".to_string(),
+            Arc::new(BigBlob),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"files://big","range":{"start":100,"end":200}}"#,
+        )
+        .await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(
+            content["_meta"]["range"],
+            serde_json::json!({"start": 100, "end": 200, "total": 1000})
+        );
+        use base64::Engine;
+        let blob = base64::engine::general_purpose::STANDARD
+            .decode(content["blob"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(blob, (100..200).map(|i| i as u8).collect::<Vec<u8>>());
+
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"files://big","range":{"start":900,"end":5000}}"#,
+        )
+        .await;
+        assert_eq!(
+            parsed["result"]["contents"][0]["_meta"]["range"],
+            serde_json::json!({"start": 900, "end": 1000, "total": 1000})
+        );
+
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"files://big","range":{"start":1001}}"#,
+        )
+        .await;
+        assert_eq!(parsed["error"]["code"], -32602, "{}", parsed);
+    }
+
+    #[tokio::test]
+    async fn an_immutable_resource_carries_its_cache_directive() {
+        struct Release;
+
+        impl ResourceDefinition for Release {
+            const RESOURCE_URI: &'static str = "releases://1.0.0";
+            const RESOURCE_NAME: &'static str = "release-1.0.0";
+            const DESCRIPTION: &'static str = "Release notes of 1.0.0";
+            const MIME_TYPE: &'static str = "text/markdown";
+
+            fn get_cache_control(&self) -> Option<crate::mcp_middleware::ResourceCacheControl> {
+                Some(crate::mcp_middleware::ResourceCacheControl::immutable())
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl McpResourceService for Release {
+            async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+                Ok(ResourceReadResult::builder()
+                    .add_text(uri, Self::MIME_TYPE, "# 1.0.0")
+                    .build())
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource(Arc::new(Release));
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"releases://1.0.0"}"#).await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "# 1.0.0");
+        assert_eq!(
+            content["_meta"]["cacheControl"],
+            serde_json::json!({"immutable": true})
+        );
+
+        // A cut keeps the directive next to its range.
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uri":"releases://1.0.0","range":{"start":2}}"#,
+        )
+        .await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "1.0.0");
+        assert_eq!(content["_meta"]["cacheControl"]["immutable"], true);
+        assert_eq!(content["_meta"]["range"]["start"], 2);
+    }
+
+    #[tokio::test]
+    async fn cache_directives_skip_error_and_redirect_contents() {
+        use crate::mcp_middleware::ResourceContent;
+
+        struct Bundle;
+
+        impl ResourceDefinition for Bundle {
+            const RESOURCE_URI: &'static str = "bundles://docs";
+            const RESOURCE_NAME: &'static str = "docs-bundle";
+            const DESCRIPTION: &'static str = "Docs, one part missing, one moved";
+            const MIME_TYPE: &'static str = "text/markdown";
+
+            fn get_cache_control(&self) -> Option<crate::mcp_middleware::ResourceCacheControl> {
+                Some(crate::mcp_middleware::ResourceCacheControl::max_age(60))
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl McpResourceService for Bundle {
+            async fn read_resource(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+                Ok(ResourceReadResult {
+                    contents: vec![
+                        ResourceContent::text("bundles://docs/intro", Self::MIME_TYPE, "# Intro"),
+                        ResourceContent::error("bundles://docs/api", "Backend unavailable"),
+                        ResourceContent::redirect("bundles://docs/faq", "docs://faq"),
+                    ],
+                })
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource(Arc::new(Bundle));
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"bundles://docs"}"#).await;
+        let contents = &parsed["result"]["contents"];
+        assert_eq!(contents[0]["_meta"]["cacheControl"]["maxAge"], 60);
+        assert_eq!(contents[1]["_meta"]["isError"], true);
+        assert!(contents[1]["_meta"].get("cacheControl").is_none());
+        assert_eq!(contents[2]["_meta"]["redirect"], "docs://faq");
+        assert!(contents[2]["_meta"].get("cacheControl").is_none());
+    }
+
+    #[tokio::test]
+    async fn a_latin1_resource_is_read_as_utf8_text() {
+        struct LegacyLog;
+
+        impl ResourceDefinition for LegacyLog {
+            const RESOURCE_URI: &'static str = "logs://legacy";
+            const RESOURCE_NAME: &'static str = "legacy-log";
+            const DESCRIPTION: &'static str = "A log written in latin-1";
+            const MIME_TYPE: &'static str = "text/plain";
+        }
+
+        #[async_trait::async_trait]
+        impl McpResourceService for LegacyLog {
+            async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+                // "Résumé: 5°C" in ISO-8859-1.
+                let bytes = [
+                    b'R', 0xE9, b's', b'u', b'm', 0xE9, b':', b' ', b'5', 0xB0, b'C',
+                ];
+                Ok(ResourceReadResult::builder()
+                    .add_encoded_text(uri, Self::MIME_TYPE, bytes, "latin1")
+                    .build())
+            }
+        }
+
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource(Arc::new(LegacyLog));
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"logs://legacy"}"#).await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["text"], "Résumé: 5°C");
+        assert_eq!(content["mimeType"], "text/plain");
+        assert!(content.get("blob").is_none());
+    }
+
+    #[tokio::test]
+    async fn a_read_of_several_uris_has_an_error_block_for_a_missing_one() {
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "notes://a".to_string(),
+            "a".to_string(),
+            "Note a".to_string(),
+            "text/plain".to_string(),
+            Arc::new(NotesFolder),
+        )
+        .await;
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(
+            &mcp,
+            session_id.as_str(),
+            r#"{"uris":["notes://a","notes://missing"]}"#,
+        )
+        .await;
+
+        assert!(parsed.get("error").is_none(), "{}", parsed);
+        let contents = parsed["result"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0]["uri"], "notes://a");
+        assert_eq!(contents[0]["text"], "content of notes://a");
+        assert_eq!(contents[1]["uri"], "notes://missing");
+        assert_eq!(contents[1]["_meta"]["isError"], true);
+
+        // A single `uri` is read as before.
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://missing"}"#).await;
+        assert_eq!(parsed["error"]["code"], -32002);
+    }
+
+    #[tokio::test]
+    async fn a_uris_batch_is_one_to_a_page_under_one_deadline() {
+        let mcp = middleware_with_echo_tool()
+            .with_max_page_size(3)
+            .with_method_timeout("resources/read", Duration::from_millis(50));
+        let resource = Arc::new(ExpensiveResource::default());
+        for uri in ["remote://a", "remote://b", "remote://c", "remote://d"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "A remote feed".to_string(),
+                "text/plain".to_string(),
+                resource.clone(),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        for params in [
+            r#"{"uris":[]}"#,
+            r#"{"uris":["remote://a","remote://b","remote://c","remote://d"]}"#,
+        ] {
+            let parsed = read_page(&mcp, session_id.as_str(), params).await;
+            assert_eq!(parsed["error"]["code"], -32602, "{}", parsed);
+        }
+
+        // Each read fits the deadline; the three of them do not.
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"remote://a"}"#).await;
+        assert!(parsed.get("result").is_some(), "{}", parsed);
+        let params = r#"{"uris":["remote://a","remote://b","remote://c"]}"#;
+        let parsed = read_page(&mcp, session_id.as_str(), params).await;
+        assert_eq!(parsed["error"]["code"], -32001, "{}", parsed);
+    }
+
+    #[tokio::test]
+    async fn one_service_serves_two_uris_told_apart_by_the_uri() {
+        let mcp = middleware_with_echo_tool();
+        let folder = Arc::new(NotesFolder);
+        for uri in ["notes://a", "notes://b"] {
+            mcp.register_dynamic_resource(
+                uri.to_string(),
+                uri.to_string(),
+                "A note".to_string(),
+                "text/plain".to_string(),
+                folder.clone(),
+            )
+            .await;
+        }
+        let session_id = initialize_session(&mcp).await;
+
+        let a = read_page(&mcp, &session_id, r#"{"uri":"notes://a"}"#).await;
+        assert_eq!(a["result"]["contents"][0]["text"], "content of notes://a");
+
+        let b = read_page(&mcp, &session_id, r#"{"uri":"notes://b"}"#).await;
+        assert_eq!(b["result"]["contents"][0]["text"], "content of notes://b");
+    }
+
+    struct NotFoundPage;
+
+    #[async_trait::async_trait]
+    impl McpResourceService for NotFoundPage {
+        async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            if uri.starts_with("secret://") {
+                return Err(format!("No access to {}", uri));
+            }
+
+            Ok(ResourceReadResult {
+                contents: vec![ResourceContent::text(
+                    uri,
+                    "text/markdown",
+                    format!("# Not found\n\n{} does not exist.", uri),
+                )],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback_serves_unknown_uris() {
+        let mut mcp = middleware_with_echo_tool();
+        mcp.register_resource_fallback(Arc::new(NotFoundPage));
+        let session_id = initialize_session(&mcp).await;
+
+        let page = read_page(&mcp, &session_id, r#"{"uri":"docs://missing"}"#).await;
+        let content = &page["result"]["contents"][0];
+        assert_eq!(content["uri"], "docs://missing");
+        assert_eq!(content["text"], "# Not found\n\ndocs://missing does not exist.");
+
+        let refused = read_page(&mcp, &session_id, r#"{"uri":"secret://key"}"#).await;
+        assert_eq!(refused["error"]["code"], -32002);
+        assert_eq!(refused["error"]["message"], "No access to secret://key");
+    }
+
+    #[tokio::test]
+    async fn server_meta_is_sent_under_server_info() {
+        let mcp = middleware_with_echo_tool()
+            .with_server_meta("commit", "a19b7d0")
+            .with_server_meta("build", 42);
+
+        let body = br#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#;
+        let result = mcp.handle_post_request(None, body, None).await;
+        let (_, body, _) = read_sse_response(result).await;
+
+        assert!(
+            body.contains(r#""_meta":{"build":42,"commit":"a19b7d0"}"#),
+            "{}",
+            body
+        );
+    }
+
+    /// An alias: every read answers with a redirect to `target`.
+    struct AliasOf(&'static str);
+
+    #[async_trait::async_trait]
+    impl McpResourceService for AliasOf {
+        async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            Ok(ResourceReadResult {
+                contents: vec![ResourceContent::redirect(uri, self.0)],
+            })
+        }
+    }
+
+    async fn register_alias(mcp: &McpMiddleware, uri: &str, target: &'static str) {
+        mcp.register_dynamic_resource(
+            uri.to_string(),
+            uri.to_string(),
+            format!("Alias of {}", target),
+            "text/plain".to_string(),
+            Arc::new(AliasOf(target)),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reading_an_alias_returns_the_target_content() {
+        let mcp = middleware_with_echo_tool();
+        mcp.register_dynamic_resource(
+            "notes://canonical".to_string(),
+            "canonical".to_string(),
+            "The real note".to_string(),
+            "text/plain".to_string(),
+            Arc::new(NotesFolder),
+        )
+        .await;
+        register_alias(&mcp, "notes://latest", "notes://canonical").await;
+        register_alias(&mcp, "notes://newest", "notes://latest").await;
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://newest"}"#).await;
+        let contents = parsed["result"]["contents"].as_array().expect("contents");
+        assert_eq!(contents.len(), 1);
+        assert_eq!(contents[0]["uri"], "notes://canonical");
+        assert!(contents[0]["_meta"].get("redirect").is_none());
+    }
+
+    #[tokio::test]
+    async fn alias_loop_fails_the_read_instead_of_spinning() {
+        let mcp = middleware_with_echo_tool();
+        register_alias(&mcp, "notes://a", "notes://b").await;
+        register_alias(&mcp, "notes://b", "notes://a").await;
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://a"}"#).await;
+        assert_eq!(parsed["error"]["code"], -32603);
+        let message = parsed["error"]["message"].as_str().unwrap();
+        assert!(message.contains("notes://a -> notes://b -> notes://a"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn unfollowed_alias_is_a_link_to_its_target() {
+        let mcp = middleware_with_echo_tool().disabled_resource_redirects();
+        register_alias(&mcp, "notes://latest", "notes://canonical").await;
+        let session_id = initialize_session(&mcp).await;
+
+        let parsed = read_page(&mcp, session_id.as_str(), r#"{"uri":"notes://latest"}"#).await;
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["uri"], "notes://latest");
+        assert_eq!(content["mimeType"], "text/uri-list");
+        assert_eq!(content["text"], "notes://canonical");
+        assert_eq!(content["_meta"]["redirect"], "notes://canonical");
+    }
+
 }
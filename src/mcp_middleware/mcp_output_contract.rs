@@ -1,15 +1,60 @@
+use std::collections::BTreeMap;
+
 use super::*;
 use my_ai_agent::my_json::{
     self,
-    json_writer::{JsonObjectWriter, RawJsonObject},
+    json_writer::{JsonObjectWriter, JsonValueWriter, RawJsonObject},
 };
 
 /// JSON-RPC error codes used by this middleware (MCP conventions).
 pub const JSONRPC_PARSE_ERROR: i64 = -32700;
+pub const JSONRPC_INVALID_REQUEST: i64 = -32600;
 pub const JSONRPC_METHOD_NOT_FOUND: i64 = -32601;
 pub const JSONRPC_INVALID_PARAMS: i64 = -32602;
 pub const JSONRPC_INTERNAL_ERROR: i64 = -32603;
 pub const JSONRPC_RESOURCE_NOT_FOUND: i64 = -32002;
+/// The request ran past its deadline. Same code the MCP SDKs use.
+pub const JSONRPC_REQUEST_TIMEOUT: i64 = -32001;
+/// The JSON-RPC range for implementation-defined server errors.
+pub const JSONRPC_SERVER_ERROR_RANGE: std::ops::RangeInclusive<i64> = -32099..=-32000;
+
+/// Whether a host may answer with `code`: it has to be in
+/// [`JSONRPC_SERVER_ERROR_RANGE`] and not one of the codes in it the
+/// middleware sends itself, so clients can still tell those apart.
+pub fn check_server_error_code(code: i64) -> Result<(), String> {
+    if !JSONRPC_SERVER_ERROR_RANGE.contains(&code) {
+        return Err(format!(
+            "Error code {} is outside the server-error range -32099..=-32000",
+            code
+        ));
+    }
+
+    if code == JSONRPC_REQUEST_TIMEOUT || code == JSONRPC_RESOURCE_NOT_FOUND {
+        return Err(format!("Error code {} is reserved by the middleware", code));
+    }
+
+    Ok(())
+}
+
+/// How every message the compilers produce is framed. With the default
+/// `sse-framing` feature a message is an SSE event, `data: <json>\n\n`.
+/// A build without it (stdio or websocket embeddings) gets the bare
+/// JSON-RPC message followed by a newline — newline-delimited JSON, so
+/// batched notifications still split apart.
+#[cfg(feature = "sse-framing")]
+pub const FRAME_PREFIX: &str = "data: ";
+#[cfg(feature = "sse-framing")]
+pub const FRAME_SUFFIX: &str = "\n\n";
+#[cfg(not(feature = "sse-framing"))]
+pub const FRAME_PREFIX: &str = "";
+#[cfg(not(feature = "sse-framing"))]
+pub const FRAME_SUFFIX: &str = "\n";
+
+/// `content-type` of streamed responses, matching the framing.
+#[cfg(feature = "sse-framing")]
+pub const FRAME_CONTENT_TYPE: &str = "text/event-stream";
+#[cfg(not(feature = "sse-framing"))]
+pub const FRAME_CONTENT_TYPE: &str = "application/json";
 
 /// Protocol revisions this middleware implements. Ordered oldest →
 /// newest; the last entry is what we answer with when the client
@@ -33,40 +78,278 @@ pub fn negotiate_protocol_version(requested: &str) -> &str {
     latest_protocol_version()
 }
 
+/// Appended to instructions cut by [`truncate_instructions`].
+pub const INSTRUCTIONS_TRUNCATED_NOTE: &str = "[Instructions truncated.]";
+
+/// `instructions` cut down to at most `max_len` bytes, note included:
+/// at the end of the last sentence that fits, else at the last word
+/// break, else mid-word. A `max_len` too small for the note gets the
+/// text cut at `max_len`, without it. `None` when they fit as they are.
+pub fn truncate_instructions(instructions: &str, max_len: usize) -> Option<String> {
+    if instructions.len() <= max_len {
+        return None;
+    }
+
+    if max_len < INSTRUCTIONS_TRUNCATED_NOTE.len() {
+        let mut end = max_len;
+        while !instructions.is_char_boundary(end) {
+            end -= 1;
+        }
+        return Some(instructions[..end].to_string());
+    }
+
+    // Room for the text itself: the note and the space before it.
+    let budget = max_len.saturating_sub(INSTRUCTIONS_TRUNCATED_NOTE.len() + 1);
+    let mut end = budget;
+    while !instructions.is_char_boundary(end) {
+        end -= 1;
+    }
+    let fitting = &instructions[..end];
+
+    let sentence_end = fitting
+        .char_indices()
+        .filter(|(i, c)| {
+            matches!(c, '.' | '!' | '?' | '\n')
+                && instructions[i + c.len_utf8()..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map(|(i, c)| i + c.len_utf8())
+        .last();
+
+    let cut = match sentence_end {
+        Some(cut) => cut,
+        None if instructions[end..].starts_with(char::is_whitespace) => end,
+        None => fitting.rfind(char::is_whitespace).unwrap_or(end),
+    };
+
+    let mut truncated = instructions[..cut].trim_end().to_string();
+    if !truncated.is_empty() {
+        truncated.push(' ');
+    }
+    truncated.push_str(INSTRUCTIONS_TRUNCATED_NOTE);
+    Some(truncated)
+}
+
+/// What the server offers: advertised at `initialize` beyond the
+/// resources capability, which is always there, and — with
+/// `McpMiddleware::with_capability_gating` — enforced on every request,
+/// so the two can not drift apart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerCapabilities {
+    pub tools: bool,
+    pub prompts: bool,
+    /// `resources.subscribe`: the server can push
+    /// `notifications/resources/updated` to the session.
+    pub resource_subscribe: bool,
+    /// `completions`: the server answers `completion/complete`.
+    pub completions: bool,
+    /// `capabilities.experimental`: non-standard features, by name.
+    /// Empty means no `experimental`.
+    pub experimental: BTreeMap<String, serde_json::Value>,
+}
+
+impl ServerCapabilities {
+    /// Whether a client may call `method`: the methods of `tools`,
+    /// `prompts` and `completions` need the capability,
+    /// `resources/subscribe` and `resources/unsubscribe` need
+    /// `resources.subscribe`. `Err` says which capability is missing.
+    pub fn allows(&self, method: &McpMethod) -> Result<(), String> {
+        let missing = match method.capability() {
+            Some("tools") if !self.tools => Some("tools"),
+            Some("prompts") if !self.prompts => Some("prompts"),
+            Some("completions") if !self.completions => Some("completions"),
+            Some("resources")
+                if !self.resource_subscribe
+                    && matches!(
+                        method,
+                        McpMethod::ResourcesSubscribe | McpMethod::ResourcesUnsubscribe
+                    ) =>
+            {
+                Some("resources.subscribe")
+            }
+            _ => None,
+        };
+
+        match missing {
+            Some(capability) => Err(format!(
+                "Method not supported: {} needs the {} capability, which this server does not advertise",
+                method, capability
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// One line of guidance for the model per enabled capability, in a
+    /// fixed order.
+    pub fn guidance(&self) -> Vec<&'static str> {
+        let mut result = Vec::new();
+        if self.tools {
+            result.push(
+                "The tool list may change during the session: re-read tools/list when notified that it changed.",
+            );
+        }
+        if self.prompts {
+            result.push(
+                "The prompt list may change during the session: re-read prompts/list when notified that it changed.",
+            );
+        }
+        if self.resource_subscribe {
+            result.push(
+                "This server supports resource subscriptions: call resources/subscribe to be notified when a resource changes.",
+            );
+        }
+        result
+    }
+}
+
+/// `instructions` as written, followed — after a blank line — by
+/// [`ServerCapabilities::guidance`].
+pub fn instructions_with_capability_guidance(
+    instructions: &str,
+    capabilities: &ServerCapabilities,
+) -> String {
+    let guidance = capabilities.guidance();
+    if guidance.is_empty() {
+        return instructions.to_string();
+    }
+
+    let guidance = guidance.join("\n");
+    if instructions.trim().is_empty() {
+        return guidance;
+    }
+    format!("{}\n\n{}", instructions.trim_end(), guidance)
+}
+
+/// `serverInfo` of the `initialize` result.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    /// Goes out as `serverInfo._meta`; empty means no `_meta`.
+    pub meta: BTreeMap<String, serde_json::Value>,
+}
+
+/// The result of `initialize`, serialized by
+/// [`InitializeResult::to_json_rpc`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InitializeResult {
+    pub protocol_version: String,
+    pub capabilities: ServerCapabilities,
+    pub server_info: ServerInfo,
+    /// Sent as they are: the capability guidance, when wanted, is
+    /// already part of them.
+    pub instructions: String,
+}
+
+impl InitializeResult {
+    /// A server name and a protocol revision this middleware implements
+    /// are required; everything else may be empty.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.server_info.name.trim().is_empty() {
+            return Err("serverInfo.name must not be empty".to_string());
+        }
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&self.protocol_version.as_str()) {
+            return Err(format!(
+                "Protocol version '{}' is not supported",
+                self.protocol_version
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The framed JSON-RPC response to the `initialize` request `id`.
+    pub fn to_json_rpc(&self, id: &RequestId) -> String {
+        let capabilities = &self.capabilities;
+        let server_meta_json =
+            serde_json::to_string(&self.server_info.meta).unwrap_or_else(|_| "{}".to_string());
+        let experimental_json =
+            serde_json::to_string(&capabilities.experimental).unwrap_or_else(|_| "{}".to_string());
+
+        let json_builder =
+            my_json::json_writer::JsonObjectWriter::new().write_json_object("result", |result| {
+                result
+                    .write("protocolVersion", self.protocol_version.as_str())
+                    .write_json_object("capabilities", |cap| {
+                        // Resources capability is advertised unconditionally:
+                        // dynamic resources may be registered at any moment
+                        // after initialize, and clients only honor
+                        // `resources/list_changed` for capabilities they were
+                        // told about up front.
+                        cap.write_json_object("resources", |res| {
+                            res.write("subscribe", capabilities.resource_subscribe)
+                                .write("listChanged", true)
+                        })
+                        .write_json_object_if("tools", capabilities.tools, |res| {
+                            res.write("listChanged", true)
+                        })
+                        .write_json_object_if("prompts", capabilities.prompts, |res| {
+                            res.write("listChanged", true)
+                        })
+                        .write_json_object_if("completions", capabilities.completions, |res| res)
+                        .write_if(
+                            "experimental",
+                            RawJsonObject::AsStr(&experimental_json),
+                            !capabilities.experimental.is_empty(),
+                        )
+                    })
+                    .write_json_object("serverInfo", |server_info| {
+                        server_info
+                            .write("name", self.server_info.name.as_str())
+                            .write("version", self.server_info.version.as_str())
+                            .write_if(
+                                "_meta",
+                                RawJsonObject::AsStr(&server_meta_json),
+                                !self.server_info.meta.is_empty(),
+                            )
+                    })
+                    .write("instructions", self.instructions.as_str())
+            });
+
+        build(json_builder, id)
+    }
+}
+
+/// [`InitializeResult::to_json_rpc`] from loose arguments.
+/// `server_meta` goes out as `serverInfo._meta`; empty means no `_meta`.
+/// With `capability_guidance` the instructions get the guidance of the
+/// enabled capabilities appended, see
+/// [`instructions_with_capability_guidance`].
+#[deprecated(
+    since = "0.9.0",
+    note = "build an `InitializeResult` and call `to_json_rpc`"
+)]
+#[allow(clippy::too_many_arguments)]
 pub fn compile_init_response(
     name: &str,
     version: &str,
     instructions: &str,
     protocol_version: &str,
     id: &RequestId,
-    has_tools: bool,
-    has_prompts: bool,
+    capabilities: &ServerCapabilities,
+    capability_guidance: bool,
+    server_meta: &BTreeMap<String, serde_json::Value>,
 ) -> String {
-    let json_builder =
-        my_json::json_writer::JsonObjectWriter::new().write_json_object("result", |result| {
-            result
-                .write("protocolVersion", protocol_version)
-                .write_json_object("capabilities", |cap| {
-                    // Resources capability is advertised unconditionally:
-                    // dynamic resources may be registered at any moment
-                    // after initialize, and clients only honor
-                    // `resources/list_changed` for capabilities they were
-                    // told about up front.
-                    cap.write_json_object("resources", |res| {
-                        res.write("subscribe", true).write("listChanged", true)
-                    })
-                    .write_json_object_if("tools", has_tools, |res| res.write("listChanged", true))
-                    .write_json_object_if("prompts", has_prompts, |res| {
-                        res.write("listChanged", true)
-                    })
-                })
-                .write_json_object("serverInfo", |server_info| {
-                    server_info.write("name", name).write("version", version)
-                })
-                .write("instructions", instructions)
-        });
+    let instructions = if capability_guidance {
+        instructions_with_capability_guidance(instructions, capabilities)
+    } else {
+        instructions.to_string()
+    };
 
-    build(json_builder, id)
+    InitializeResult {
+        protocol_version: protocol_version.to_string(),
+        capabilities: capabilities.clone(),
+        server_info: ServerInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            meta: server_meta.clone(),
+        },
+        instructions,
+    }
+    .to_json_rpc(id)
 }
 
 /// JSON-RPC 2.0 error object (without SSE framing) — used as the body
@@ -81,7 +364,7 @@ pub fn compile_jsonrpc_error_body(code: i64, message: &str, id: &RequestId) -> S
         .build()
 }
 
-/// JSON-RPC 2.0 error response as an SSE `data:` frame.
+/// JSON-RPC 2.0 error response, framed (see [`FRAME_PREFIX`]).
 pub fn compile_jsonrpc_error(code: i64, message: &str, id: &RequestId) -> String {
     let json_builder = JsonObjectWriter::new().write_json_object("error", |err| {
         err.write("code", code).write("message", message)
@@ -90,6 +373,22 @@ pub fn compile_jsonrpc_error(code: i64, message: &str, id: &RequestId) -> String
     build(json_builder, id)
 }
 
+/// [`compile_jsonrpc_error`] with `data`, which is JSON text.
+pub fn compile_jsonrpc_error_with_data(
+    code: i64,
+    message: &str,
+    data: &str,
+    id: &RequestId,
+) -> String {
+    let json_builder = JsonObjectWriter::new().write_json_object("error", |err| {
+        err.write("code", code)
+            .write("message", message)
+            .write("data", RawJsonObject::AsStr(data))
+    });
+
+    build(json_builder, id)
+}
+
 pub fn compile_resource_templates_list(id: &RequestId) -> String {
     let json_builder = JsonObjectWriter::new().write_json_object("result", |result| {
         result.write_json_array("resourceTemplates", |arr| arr)
@@ -98,39 +397,111 @@ pub fn compile_resource_templates_list(id: &RequestId) -> String {
     build(json_builder, id)
 }
 
-pub fn compile_tool_calls(tools: Vec<ToolCallSchemaData>, id: &RequestId) -> String {
+/// Without `output_schema` the tools are listed with their input schema
+/// only: a tool that declares an output schema must send structured
+/// results.
+pub fn compile_tool_calls(
+    tools: Vec<ToolCallSchemaData>,
+    output_schema: bool,
+    id: &RequestId,
+) -> String {
+    compile_tool_calls_page(tools, output_schema, id, None)
+}
+
+/// [`compile_tool_calls`] as one page of the list, with the cursor of
+/// the next page.
+pub fn compile_tool_calls_page(
+    tools: Vec<ToolCallSchemaData>,
+    output_schema: bool,
+    id: &RequestId,
+    next_cursor: Option<&str>,
+) -> String {
     let json_builder = JsonObjectWriter::new().write_json_object("result", |result| {
-        result.write_json_array("tools", |mut arr| {
+        let result = result.write_json_array("tools", |mut arr| {
             for tool in tools.iter() {
                 arr = arr.write_json_object(|obj| {
                     obj.write("name", tool.mcp.get_fn_name())
-                        .write("description", tool.mcp.get_description())
-                        .write_ref("inputSchema", &tool.input)
-                        .write_ref("outputSchema", &tool.output)
+                        .write_if(
+                            "description",
+                            tool.mcp.get_description(),
+                            !tool.mcp.get_description().is_empty(),
+                        )
+                        .write("inputSchema", RawJsonObject::AsStr(&tool.schema.input))
+                        .write_if(
+                            "outputSchema",
+                            RawJsonObject::AsStr(&tool.schema.output),
+                            output_schema,
+                        )
+                        .write_json_object_if(
+                            "annotations",
+                            tool.mcp.supports_streaming(),
+                            |annotations| annotations.write("streamingHint", true),
+                        )
                 });
             }
 
             arr
-        })
+        });
+
+        match next_cursor {
+            Some(cursor) => result.write("nextCursor", cursor),
+            None => result,
+        }
     });
 
     build(json_builder, id)
 }
 
-pub fn compile_prompts_list(prompts: Vec<super::PromptSchemaData>, id: &RequestId) -> String {
+/// An optional argument is listed without `required` unless
+/// `explicit_required_false` asks for `"required": false` on it too.
+pub fn compile_prompts_list(
+    prompts: Vec<super::PromptSchemaData>,
+    explicit_required_false: bool,
+    id: &RequestId,
+) -> String {
+    compile_prompts_list_page(prompts, explicit_required_false, id, None)
+}
+
+/// [`compile_prompts_list`] as one page of the list, with the cursor of
+/// the next page.
+pub fn compile_prompts_list_page(
+    prompts: Vec<super::PromptSchemaData>,
+    explicit_required_false: bool,
+    id: &RequestId,
+    next_cursor: Option<&str>,
+) -> String {
     let json_builder = JsonObjectWriter::new().write_json_object("result", |result| {
-        result.write_json_array("prompts", |mut arr| {
+        let result = result.write_json_array("prompts", |mut arr| {
             for prompt in prompts.iter() {
                 arr = arr.write_json_object(|obj| {
                     obj.write("name", prompt.prompt.get_prompt_name())
-                        .write("description", prompt.prompt.get_description())
+                        .write_if(
+                            "description",
+                            prompt.description.as_str(),
+                            !prompt.description.is_empty(),
+                        )
                         .write_json_array("arguments", |mut args_arr| {
                             for arg in prompt.argument_descriptions.iter() {
+                                let schema = arg.schema.as_ref().map(|schema| schema.to_string());
                                 args_arr = args_arr.write_json_object(|arg_obj| {
-                                    arg_obj
+                                    let arg_obj = arg_obj
                                         .write("name", arg.name.as_str())
-                                        .write("description", arg.description.as_str())
-                                        .write("required", arg.required)
+                                        .write_if(
+                                            "description",
+                                            arg.description.as_str(),
+                                            !arg.description.is_empty(),
+                                        )
+                                        .write_if(
+                                            "required",
+                                            arg.required,
+                                            arg.required || explicit_required_false,
+                                        );
+                                    match schema.as_ref() {
+                                        Some(schema) => {
+                                            arg_obj.write("schema", RawJsonObject::AsStr(schema))
+                                        }
+                                        None => arg_obj,
+                                    }
                                 });
                             }
                             args_arr
@@ -139,37 +510,92 @@ pub fn compile_prompts_list(prompts: Vec<super::PromptSchemaData>, id: &RequestI
             }
 
             arr
-        })
+        });
+
+        match next_cursor {
+            Some(cursor) => result.write("nextCursor", cursor),
+            None => result,
+        }
     });
 
     build(json_builder, id)
 }
 
 pub fn compile_get_prompt_response(response: PromptExecutionResult, id: &RequestId) -> String {
-    let mut result = JsonObjectWriter::new()
-        .write("jsonrpc", "2.0")
-        .write("id", id)
-        .write_json_object("result", |result| {
-            result
-                .write("description", response.description.as_str())
-                .write_json_array("messages", |arr| {
-                    arr.write_json_object(|obj| {
-                        obj.write("role", "user")
-                            .write_json_object("content", |content| {
-                                content
-                                    .write("type", "text")
-                                    .write("text", response.message.as_str())
-                            })
-                    })
-                })
+    compile_get_prompt_response_parts(response, id).collect()
+}
+
+/// What closes a `prompts/get` response after its last message: the
+/// `messages` array, `result` and the message itself.
+const PROMPT_RESPONSE_CLOSE: &str = "]}}";
+
+/// [`compile_get_prompt_response`] as the parts it is written in: the
+/// head with the text message, one part per embedded resource, then
+/// the closing part. The resources are already in memory — the prompt
+/// returned them — but each is serialized only when its part is pulled,
+/// so the response is never built as one string next to them.
+/// Concatenated, the parts are exactly [`compile_get_prompt_response`].
+pub fn compile_get_prompt_response_parts(
+    response: PromptExecutionResult,
+    id: &RequestId,
+) -> PromptResponseParts {
+    let message = JsonObjectWriter::new()
+        .write("role", "user")
+        .write_json_object("content", |content| {
+            content
+                .write("type", "text")
+                .write("text", response.message.as_str())
         })
         .build();
 
-    result.insert_str(0, "data: ");
-    result.push('\n');
-    result.push('\n');
+    // Written by hand up to the open `messages` array, which the writer
+    // can not leave open; the last part closes what is opened here.
+    let mut head = FRAME_PREFIX.to_string();
+    head.push_str(r#"{"jsonrpc":"2.0","id":"#);
+    JsonValueWriter::write(&id, &mut head);
+    head.push_str(r#","result":{"description":"#);
+    JsonValueWriter::write(&response.description.as_str(), &mut head);
+    head.push_str(r#","messages":["#);
+    head.push_str(message.as_str());
+
+    PromptResponseParts {
+        head: Some(head),
+        resources: response.embedded_resources.into_iter(),
+        tail: Some(format!("{}{}", PROMPT_RESPONSE_CLOSE, FRAME_SUFFIX)),
+    }
+}
 
-    result
+/// See [`compile_get_prompt_response_parts`].
+pub struct PromptResponseParts {
+    head: Option<String>,
+    resources: std::vec::IntoIter<ResourceContent>,
+    tail: Option<String>,
+}
+
+impl Iterator for PromptResponseParts {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(head) = self.head.take() {
+            return Some(head);
+        }
+
+        // Each embedded resource is a message of its own: a prompt
+        // message carries exactly one content block.
+        if let Some(resource) = self.resources.next() {
+            let message = JsonObjectWriter::new()
+                .write("role", "user")
+                .write_json_object("content", |content| {
+                    content
+                        .write("type", "resource")
+                        .write_json_object("resource", |obj| write_resource_content(obj, &resource))
+                })
+                .build();
+            return Some(format!(",{}", message));
+        }
+
+        self.tail.take()
+    }
 }
 
 pub fn compile_resources_list(
@@ -184,8 +610,12 @@ pub fn compile_resources_list(
                     let mut obj = obj
                         .write("uri", resource.resource.get_resource_uri())
                         .write("name", resource.resource.get_resource_name())
-                        .write("description", resource.resource.get_description())
-                        .write("mimeType", resource.resource.get_mime_type());
+                        .write_if(
+                            "description",
+                            resource.resource.get_description(),
+                            !resource.resource.get_description().is_empty(),
+                        )
+                        .write("mimeType", list_mime_type(resource.resource.get_mime_type()));
 
                     if let Some(title) = resource.resource.get_title() {
                         obj = obj.write("title", title);
@@ -232,78 +662,395 @@ pub fn compile_resources_list(
     build(json_builder, id)
 }
 
-pub fn compile_read_resource_response(response: ResourceReadResult, id: &RequestId) -> String {
+/// A resource registered without a MIME type is listed as text.
+fn list_mime_type(mime_type: &str) -> &str {
+    if mime_type.is_empty() {
+        return DEFAULT_TEXT_MIME_TYPE;
+    }
+    mime_type
+}
+
+/// One resource content object — an item of `resources/read`'s
+/// `contents`, or the `resource` of an embedded-resource block.
+fn write_resource_content(obj: JsonObjectWriter, content: &ResourceContent) -> JsonObjectWriter {
+    let mut obj = obj
+        .write("uri", content.uri.as_str())
+        .write("mimeType", content.effective_mime_type());
+
+    obj = match &content.body {
+        // According to spec, text should be a direct string field, not nested
+        ResourceBody::Text(text) => obj.write("text", text.as_str()),
+        // blob is base64-encoded string
+        ResourceBody::Blob(blob) => obj.write("blob", blob.as_str()),
+        // A directory listing travels as text: the JSON
+        // array of its children.
+        ResourceBody::Listing(listing) => {
+            let children = serde_json::to_string(listing).unwrap_or_else(|_| "[]".to_string());
+            obj.write("text", children.as_str())
+        }
+        ResourceBody::Error(error) => obj.write("text", error.as_str()),
+        ResourceBody::Redirect(target) => obj.write("text", target.as_str()),
+    };
+
+    let is_error = matches!(content.body, ResourceBody::Error(_));
+    let redirect = match &content.body {
+        ResourceBody::Redirect(target) => Some(target.as_str()),
+        _ => None,
+    };
+
+    // One `_meta` for all of them: a cut of a cacheable resource
+    // carries both its range and its cache directives.
+    let has_meta = is_error
+        || redirect.is_some()
+        || content.range.is_some()
+        || content.cache_control.is_some();
+
+    obj = obj.write_json_object_if("_meta", has_meta, |mut meta| {
+        if is_error {
+            meta = meta.write("isError", true);
+        }
+
+        if let Some(target) = redirect {
+            meta = meta.write("redirect", target);
+        }
+
+        if let Some(range) = &content.range {
+            meta = meta.write_json_object("range", |range_obj| {
+                range_obj
+                    .write("start", range.start)
+                    .write("end", range.end)
+                    .write("total", range.total)
+            });
+        }
+
+        if let Some(cache_control) = &content.cache_control {
+            meta = meta.write_json_object("cacheControl", |cache_obj| {
+                cache_obj
+                    .write_if(
+                        "maxAge",
+                        cache_control.max_age.unwrap_or_default(),
+                        cache_control.max_age.is_some(),
+                    )
+                    .write_if("immutable", true, cache_control.immutable)
+            });
+        }
+
+        meta
+    });
+
+    if let Some(last_modified) = &content.last_modified {
+        obj = obj.write_json_object("annotations", |annotations| {
+            annotations.write("lastModified", last_modified.as_str())
+        });
+    }
+
+    obj
+}
+
+pub fn compile_read_resource_response(
+    response: ResourceReadResult,
+    id: &RequestId,
+    next_cursor: Option<&str>,
+) -> String {
     let mut result = JsonObjectWriter::new()
         .write("jsonrpc", "2.0")
         .write("id", id)
         .write_json_object("result", |result| {
-            result.write_json_array("contents", |mut arr| {
+            let mut result = result.write_json_array("contents", |mut arr| {
                 for content in response.contents.iter() {
-                    arr = arr.write_json_object(|obj| {
-                        let mut obj = obj
-                            .write("uri", content.uri.as_str())
-                            .write("mimeType", content.mime_type.as_str());
-
-                        // According to spec, text should be a direct string field, not nested
-                        if let Some(text) = &content.text {
-                            obj = obj.write("text", text.as_str());
-                        }
-
-                        // blob is base64-encoded string
-                        if let Some(blob) = &content.blob {
-                            obj = obj.write("blob", blob.as_str());
-                        }
-
-                        obj
-                    });
+                    arr = arr.write_json_object(|obj| write_resource_content(obj, content));
                 }
                 arr
-            })
+            });
+
+            if let Some(cursor) = next_cursor {
+                result = result.write("nextCursor", cursor);
+            }
+
+            result
         })
         .build();
 
-    result.insert_str(0, "data: ");
-    result.push('\n');
-    result.push('\n');
+    result.insert_str(0, FRAME_PREFIX);
+    result.push_str(FRAME_SUFFIX);
 
     result
 }
 
+/// The result of a `tools/call`, serialized by
+/// [`ToolCallResult::to_json_rpc`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolCallResult {
+    /// What the tool returned, as JSON: `structuredContent`, and the
+    /// text content block unless `content` has one of its own. For a
+    /// failed call, the message.
+    pub structured_json: String,
+    /// `content` in order. Binary blocks are already base64: nothing
+    /// here turns bytes into text, so the response is valid UTF-8
+    /// whatever a tool produced.
+    pub content: Vec<ToolContentBlock>,
+    /// Goes out as `_meta` of the first text content block.
+    pub content_meta: Option<serde_json::Value>,
+    /// Go out as `annotations.citations` of the first text content
+    /// block.
+    pub citations: Vec<TextCitation>,
+    /// Marked with `_meta.cached: true` on the result itself.
+    pub cached: bool,
+    /// Sent with `isError: true` and without `structuredContent`.
+    pub is_error: bool,
+    /// Nothing to show (see [`VoidOutput`]): `structured_json` is not
+    /// sent as text, and `structuredContent` is `{}`.
+    pub void_output: bool,
+}
+
+impl ToolCallResult {
+    /// A call the tool ran and failed: `message` is its only content.
+    pub fn failed(message: String) -> Self {
+        Self {
+            structured_json: message,
+            is_error: true,
+            ..Default::default()
+        }
+    }
+
+    /// The framed JSON-RPC response to the `tools/call` request `id`.
+    pub fn to_json_rpc(&self, options: ToolCallResponseOptions, id: &RequestId) -> String {
+        let is_void = !self.is_error && self.void_output;
+
+        let json_text = if is_void || self.content.iter().any(ToolContentBlock::is_text) {
+            None
+        } else {
+            Some(self.structured_json.as_str())
+        };
+
+        let structured_content = if is_void {
+            "{}"
+        } else {
+            self.structured_json.as_str()
+        };
+
+        let content_meta_json = self.content_meta.as_ref().map(|meta| meta.to_string());
+        let progress_token_json = options.progress_token.map(|token| token.to_string());
+
+        let mut result = JsonObjectWriter::new()
+            .write("jsonrpc", "2.0")
+            .write("id", id)
+            .write_json_object("result", |result| {
+                result
+                    .write_json_array("content", |mut arr| {
+                        // Citations and `_meta` go on the first text block.
+                        let mut annotations =
+                            Some((self.citations.as_slice(), content_meta_json.as_deref()));
+                        if let Some(text) = json_text {
+                            arr = arr.write_json_object(|obj| {
+                                write_text_block(obj, text, annotations.take())
+                            });
+                        }
+                        for block in self.content.iter() {
+                            arr = match block {
+                                ToolContentBlock::Text(text) => arr.write_json_object(|obj| {
+                                    write_text_block(obj, text, annotations.take())
+                                }),
+                                ToolContentBlock::Binary(block) => arr.write_json_object(|obj| {
+                                    obj.write("type", block.content_type)
+                                        .write("data", block.data.as_str())
+                                        .write("mimeType", block.mime_type.as_str())
+                                }),
+                            };
+                        }
+                        arr
+                    })
+                    .write_if(
+                        "structuredContent",
+                        RawJsonObject::AsStr(structured_content),
+                        options.structured && !self.is_error,
+                    )
+                    .write("isError", self.is_error)
+                    .write_json_object_if(
+                        "_meta",
+                        self.cached || progress_token_json.is_some(),
+                        |meta| {
+                            meta.write_if("cached", true, self.cached).write_if(
+                                "progressToken",
+                                RawJsonObject::AsStr(
+                                    progress_token_json.as_deref().unwrap_or_default(),
+                                ),
+                                progress_token_json.is_some(),
+                            )
+                        },
+                    )
+            })
+            .build();
+
+        result.insert_str(0, FRAME_PREFIX);
+        result.push_str(FRAME_SUFFIX);
+        result
+    }
+}
+
+/// A text of the tool's own replaces the JSON in the text block, and so
+/// does the instruction when the tool added no text: a tool's text wins
+/// over its instruction, see [`ToolCallOutput::instruction`].
+/// `structuredContent` stays the JSON either way.
+impl From<ExecutedToolCall> for ToolCallResult {
+    fn from(executed: ExecutedToolCall) -> Self {
+        let mut content = executed.content;
+        if let Some(instruction) = executed.instruction {
+            if !content.iter().any(ToolContentBlock::is_text) {
+                content.insert(0, ToolContentBlock::Text(instruction));
+            }
+        }
+
+        Self {
+            structured_json: executed.structured_json,
+            content,
+            content_meta: executed.content_meta,
+            citations: executed.citations,
+            cached: executed.cached,
+            is_error: false,
+            void_output: executed.void_output,
+        }
+    }
+}
+
+/// How a [`ToolCallResult`] goes out, apart from what it holds.
+#[derive(Debug, Clone, Copy)]
+pub struct ToolCallResponseOptions<'s> {
+    /// The request's `_meta.progressToken`, echoed as the result's
+    /// `_meta.progressToken`.
+    pub progress_token: Option<&'s serde_json::Value>,
+    /// Without it the result is the content blocks alone. See
+    /// [`McpMiddleware::disabled_structured_content`].
+    pub structured: bool,
+}
+
+/// [`ToolCallResult::to_json_rpc`] from loose arguments: `instruction`
+/// is the text block, and `binary` follows it.
+#[deprecated(
+    since = "0.9.0",
+    note = "build a `ToolCallResult` and call `to_json_rpc`"
+)]
+#[allow(clippy::too_many_arguments)]
 pub fn compile_execute_tool_call_response(
     response: String,
     instruction: Option<String>,
+    content_meta: Option<&serde_json::Value>,
+    citations: &[TextCitation],
+    binary: &[ToolBinaryContent],
+    cached: bool,
+    progress_token: Option<&serde_json::Value>,
+    structured: bool,
     id: &RequestId,
     is_error: bool,
 ) -> String {
-    let content_text = match instruction.as_deref() {
-        Some(text) => text.to_string(),
-        None => response.clone(),
+    // Loose arguments carry no type: `null` is taken for void.
+    let void_output = !is_error && response == VOID_TOOL_OUTPUT;
+    let content = instruction
+        .map(ToolContentBlock::Text)
+        .into_iter()
+        .chain(binary.iter().cloned().map(ToolContentBlock::Binary))
+        .collect();
+
+    ToolCallResult {
+        structured_json: response,
+        content,
+        content_meta: content_meta.cloned(),
+        citations: citations.to_vec(),
+        cached,
+        is_error,
+        void_output,
+    }
+    .to_json_rpc(
+        ToolCallResponseOptions {
+            progress_token,
+            structured,
+        },
+        id,
+    )
+}
+
+/// A text content block; `annotations` are the citations and the
+/// `_meta` JSON of the result, for the block that carries them.
+fn write_text_block(
+    obj: JsonObjectWriter,
+    text: &str,
+    annotations: Option<(&[TextCitation], Option<&str>)>,
+) -> JsonObjectWriter {
+    let mut obj = obj.write("type", "text").write("text", text);
+    let Some((citations, meta)) = annotations else {
+        return obj;
     };
 
-    let mut result = JsonObjectWriter::new()
-        .write("jsonrpc", "2.0")
-        .write("id", id)
-        .write_json_object("result", |result| {
-            result
-                .write_json_array("content", |arr| {
-                    arr.write_json_object(|obj| {
-                        obj.write("type", "text").write("text", content_text.as_str())
-                    })
+    if !citations.is_empty() {
+        obj = obj.write_json_object("annotations", |annotations| {
+            write_citations(annotations, citations)
+        });
+    }
+    match meta {
+        Some(meta) => obj.write("_meta", RawJsonObject::AsStr(meta)),
+        None => obj,
+    }
+}
+
+fn write_citations(annotations: JsonObjectWriter, citations: &[TextCitation]) -> JsonObjectWriter {
+    annotations.write_json_array("citations", |mut arr| {
+        for citation in citations {
+            arr = arr.write_json_object(|obj| {
+                let obj = obj
+                    .write("start", citation.start as u64)
+                    .write("end", citation.end as u64)
+                    .write("uri", citation.uri.as_str());
+                match citation.title.as_deref() {
+                    Some(title) => obj.write("title", title),
+                    None => obj,
+                }
+            });
+        }
+        arr
+    })
+}
+
+/// Answer to a `tools/call` with `_meta.dryRun` whose arguments are
+/// valid. Carries no `structuredContent`: the tool did not run.
+pub fn compile_dry_run_response(id: &RequestId) -> String {
+    let json_builder = JsonObjectWriter::new().write_json_object("result", |result| {
+        result
+            .write_json_array("content", |arr| {
+                arr.write_json_object(|obj| {
+                    obj.write("type", "text").write("text", "Arguments are valid")
                 })
-                .write_if(
-                    "structuredContent",
-                    RawJsonObject::AsStr(&response),
-                    !is_error,
-                )
-                .write("isError", is_error)
-        })
-        .build();
+            })
+            .write("isError", false)
+            .write_json_object("_meta", |meta| meta.write("dryRun", true))
+    });
 
-    result.push('\n');
-    result.push('\n');
+    build(json_builder, id)
+}
 
-    result.insert_str(0, "data: ");
-    result
+/// The answer to a `completion/complete`. Values past
+/// [`MAX_COMPLETION_VALUES`] are dropped, and `hasMore` is set then.
+pub fn compile_completion_response(completion: &CompletionValues, id: &RequestId) -> String {
+    let values = &completion.values[..completion.values.len().min(MAX_COMPLETION_VALUES)];
+    let has_more = completion.has_more || values.len() < completion.values.len();
+
+    let json_builder = JsonObjectWriter::new().write_json_object("result", |result| {
+        result.write_json_object("completion", |obj| {
+            obj.write_json_array("values", |mut arr| {
+                for value in values {
+                    arr = arr.write(value.as_str());
+                }
+                arr
+            })
+            .write_if(
+                "total",
+                completion.total.unwrap_or_default() as u64,
+                completion.total.is_some(),
+            )
+            .write("hasMore", has_more)
+        })
+    });
+
+    build(json_builder, id)
 }
 
 /// `{"jsonrpc":"2.0","id":...,"result":{}}` — used for `ping`,
@@ -315,21 +1062,158 @@ pub fn compile_empty_result_response(id: &RequestId) -> String {
         .write_json_object("result", |o| o)
         .build();
 
-    result.insert_str(0, "data: ");
-    result.push('\n');
+    result.insert_str(0, FRAME_PREFIX);
+    result.push_str(FRAME_SUFFIX);
+
+    result
+}
+
+/// Strips the transport framing `build` adds, leaving the bare JSON-RPC
+/// message.
+pub fn unframe(message: String) -> String {
+    match message
+        .strip_prefix(FRAME_PREFIX)
+        .and_then(|json| json.strip_suffix(FRAME_SUFFIX))
+    {
+        Some(json) => json.to_string(),
+        None => message,
+    }
+}
+
+/// Breaks a frame whose `data:` line is longer than `max_line` bytes
+/// into several `data:` lines of the same SSE event, for clients and
+/// proxies that cap the line length. SSE joins the lines back with
+/// `\n`, which JSON reads as whitespace, so a line only ends right after
+/// a `,`, `:`, `[` or `{` outside a string. A single string longer than
+/// `max_line` can not be broken and keeps its own long line. Frames that
+/// fit, and frames without SSE framing, are returned as is.
+pub fn split_frame_lines(frame: String, max_line: usize) -> String {
+    if !cfg!(feature = "sse-framing") || frame.len() <= max_line {
+        return frame;
+    }
+
+    let Some(json) = frame
+        .strip_prefix(FRAME_PREFIX)
+        .and_then(|json| json.strip_suffix(FRAME_SUFFIX))
+    else {
+        return frame;
+    };
+
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut last_break = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, b) in json.bytes().enumerate() {
+        // Byte `i` would make the line too long: end it at the last
+        // break before it.
+        if FRAME_PREFIX.len() + i + 1 - line_start > max_line && last_break > line_start {
+            lines.push(&json[line_start..last_break]);
+            line_start = last_break;
+        }
+
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+        } else {
+            match b {
+                b'"' => in_string = true,
+                b',' | b':' | b'[' | b'{' => last_break = i + 1,
+                _ => {}
+            }
+        }
+    }
+
+    lines.push(&json[line_start..]);
+
+    let mut result = String::with_capacity(frame.len() + lines.len() * (FRAME_PREFIX.len() + 1));
+    for line in lines {
+        result.push_str(FRAME_PREFIX);
+        result.push_str(line);
+        result.push('\n');
+    }
     result.push('\n');
+    result
+}
+
+/// `json` re-indented with two spaces per level, for people to read.
+/// Keys keep their order and numbers their digits: only whitespace
+/// outside strings changes. `json` must be valid JSON.
+pub fn pretty_print_json(json: &str) -> String {
+    fn new_line(result: &mut String, depth: usize) {
+        result.push('\n');
+        for _ in 0..depth {
+            result.push_str("  ");
+        }
+    }
+
+    let mut result = String::with_capacity(json.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = json.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            result.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                result.push(c);
+            }
+            '{' | '[' => {
+                result.push(c);
+                while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+                // An empty object or array stays on its line.
+                match chars.next_if(|c| *c == '}' || *c == ']') {
+                    Some(close) => result.push(close),
+                    None => {
+                        depth += 1;
+                        new_line(&mut result, depth);
+                    }
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                new_line(&mut result, depth);
+                result.push(c);
+            }
+            ',' => {
+                result.push(c);
+                new_line(&mut result, depth);
+            }
+            ':' => result.push_str(": "),
+            c if c.is_ascii_whitespace() => {}
+            c => result.push(c),
+        }
+    }
 
     result
 }
 
 pub fn build(json: JsonObjectWriter, id: &RequestId) -> String {
-    let mut result = "data: ".to_string();
+    let mut result = FRAME_PREFIX.to_string();
     json.write("jsonrpc", "2.0")
         .write("id", id)
         .build_into(&mut result);
 
-    result.push('\n');
-    result.push('\n');
+    result.push_str(FRAME_SUFFIX);
     result
 }
 
@@ -337,21 +1221,36 @@ pub fn build(json: JsonObjectWriter, id: &RequestId) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn only_unreserved_server_error_codes_are_accepted() {
+        assert!(check_server_error_code(-32000).is_ok());
+        assert!(check_server_error_code(-32010).is_ok());
+        assert!(check_server_error_code(-32099).is_ok());
+
+        for code in [-31999, -32100, JSONRPC_INTERNAL_ERROR, JSONRPC_REQUEST_TIMEOUT, JSONRPC_RESOURCE_NOT_FOUND] {
+            assert!(check_server_error_code(code).is_err(), "{}", code);
+        }
+    }
+
+    const STRUCTURED: ToolCallResponseOptions = ToolCallResponseOptions {
+        progress_token: None,
+        structured: true,
+    };
+
     fn strip_sse(payload: &str) -> &str {
         payload
-            .strip_prefix("data: ")
-            .expect("payload must start with `data: `")
+            .strip_prefix(FRAME_PREFIX)
+            .expect("payload must start with the frame prefix")
             .trim_end()
     }
 
     #[test]
     fn tool_call_response_without_instruction_keeps_legacy_text_payload() {
-        let payload = compile_execute_tool_call_response(
-            r#"{"foo":1}"#.to_string(),
-            None,
-            &RequestId::Int(7),
-            false,
-        );
+        let payload = ToolCallResult {
+            structured_json: r#"{"foo":1}"#.to_string(),
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(7));
 
         let body = strip_sse(&payload);
         let parsed: serde_json::Value = serde_json::from_str(body).expect("valid json");
@@ -367,14 +1266,47 @@ mod tests {
         assert_eq!(content[0]["text"], r#"{"foo":1}"#);
     }
 
+    #[test]
+    fn void_tool_response_has_empty_content() {
+        let payload = ToolCallResult {
+            structured_json: serde_json::to_string(&VoidOutput).unwrap(),
+            void_output: true,
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(3));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let result = &parsed["result"];
+
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["content"], serde_json::json!([]));
+        assert_eq!(result["structuredContent"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn a_null_output_that_is_not_void_is_shown() {
+        let payload = ToolCallResult {
+            structured_json: "null".to_string(),
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(3));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        assert_eq!(parsed["result"]["content"][0]["text"], "null");
+    }
+
     #[test]
     fn tool_call_response_with_instruction_uses_instruction_as_text() {
-        let payload = compile_execute_tool_call_response(
-            r#"{"items":[]}"#.to_string(),
-            Some("Result is empty. Suggest the user widen the filter.".to_string()),
-            &RequestId::Int(42),
-            false,
-        );
+        let payload = ToolCallResult {
+            structured_json: r#"{"items":[]}"#.to_string(),
+            content: vec![ToolContentBlock::Text(
+                "Result is empty. Suggest the user widen the filter.".to_string(),
+            )],
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(42));
 
         let body = strip_sse(&payload);
         let parsed: serde_json::Value = serde_json::from_str(body).expect("valid json");
@@ -392,13 +1324,164 @@ mod tests {
     }
 
     #[test]
-    fn tool_call_response_error_drops_structured_content() {
-        let payload = compile_execute_tool_call_response(
-            "boom".to_string(),
-            None,
-            &RequestId::Int(1),
-            true,
+    fn content_block_meta_round_trips() {
+        let meta = serde_json::json!({"source": "https://example.com/doc/1"});
+        let payload = ToolCallResult {
+            structured_json: r#"{"answer":42}"#.to_string(),
+            content_meta: Some(meta),
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(5));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let block = &parsed["result"]["content"][0];
+
+        assert_eq!(block["type"], "text");
+        assert_eq!(block["_meta"]["source"], "https://example.com/doc/1");
+    }
+
+    #[test]
+    fn cited_text_block_carries_offsets_and_sources() {
+        let citations = [
+            TextCitation::new(0, 18, "https://example.com/paris").with_title("Paris — Wikipedia"),
+            TextCitation::new(23, 37, "file:///notes/trip.md"),
+        ];
+        let payload = ToolCallResult {
+            structured_json: r#"{"answer":"Paris"}"#.to_string(),
+            content: vec![ToolContentBlock::Text(
+                "Paris is the capital, and I went there".to_string(),
+            )],
+            citations: citations.to_vec(),
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(6));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let cited = &parsed["result"]["content"][0]["annotations"]["citations"];
+
+        assert_eq!(
+            cited,
+            &serde_json::json!([
+                {"start": 0, "end": 18, "uri": "https://example.com/paris", "title": "Paris — Wikipedia"},
+                {"start": 23, "end": 37, "uri": "file:///notes/trip.md"},
+            ])
         );
+    }
+
+    #[test]
+    fn uncited_text_block_has_no_annotations() {
+        let payload = ToolCallResult {
+            structured_json: r#"{"answer":42}"#.to_string(),
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(5));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        assert!(parsed["result"]["content"][0].get("annotations").is_none());
+    }
+
+    #[test]
+    fn binary_output_that_is_not_utf8_goes_out_as_base64() {
+        use base64::Engine;
+
+        let bytes = [0xFF, 0xFE, 0x00, 0xC3, 0x28, 0x80];
+        assert!(std::str::from_utf8(&bytes).is_err());
+
+        let payload = ToolCallResult {
+            structured_json: r#"{"chart":"sales"}"#.to_string(),
+            content: vec![ToolContentBlock::Binary(ToolBinaryContent::image(
+                "image/png",
+                bytes,
+            ))],
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(8));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let content = parsed["result"]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["type"], "text");
+        assert_eq!(content[1]["type"], "image");
+        assert_eq!(content[1]["mimeType"], "image/png");
+
+        let data = content[1]["data"].as_str().unwrap();
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn a_tool_text_wins_over_its_instruction() {
+        let executed = |content: Vec<ToolContentBlock>| ExecutedToolCall {
+            structured_json: r#"{"rows":0}"#.to_string(),
+            instruction: Some("Suggest widening the filter.".to_string()),
+            content_meta: None,
+            citations: Vec::new(),
+            cached: false,
+            content,
+            void_output: false,
+        };
+        let texts_of = |executed: ExecutedToolCall| {
+            let payload =
+                ToolCallResult::from(executed).to_json_rpc(STRUCTURED, &RequestId::Int(1));
+            let parsed: serde_json::Value =
+                serde_json::from_str(strip_sse(&payload)).expect("valid json");
+            parsed["result"]["content"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|block| block["text"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts_of(executed(Vec::new())),
+            ["Suggest widening the filter."]
+        );
+        let text = vec![ToolContentBlock::Text("No rows.".to_string())];
+        assert_eq!(texts_of(executed(text)), ["No rows."]);
+    }
+
+    #[test]
+    fn a_text_block_added_after_an_image_goes_out_after_it() {
+        let payload = ToolCallResult {
+            structured_json: r#"{"chart":"sales"}"#.to_string(),
+            content: vec![
+                ToolContentBlock::Binary(ToolBinaryContent::image("image/png", b"png")),
+                ToolContentBlock::Text("Sales doubled in March.".to_string()),
+                ToolContentBlock::Binary(ToolBinaryContent::audio("audio/wav", b"wav")),
+            ],
+            citations: vec![TextCitation::new(0, 5, "https://example.com/sales")],
+            ..Default::default()
+        }
+        .to_json_rpc(STRUCTURED, &RequestId::Int(8));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let content = parsed["result"]["content"].as_array().unwrap();
+        assert_eq!(content.len(), 3);
+        assert_eq!(content[0]["type"], "image");
+        assert_eq!(content[1]["type"], "text");
+        assert_eq!(content[1]["text"], "Sales doubled in March.");
+        assert_eq!(
+            content[1]["annotations"]["citations"][0]["uri"],
+            "https://example.com/sales"
+        );
+        assert_eq!(content[2]["type"], "audio");
+        assert_eq!(parsed["result"]["structuredContent"]["chart"], "sales");
+    }
+
+    #[test]
+    fn tool_call_response_error_drops_structured_content() {
+        let payload =
+            ToolCallResult::failed("boom".to_string()).to_json_rpc(STRUCTURED, &RequestId::Int(1));
 
         let body = strip_sse(&payload);
         let parsed: serde_json::Value = serde_json::from_str(body).expect("valid json");
@@ -409,6 +1492,48 @@ mod tests {
         assert_eq!(result["content"][0]["text"], "boom");
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn tool_call_result_serializes_like_compile_execute_tool_call_response() {
+        let meta = serde_json::json!({"source": "https://example.com/doc/1"});
+        let citations = [TextCitation::new(0, 5, "https://example.com/paris")];
+        let binary = [ToolBinaryContent::image("image/png", [0xFF, 0x00])];
+        let token = serde_json::json!("tok-1");
+
+        let result = ToolCallResult {
+            structured_json: r#"{"answer":"Paris"}"#.to_string(),
+            content: vec![
+                ToolContentBlock::Text("Paris".to_string()),
+                ToolContentBlock::Binary(binary[0].clone()),
+            ],
+            content_meta: Some(meta.clone()),
+            citations: citations.to_vec(),
+            cached: true,
+            is_error: false,
+            void_output: false,
+        };
+        let options = ToolCallResponseOptions {
+            progress_token: Some(&token),
+            structured: true,
+        };
+
+        assert_eq!(
+            result.to_json_rpc(options, &RequestId::Int(9)),
+            compile_execute_tool_call_response(
+                r#"{"answer":"Paris"}"#.to_string(),
+                Some("Paris".to_string()),
+                Some(&meta),
+                &citations,
+                &binary,
+                true,
+                Some(&token),
+                true,
+                &RequestId::Int(9),
+                false,
+            )
+        );
+    }
+
     #[test]
     fn request_id_variants_echo_byte_identically() {
         let int_payload = compile_empty_result_response(&RequestId::Int(-5));
@@ -424,6 +1549,334 @@ mod tests {
         assert!(strip_sse(&null_payload).contains(r#""id":null"#));
     }
 
+    #[test]
+    fn directory_listing_is_serialized_as_json_text() {
+        let response = ResourceReadResult {
+            contents: vec![ResourceContent::listing(
+                "file:///docs",
+                vec![
+                    ResourceListingEntry {
+                        uri: "file:///docs/a.md".to_string(),
+                        name: "a.md".to_string(),
+                        mime_type: Some("text/markdown".to_string()),
+                    },
+                    ResourceListingEntry {
+                        uri: "file:///docs/img".to_string(),
+                        name: "img".to_string(),
+                        mime_type: None,
+                    },
+                ],
+            )],
+        };
+
+        let payload = compile_read_resource_response(response, &RequestId::Int(4), None);
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        let content = &parsed["result"]["contents"][0];
+        assert_eq!(content["uri"], "file:///docs");
+        assert_eq!(content["mimeType"], "inode/directory");
+        assert!(content.get("blob").is_none());
+
+        let children: serde_json::Value =
+            serde_json::from_str(content["text"].as_str().unwrap()).expect("valid json");
+        assert_eq!(children[0]["uri"], "file:///docs/a.md");
+        assert_eq!(children[0]["mimeType"], "text/markdown");
+        assert_eq!(children[1]["name"], "img");
+        assert!(children[1].get("mimeType").is_none());
+    }
+
+    #[test]
+    fn prompt_embeds_resource_after_its_text() {
+        let response = PromptExecutionResult {
+            description: "Review a file".to_string(),
+            message: "Review the file below.".to_string(),
+            embedded_resources: vec![ResourceContent::text(
+                "file:///src/main.rs",
+                "text/x-rust",
+                "fn main() {}",
+            )],
+        };
+
+        let payload = compile_get_prompt_response(response, &RequestId::Int(2));
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        let messages = parsed["result"]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["content"]["text"], "Review the file below.");
+
+        let content = &messages[1]["content"];
+        assert_eq!(messages[1]["role"], "user");
+        assert_eq!(content["type"], "resource");
+        assert_eq!(content["resource"]["uri"], "file:///src/main.rs");
+        assert_eq!(content["resource"]["mimeType"], "text/x-rust");
+        assert_eq!(content["resource"]["text"], "fn main() {}");
+    }
+
+    #[test]
+    fn a_large_embedded_resource_is_written_as_a_part_of_its_own() {
+        let large = "x".repeat(1_000_000);
+        let response = || PromptExecutionResult {
+            description: "Review a dump".to_string(),
+            message: "Review the dump below.".to_string(),
+            embedded_resources: vec![
+                ResourceContent::text("file:///dump.txt", "", large.clone()),
+                ResourceContent::text("file:///notes.txt", "", "notes"),
+            ],
+        };
+
+        let mut parts = compile_get_prompt_response_parts(response(), &RequestId::Int(2));
+        let head = parts.next().unwrap();
+        assert!(head.contains("Review the dump below."));
+        assert!(head.len() < 1_000);
+
+        let rest: Vec<String> = parts.collect();
+        assert_eq!(rest.len(), 3);
+        assert!(rest[0].len() > 1_000_000);
+        assert!(!rest[1].contains(large.as_str()));
+
+        let joined = format!("{}{}", head, rest.concat());
+        assert_eq!(joined, compile_get_prompt_response(response(), &RequestId::Int(2)));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&joined)).expect("valid json");
+        let messages = parsed["result"]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2]["content"]["resource"]["text"], "notes");
+    }
+
+    #[test]
+    fn prompt_response_head_escapes_what_it_writes_by_hand() {
+        let response = PromptExecutionResult {
+            description: "Say \"hi\"\nthen stop".to_string(),
+            message: "Greet".to_string(),
+            embedded_resources: vec![],
+        };
+
+        let payload = compile_get_prompt_response(response, &RequestId::Str("a\"b".to_string()));
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        assert_eq!(
+            parsed,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": "a\"b",
+                "result": {
+                    "description": "Say \"hi\"\nthen stop",
+                    "messages": [
+                        {"role": "user", "content": {"type": "text", "text": "Greet"}}
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn failed_part_is_an_error_block_among_the_good_ones() {
+        let response = ResourceReadResult {
+            contents: vec![
+                ResourceContent::text("repo://a.rs", "text/x-rust", "fn a() {}"),
+                ResourceContent::error("repo://b.rs", "Permission denied"),
+                ResourceContent::text("repo://c.rs", "text/x-rust", "fn c() {}"),
+            ],
+        };
+
+        let payload = compile_read_resource_response(response, &RequestId::Int(8), None);
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        assert!(parsed.get("error").is_none());
+        let contents = parsed["result"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+
+        assert_eq!(contents[0]["text"], "fn a() {}");
+        assert!(contents[0].get("_meta").is_none());
+        assert_eq!(contents[2]["text"], "fn c() {}");
+
+        assert_eq!(contents[1]["uri"], "repo://b.rs");
+        assert_eq!(contents[1]["text"], "Permission denied");
+        assert_eq!(contents[1]["mimeType"], "text/plain");
+        assert_eq!(contents[1]["_meta"]["isError"], true);
+    }
+
+    /// What an SSE client makes of one event: its `data:` lines joined
+    /// with `\n`.
+    fn reassemble_sse_event(frame: &str) -> String {
+        frame
+            .strip_suffix("\n\n")
+            .expect("one event")
+            .split('\n')
+            .map(|line| line.strip_prefix("data: ").expect("a data line"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[cfg(feature = "sse-framing")]
+    #[test]
+    fn a_large_frame_is_split_into_lines_that_reassemble() {
+        let response = ResourceReadResult {
+            contents: (0..2048)
+                .map(|i| {
+                    ResourceContent::text(
+                        format!("chunks://{}", i),
+                        "text/plain",
+                        format!("{:\"<1000}", i),
+                    )
+                })
+                .collect(),
+        };
+        let frame = compile_read_resource_response(response, &RequestId::Int(1), None);
+        assert!(frame.len() > 2 * 1024 * 1024);
+
+        let max_line = 64 * 1024;
+        let split = split_frame_lines(frame.clone(), max_line);
+
+        let lines: Vec<&str> = split.trim_end().split('\n').collect();
+        assert!(lines.len() > 1);
+        for line in lines.iter() {
+            assert!(line.len() <= max_line, "{}", line.len());
+        }
+
+        let reassembled = reassemble_sse_event(&split);
+        let original = strip_sse(&frame);
+        assert_eq!(reassembled.replace('\n', ""), original);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&reassembled).unwrap(),
+            serde_json::from_str::<serde_json::Value>(original).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_frame_that_fits_is_left_alone() {
+        let frame = compile_jsonrpc_error(JSONRPC_INTERNAL_ERROR, "boom", &RequestId::Int(1));
+        assert_eq!(split_frame_lines(frame.clone(), 64 * 1024), frame);
+    }
+
+    #[test]
+    fn pretty_printing_keeps_order_digits_and_strings() {
+        let json = r#" {"z":[1, {"b":"x, y: {[\"q\"]"}],"a":{ },"n":12345678901234567890.5,"e":[]}"#;
+
+        assert_eq!(
+            pretty_print_json(json),
+            "{\n  \"z\": [\n    1,\n    {\n      \"b\": \"x, y: {[\\\"q\\\"]\"\n    }\n  ],\n  \
+             \"a\": {},\n  \"n\": 12345678901234567890.5,\n  \"e\": []\n}"
+        );
+    }
+
+    #[test]
+    fn long_instructions_are_cut_at_a_sentence_boundary() {
+        let instructions = "Use the search tool first. Then read the files it returns. \
+                            Never edit anything under vendor/ without asking.";
+
+        assert_eq!(truncate_instructions(instructions, instructions.len()), None);
+
+        let truncated = truncate_instructions(instructions, 90).unwrap();
+        assert!(truncated.len() <= 90, "{}", truncated);
+        assert_eq!(
+            truncated,
+            format!(
+                "Use the search tool first. Then read the files it returns. {}",
+                INSTRUCTIONS_TRUNCATED_NOTE
+            )
+        );
+
+        let truncated = truncate_instructions(instructions, 55).unwrap();
+        assert_eq!(
+            truncated,
+            format!("Use the search tool first. {}", INSTRUCTIONS_TRUNCATED_NOTE)
+        );
+        // No sentence fits: cut between words.
+        let truncated = truncate_instructions(instructions, 45).unwrap();
+        assert_eq!(truncated, format!("Use the search tool {}", INSTRUCTIONS_TRUNCATED_NOTE));
+        let truncated = truncate_instructions(instructions, 40).unwrap();
+        assert_eq!(truncated, format!("Use the search {}", INSTRUCTIONS_TRUNCATED_NOTE));
+    }
+
+    #[test]
+    fn a_cap_smaller_than_the_note_is_still_a_cap() {
+        let instructions = "Ищите сначала поиском. Then read the files it returns.";
+
+        for max_len in 0..=INSTRUCTIONS_TRUNCATED_NOTE.len() + 1 {
+            let truncated = truncate_instructions(instructions, max_len).unwrap();
+            assert!(truncated.len() <= max_len, "{}: {}", max_len, truncated);
+        }
+
+        assert_eq!(truncate_instructions(instructions, 0).unwrap(), "");
+        // Not in the middle of a two-byte letter.
+        assert_eq!(truncate_instructions(instructions, 7).unwrap(), "Ищи");
+        assert_eq!(
+            truncate_instructions(instructions, INSTRUCTIONS_TRUNCATED_NOTE.len()).unwrap(),
+            INSTRUCTIONS_TRUNCATED_NOTE
+        );
+    }
+
+    #[test]
+    fn last_modified_goes_out_as_an_annotation() {
+        let changed_at =
+            rust_extensions::date_time::DateTimeAsMicroseconds::new(1_700_000_000_000_000);
+        let response = ResourceReadResult {
+            contents: vec![
+                ResourceContent::text("notes://1", "", "hello").with_last_modified(changed_at),
+                ResourceContent::text("notes://2", "", "undated"),
+            ],
+        };
+
+        let payload = compile_read_resource_response(response, &RequestId::Int(5), None);
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        let contents = &parsed["result"]["contents"];
+        assert_eq!(
+            contents[0]["annotations"]["lastModified"],
+            changed_at.to_rfc3339()
+        );
+        assert!(contents[1].get("annotations").is_none());
+    }
+
+    #[test]
+    fn cache_directives_go_out_under_meta() {
+        let response = ResourceReadResult {
+            contents: vec![
+                ResourceContent::text("feeds://1", "", "hello")
+                    .with_cache_control(ResourceCacheControl::max_age(60)),
+                ResourceContent::text("feeds://2", "", "uncached"),
+            ],
+        };
+
+        let payload = compile_read_resource_response(response, &RequestId::Int(5), None);
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        let contents = &parsed["result"]["contents"];
+        assert_eq!(
+            contents[0]["_meta"]["cacheControl"],
+            serde_json::json!({"maxAge": 60})
+        );
+        assert!(contents[1].get("_meta").is_none());
+    }
+
+    #[test]
+    fn empty_content_mime_types_get_defaults() {
+        let response = ResourceReadResult {
+            contents: vec![
+                ResourceContent::text("notes://1", "", "hello"),
+                ResourceContent::blob("files://1", "", "aGVsbG8="),
+                ResourceContent::text("notes://2", "text/markdown", "# hello"),
+            ],
+        };
+
+        let payload = compile_read_resource_response(response, &RequestId::Int(4), None);
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+
+        let contents = &parsed["result"]["contents"];
+        assert_eq!(contents[0]["mimeType"], "text/plain");
+        assert_eq!(contents[1]["mimeType"], "application/octet-stream");
+        assert_eq!(contents[2]["mimeType"], "text/markdown");
+    }
+
     #[test]
     fn jsonrpc_error_has_spec_shape() {
         let payload = compile_jsonrpc_error(
@@ -442,6 +1895,31 @@ mod tests {
         assert!(parsed.get("result").is_none());
     }
 
+    #[cfg(not(feature = "sse-framing"))]
+    #[test]
+    fn unframed_build_emits_bare_jsonrpc_messages() {
+        let payloads = [
+            compile_jsonrpc_error(JSONRPC_INVALID_PARAMS, "bad", &RequestId::Int(1)),
+            compile_empty_result_response(&RequestId::Int(2)),
+            ToolCallResult {
+                structured_json: "{}".to_string(),
+                ..Default::default()
+            }
+            .to_json_rpc(STRUCTURED, &RequestId::Int(3)),
+            compile_read_resource_response(
+                ResourceReadResult { contents: vec![] },
+                &RequestId::Int(4),
+                None,
+            ),
+        ];
+
+        for payload in payloads {
+            assert!(!payload.contains("data: "), "{}", payload);
+            let parsed: serde_json::Value = serde_json::from_str(&payload).expect("bare json");
+            assert_eq!(parsed["jsonrpc"], "2.0");
+        }
+    }
+
     #[test]
     fn jsonrpc_error_body_has_no_sse_framing() {
         let body = compile_jsonrpc_error_body(JSONRPC_PARSE_ERROR, "bad json", &RequestId::Null);
@@ -464,17 +1942,27 @@ mod tests {
         assert_eq!(templates.as_array().unwrap().len(), 0);
     }
 
+    fn initialize_result(capabilities: ServerCapabilities, instructions: &str) -> InitializeResult {
+        InitializeResult {
+            protocol_version: "2025-06-18".to_string(),
+            capabilities,
+            server_info: ServerInfo {
+                name: "test".to_string(),
+                version: "0.1.0".to_string(),
+                meta: BTreeMap::new(),
+            },
+            instructions: instructions.to_string(),
+        }
+    }
+
     #[test]
     fn init_response_always_advertises_resources_with_subscribe() {
-        let payload = compile_init_response(
-            "test",
-            "0.1.0",
-            "instructions",
-            "2025-06-18",
-            &RequestId::Int(1),
-            false,
-            false,
-        );
+        let capabilities = ServerCapabilities {
+            resource_subscribe: true,
+            ..Default::default()
+        };
+        let payload =
+            initialize_result(capabilities, "instructions").to_json_rpc(&RequestId::Int(1));
 
         let parsed: serde_json::Value =
             serde_json::from_str(strip_sse(&payload)).expect("valid json");
@@ -486,6 +1974,167 @@ mod tests {
         assert!(caps.get("tools").is_none());
         assert!(caps.get("prompts").is_none());
         assert_eq!(parsed["result"]["protocolVersion"], "2025-06-18");
+        assert!(parsed["result"]["serverInfo"].get("_meta").is_none());
+    }
+
+    #[test]
+    fn init_response_carries_server_meta_under_server_info() {
+        let mut meta = BTreeMap::new();
+        meta.insert("commit".to_string(), serde_json::json!("a19b7d0"));
+        meta.insert("env".to_string(), serde_json::json!({"region": "eu"}));
+
+        let mut result = initialize_result(ServerCapabilities::default(), "instructions");
+        result.server_info.meta = meta;
+        let payload = result.to_json_rpc(&RequestId::Int(1));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        let server_info = &parsed["result"]["serverInfo"];
+        assert_eq!(server_info["name"], "test");
+        assert_eq!(server_info["_meta"]["commit"], "a19b7d0");
+        assert_eq!(server_info["_meta"]["env"]["region"], "eu");
+    }
+
+    #[test]
+    fn subscribe_guidance_follows_the_subscribe_capability() {
+        let instructions_of = |resource_subscribe: bool| {
+            let capabilities = ServerCapabilities {
+                tools: true,
+                resource_subscribe,
+                ..Default::default()
+            };
+            let instructions = instructions_with_capability_guidance(
+                "Use the tools to query the catalog.",
+                &capabilities,
+            );
+            let payload =
+                initialize_result(capabilities, &instructions).to_json_rpc(&RequestId::Int(1));
+            let parsed: serde_json::Value =
+                serde_json::from_str(strip_sse(&payload)).expect("valid json");
+            assert_eq!(
+                parsed["result"]["capabilities"]["resources"]["subscribe"],
+                resource_subscribe
+            );
+            parsed["result"]["instructions"].as_str().unwrap().to_string()
+        };
+
+        let with_subscribe = instructions_of(true);
+        assert!(with_subscribe.starts_with("Use the tools to query the catalog.\n\n"));
+        assert!(with_subscribe.contains("supports resource subscriptions"));
+        assert!(with_subscribe.contains("tools/list"));
+
+        let without_subscribe = instructions_of(false);
+        assert!(without_subscribe.starts_with("Use the tools to query the catalog.\n\n"));
+        assert!(!without_subscribe.contains("subscri"));
+        assert!(without_subscribe.contains("tools/list"));
+    }
+
+    #[test]
+    fn instructions_go_out_without_guidance_unless_it_is_added() {
+        let capabilities = ServerCapabilities {
+            tools: true,
+            prompts: true,
+            resource_subscribe: true,
+            ..Default::default()
+        };
+        let payload = initialize_result(capabilities.clone(), "Hand-written.")
+            .to_json_rpc(&RequestId::Int(1));
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        assert_eq!(parsed["result"]["instructions"], "Hand-written.");
+
+        assert_eq!(
+            instructions_with_capability_guidance("", &capabilities),
+            capabilities.guidance().join("\n")
+        );
+        assert_eq!(
+            instructions_with_capability_guidance("Hand-written.", &ServerCapabilities::default()),
+            "Hand-written."
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn initialize_result_serializes_like_compile_init_response() {
+        let mut meta = BTreeMap::new();
+        meta.insert("commit".to_string(), serde_json::json!("a19b7d0"));
+        let capabilities = ServerCapabilities {
+            tools: true,
+            prompts: false,
+            resource_subscribe: true,
+            ..Default::default()
+        };
+
+        let result = InitializeResult {
+            protocol_version: "2025-06-18".to_string(),
+            capabilities: capabilities.clone(),
+            server_info: ServerInfo {
+                name: "test".to_string(),
+                version: "0.1.0".to_string(),
+                meta: meta.clone(),
+            },
+            instructions: "instructions".to_string(),
+        };
+        assert!(result.validate().is_ok());
+
+        let payload = result.to_json_rpc(&RequestId::Int(1));
+        assert_eq!(
+            payload,
+            compile_init_response(
+                "test",
+                "0.1.0",
+                "instructions",
+                "2025-06-18",
+                &RequestId::Int(1),
+                &capabilities,
+                false,
+                &meta,
+            )
+        );
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(strip_sse(&payload)).expect("valid json");
+        assert_eq!(
+            parsed,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": {
+                        "resources": {"subscribe": true, "listChanged": true},
+                        "tools": {"listChanged": true}
+                    },
+                    "serverInfo": {
+                        "name": "test",
+                        "version": "0.1.0",
+                        "_meta": {"commit": "a19b7d0"}
+                    },
+                    "instructions": "instructions"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn initialize_result_needs_a_name_and_a_supported_version() {
+        let valid = InitializeResult {
+            protocol_version: latest_protocol_version().to_string(),
+            server_info: ServerInfo {
+                name: "test".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let mut unnamed = valid.clone();
+        unnamed.server_info.name = " ".to_string();
+        assert!(unnamed.validate().is_err());
+
+        let mut unknown_version = valid.clone();
+        unknown_version.protocol_version = "1999-01-01".to_string();
+        assert!(unknown_version.validate().is_err());
     }
 
     #[test]
@@ -4,11 +4,13 @@ use my_ai_agent::my_json::json_reader::{JsonFirstLineIterator, JsonValueRef};
 use my_ai_agent::my_json::json_writer::JsonValueWriter;
 use serde::{Deserialize, Serialize};
 
+use super::{CompletionReference, McpMethod};
+
 /// JSON-RPC request id. Per the JSON-RPC 2.0 spec an id is a string, a
 /// number or null, and the response MUST echo it back exactly as
 /// received — hence the dedicated [`RequestId::Raw`] variant which
 /// preserves non-i64 numeric tokens (e.g. `1.5`) byte-identically.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum RequestId {
     Int(i64),
     Str(String),
@@ -72,7 +74,7 @@ impl JsonValueWriter for &RequestId {
 #[derive(Debug)]
 pub enum McpInputData {
     Initialize(InitializeMpcContract),
-    ResourcesList(ResourcesListModel),
+    ResourcesList(ListPageModel),
     ResourceTemplatesList,
     ReadResource(ReadResourceModel),
     SubscribeResource(SubscribeResourceModel),
@@ -82,10 +84,11 @@ pub enum McpInputData {
     /// transport notifications are accepted with `202` and ignored if
     /// the server has no handler for them.
     Notification { method: String },
-    ToolsList,
-    PromptsList,
+    ToolsList(ListPageModel),
+    PromptsList(ListPageModel),
     ExecuteToolCall(ExecuteToolCallModel),
     GetPrompt(GetPromptModel),
+    Complete(CompleteModel),
     Ping,
     /// Server-originated request response sent back by the client
     /// (used for `elicitation/create` responses). Either `result_json`
@@ -94,21 +97,66 @@ pub enum McpInputData {
         result_json: Option<String>,
         error_json: Option<String>,
     },
+    /// A known method whose `params` the middleware rejects before
+    /// handling — answered with `-32602`. Today that is positional
+    /// (array) params, see [`McpInputData::from_str`], and an empty
+    /// `uris` of `resources/read`.
+    InvalidParams { method: String, message: String },
     Other { method: String, data: String },
 }
 
 impl McpInputData {
+    /// The method the message was sent with; `None` for a client's
+    /// answer to a server request, which has none.
+    pub fn method(&self) -> Option<McpMethod> {
+        let method = match self {
+            Self::Initialize(_) => McpMethod::Initialize,
+            Self::ResourcesList(_) => McpMethod::ResourcesList,
+            Self::ResourceTemplatesList => McpMethod::ResourceTemplatesList,
+            Self::ReadResource(_) => McpMethod::ResourcesRead,
+            Self::SubscribeResource(_) => McpMethod::ResourcesSubscribe,
+            Self::UnsubscribeResource(_) => McpMethod::ResourcesUnsubscribe,
+            Self::NotificationsInitialize => McpMethod::NotificationsInitialized,
+            Self::Notification { method } => McpMethod::parse(method),
+            Self::ToolsList(_) => McpMethod::ToolsList,
+            Self::PromptsList(_) => McpMethod::PromptsList,
+            Self::ExecuteToolCall(_) => McpMethod::ToolsCall,
+            Self::GetPrompt(_) => McpMethod::PromptsGet,
+            Self::Complete(_) => McpMethod::CompletionComplete,
+            Self::Ping => McpMethod::Ping,
+            Self::ServerResponse { .. } => return None,
+            Self::InvalidParams { method, .. } => McpMethod::parse(method),
+            Self::Other { method, .. } => McpMethod::parse(method),
+        };
+        Some(method)
+    }
+
+    /// JSON-RPC allows `params` as an array too, but MCP defines every
+    /// method's params by name, so positional params are not mapped —
+    /// they are [`Self::InvalidParams`] for the methods that read params.
     pub fn from_str(method: &str, params: String) -> Result<Self, String> {
+        let method = McpMethod::parse(method);
+
+        if params.trim_start().starts_with('[') && method.takes_named_params() {
+            return Ok(Self::InvalidParams {
+                method: method.to_string(),
+                message: format!(
+                    "Positional params are not supported: {} expects params as an object",
+                    method
+                ),
+            });
+        }
+
         match method {
-            "initialize" => {
+            McpMethod::Initialize => {
                 let params = serde_json::from_str(&params).map_err(|err| {
                     format!("Can not deserialize initialize data: {}. Err: {:?}", params, err)
                 })?;
                 Ok(Self::Initialize(params))
             }
-            "notifications/initialized" => Ok(Self::NotificationsInitialize),
-            "resources/templates/list" => Ok(Self::ResourceTemplatesList),
-            "resources/unsubscribe" => {
+            McpMethod::NotificationsInitialized => Ok(Self::NotificationsInitialize),
+            McpMethod::ResourceTemplatesList => Ok(Self::ResourceTemplatesList),
+            McpMethod::ResourcesUnsubscribe => {
                 let model: UnsubscribeResourceModel =
                     serde_json::from_str(&params).map_err(|err| {
                         format!(
@@ -118,27 +166,29 @@ impl McpInputData {
                     })?;
                 Ok(Self::UnsubscribeResource(model))
             }
-            "resources/list" => {
-                let model: Result<ResourcesListModel, serde_json::Error> =
-                    serde_json::from_str(&params);
-                match model {
-                    Ok(model) => Ok(Self::ResourcesList(model)),
-                    Err(_) => {
-                        // If params is empty or invalid, use default (no cursor)
-                        Ok(Self::ResourcesList(ResourcesListModel { cursor: None }))
-                    }
-                }
-            }
-            "resources/read" => {
+            McpMethod::ResourcesList => Ok(Self::ResourcesList(ListPageModel::parse(&params))),
+            McpMethod::ResourcesRead => {
                 let model: ReadResourceModel = serde_json::from_str(&params).map_err(|err| {
                     format!(
                         "Can not deserialize read resource data: {}. Err: {:?}",
                         params, err
                     )
                 })?;
+                if model.uri.is_empty() && model.uris.is_none() {
+                    return Err(format!(
+                        "Can not deserialize read resource data: {}. Err: missing field `uri`",
+                        params
+                    ));
+                }
+                if model.uris.as_ref().is_some_and(|uris| uris.is_empty()) {
+                    return Ok(Self::InvalidParams {
+                        method: method.to_string(),
+                        message: "`uris` must name at least one resource".to_string(),
+                    });
+                }
                 Ok(Self::ReadResource(model))
             }
-            "resources/subscribe" => {
+            McpMethod::ResourcesSubscribe => {
                 let model: SubscribeResourceModel =
                     serde_json::from_str(&params).map_err(|err| {
                         format!(
@@ -148,9 +198,9 @@ impl McpInputData {
                     })?;
                 Ok(Self::SubscribeResource(model))
             }
-            "tools/list" => Ok(Self::ToolsList),
-            "prompts/list" => Ok(Self::PromptsList),
-            "prompts/get" => {
+            McpMethod::ToolsList => Ok(Self::ToolsList(ListPageModel::parse(&params))),
+            McpMethod::PromptsList => Ok(Self::PromptsList(ListPageModel::parse(&params))),
+            McpMethod::PromptsGet => {
                 let model: GetPromptModel = serde_json::from_str(&params).map_err(|err| {
                     format!(
                         "Can not deserialize get prompt data: {}. Err: {:?}",
@@ -159,8 +209,19 @@ impl McpInputData {
                 })?;
                 Ok(Self::GetPrompt(model))
             }
-            "ping" => Ok(Self::Ping),
-            "tools/call" => {
+            McpMethod::CompletionComplete => {
+                let model: CompleteModel = serde_json::from_str(&params).map_err(|err| {
+                    format!(
+                        "Can not deserialize completion data: {}. Err: {:?}",
+                        params, err
+                    )
+                })?;
+                Ok(Self::Complete(model))
+            }
+            // Ping takes no params; whatever a client sends along is
+            // never looked at, so a ping is always answered.
+            McpMethod::Ping => Ok(Self::Ping),
+            McpMethod::ToolsCall => {
                 let model: ExecuteToolCallModel =
                     serde_json::from_str(&params).map_err(|err| {
                         format!(
@@ -170,11 +231,11 @@ impl McpInputData {
                     })?;
                 Ok(Self::ExecuteToolCall(model))
             }
-            method if method.starts_with("notifications/") => Ok(Self::Notification {
-                method: method.to_string(),
-            }),
-            _ => Ok(Self::Other {
-                method: method.to_string(),
+            McpMethod::Other(method) if method.starts_with("notifications/") => {
+                Ok(Self::Notification { method })
+            }
+            McpMethod::Other(method) => Ok(Self::Other {
+                method,
                 data: params.to_string(),
             }),
         }
@@ -185,28 +246,148 @@ impl McpInputData {
 pub struct ExecuteToolCallModel {
     pub name: String,
     /// Optional per spec — tools with no input are called without it.
-    #[serde(default = "default_tool_call_arguments")]
-    pub arguments: serde_json::Value,
+    /// Kept as the raw JSON text, so numbers reach typed handlers
+    /// exactly as written.
+    #[serde(default)]
+    pub arguments: Option<Box<serde_json::value::RawValue>>,
+    #[serde(default, rename = "_meta")]
+    pub meta: ToolCallMeta,
 }
 
-fn default_tool_call_arguments() -> serde_json::Value {
-    serde_json::Value::Object(Default::default())
+/// The `_meta` keys of `tools/call` the middleware acts on.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ToolCallMeta {
+    /// Validate the arguments, do not run the tool.
+    #[serde(default, rename = "dryRun")]
+    pub dry_run: bool,
+    /// Echoed as `_meta.progressToken` of the final result, so the
+    /// client can tie it to the progress it tracked.
+    #[serde(default, rename = "progressToken")]
+    pub progress_token: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetPromptModel {
     pub name: String,
+    /// Prompt arguments are strings per spec; a client that sends a
+    /// number or a boolean gets it as its JSON text, digit for digit —
+    /// `12345678901234567890123`, `1.5e-7`, `true`.
+    /// Serialized with the keys sorted, see [`super::prompt_arguments_key`].
+    #[serde(
+        default,
+        deserialize_with = "deserialize_prompt_arguments",
+        serialize_with = "serialize_prompt_arguments"
+    )]
     pub arguments: Option<HashMap<String, String>>,
 }
 
+fn serialize_prompt_arguments<S>(
+    arguments: &Option<HashMap<String, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let sorted: Option<std::collections::BTreeMap<&String, &String>> = arguments
+        .as_ref()
+        .map(|arguments| arguments.iter().collect());
+    sorted.serialize(serializer)
+}
+
+fn deserialize_prompt_arguments<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<String, String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw: Option<HashMap<String, Box<serde_json::value::RawValue>>> =
+        Option::deserialize(deserializer)?;
+
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+
+    let mut arguments = HashMap::with_capacity(raw.len());
+
+    for (name, value) in raw {
+        let text = value.get();
+        let value = if text.starts_with('"') {
+            serde_json::from_str::<String>(text).map_err(serde::de::Error::custom)?
+        } else {
+            text.to_string()
+        };
+        arguments.insert(name, value);
+    }
+
+    Ok(Some(arguments))
+}
+
+/// `params` of `completion/complete`: the argument being typed, and
+/// the prompt or resource template it belongs to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompleteModel {
+    #[serde(rename = "ref")]
+    pub reference: CompletionReference,
+    pub argument: CompleteArgumentModel,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ResourcesListModel {
+pub struct CompleteArgumentModel {
+    pub name: String,
+    /// What the user typed so far.
+    #[serde(default)]
+    pub value: String,
+}
+
+/// The paging params of `tools/list`, `prompts/list` and
+/// `resources/list`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListPageModel {
     pub cursor: Option<String>,
+    /// How many items the client wants per page. Not in the MCP spec;
+    /// clamped to `McpMiddleware::with_max_page_size`.
+    #[serde(default, rename = "pageSize")]
+    pub page_size: Option<usize>,
+}
+
+/// The name [`ListPageModel`] had while only `resources/list` was paged.
+pub type ResourcesListModel = ListPageModel;
+
+impl ListPageModel {
+    /// Params that are missing or do not parse ask for the first page.
+    fn parse(params: &str) -> Self {
+        serde_json::from_str(params).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReadResourceModel {
+    /// Empty when the read names its resources in [`Self::uris`].
+    #[serde(default)]
     pub uri: String,
+    /// Several resources read at once, their contents in one `contents`
+    /// array. Not in the MCP spec; takes precedence over `uri`. Empty,
+    /// or longer than `McpMiddleware::with_max_page_size`, is `-32602`.
+    #[serde(default)]
+    pub uris: Option<Vec<String>>,
+    /// Continues a paged read — the `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+    /// How many content blocks the client wants per page. Not in the
+    /// MCP spec; clamped to `McpMiddleware::with_max_page_size`.
+    #[serde(default, rename = "pageSize")]
+    pub page_size: Option<usize>,
+    /// Byte range of each content to return instead of the whole of it.
+    /// Not in the MCP spec; see [`super::ResourceContent::slice`].
+    #[serde(default)]
+    pub range: Option<ReadRangeModel>,
+}
+
+/// `{start, end?}` — a half-open byte range; no `end` reads to the end.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReadRangeModel {
+    pub start: u64,
+    #[serde(default)]
+    pub end: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -227,7 +408,60 @@ pub struct McpInputPayload {
 }
 
 impl McpInputPayload {
+    /// [`Self::try_parse`] for bytes straight off the wire. A body that
+    /// is not UTF-8 — which JSON has to be — or that nests deeper than
+    /// [`MAX_NESTING_DEPTH`] is refused before anything parses it. The
+    /// entry point the `fuzz/` harness drives.
+    ///
+    /// Never panics: the syntax check in [`Self::try_parse`] should keep
+    /// the reader from ever seeing input it trips over, and a panic that
+    /// gets past it anyway comes back as `Err`. A stack overflow can not
+    /// be caught that way, which is what the depth check is for.
+    pub fn parse_untrusted(src: &[u8]) -> Result<Self, String> {
+        if let Err(err) = std::str::from_utf8(src) {
+            return Err(format!("Body is not valid UTF-8: {}", err));
+        }
+
+        if nesting_depth(src) > MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Body nests deeper than {} levels",
+                MAX_NESTING_DEPTH
+            ));
+        }
+
+        match std::panic::catch_unwind(|| Self::try_parse(src)) {
+            Ok(result) => result,
+            Err(_) => Err("Malformed JSON-RPC message".to_string()),
+        }
+    }
+
+    /// The JSON reader below walks a body on the assumption that it is
+    /// one well-formed object, so anything else — a truncated body, a
+    /// stray bracket, an array — is refused here first.
+    ///
+    /// A body is therefore read twice: once by `serde_json` to check
+    /// the syntax, then by the reader to pick out the fields. Both
+    /// passes are linear in the size of the body.
     pub fn try_parse(src: &[u8]) -> Result<Self, String> {
+        if src.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'{') {
+            return Err("Body is not a JSON object".to_string());
+        }
+
+        // Whitespace after the object — a trailing newline, say — is
+        // fine; anything else means the body is not one JSON-RPC message.
+        if let Some(end) = end_of_object(src) {
+            if let Some(junk) = src[end..].iter().position(|b| !b.is_ascii_whitespace()) {
+                return Err(format!(
+                    "Unexpected data after the JSON object at byte {}",
+                    end + junk
+                ));
+            }
+        }
+
+        if let Err(err) = serde_json::from_slice::<serde::de::IgnoredAny>(src) {
+            return Err(format!("Malformed JSON-RPC message: {}", err));
+        }
+
         let json_iterator = JsonFirstLineIterator::new(src);
 
         let mut version: Option<String> = None;
@@ -272,7 +506,14 @@ impl McpInputPayload {
         // JSON-RPC response (no `method`, has `id` and `result`/`error`) →
         // routed to ServerResponse so the middleware can resolve the
         // matching pending server-initiated request (e.g. elicitation).
-        if method.is_none() && !id.is_null() && (result_json.is_some() || error_json.is_some()) {
+        // An error with a null id — the client could not read our
+        // request — matches none, and is routed there to be ignored.
+        let is_response = match (&result_json, &error_json) {
+            (_, Some(_)) => true,
+            (Some(_), None) => !id.is_null(),
+            (None, None) => false,
+        };
+        if method.is_none() && is_response {
             return Ok(Self {
                 _version: version.to_string(),
                 id,
@@ -338,6 +579,83 @@ pub struct ClientCapabilities {
     pub elicitation: Option<serde_json::Value>,
 }
 
+/// Deepest nesting of arrays and objects [`McpInputPayload::parse_untrusted`]
+/// lets through — serde_json's own recursion limit.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// How deep arrays and objects nest in `src`, strings skipped. Unbalanced
+/// brackets are counted as they come; the parser reports those itself.
+fn nesting_depth(src: &[u8]) -> usize {
+    let mut depth = 0usize;
+    let mut deepest = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for byte in src {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    deepest
+}
+
+/// Where the top-level JSON object of `src` ends, just past its closing
+/// `}`. `None` when `src` does not start with an object, or the object
+/// never closes — the parser reports those itself.
+fn end_of_object(src: &[u8]) -> Option<usize> {
+    let start = src.iter().position(|b| !b.is_ascii_whitespace())?;
+    if src[start] != b'{' {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, byte) in src.iter().enumerate().skip(start) {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use crate::mcp_middleware::*;
@@ -398,6 +716,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn array_params_of_a_tool_call_are_invalid_params() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/call","id":7,"params":["echo",{"text":"hi"}]}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        assert_eq!(parsed.id, RequestId::Int(7));
+        match parsed.data {
+            McpInputData::InvalidParams { method, message } => {
+                assert_eq!(method, "tools/call");
+                assert!(message.contains("Positional params"), "{}", message);
+            }
+            other => panic!("expected InvalidParams, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn array_params_of_a_param_less_method_are_ignored() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/list","id":1,"params":[]}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        assert!(matches!(parsed.data, McpInputData::ToolsList(_)));
+    }
+
+    #[test]
+    fn an_empty_uris_list_is_invalid_params() {
+        let payload = r#"{"jsonrpc":"2.0","method":"resources/read","id":3,"params":{"uris":[]}}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        match parsed.data {
+            McpInputData::InvalidParams { method, message } => {
+                assert_eq!(method, "resources/read");
+                assert!(message.contains("uris"), "{}", message);
+            }
+            other => panic!("expected InvalidParams, got {:?}", other),
+        }
+    }
+
     #[test]
     fn parse_jsonrpc_response_routes_to_server_response() {
         let payload = r#"{"jsonrpc":"2.0","id":-1,"result":{"action":"accept","content":{"password":"x"}}}"#;
@@ -427,6 +779,148 @@ mod tests {
         }
     }
 
+    #[test]
+    fn null_id_error_response_routes_to_server_response() {
+        let payload = r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Parse error"}}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        assert!(parsed.id.is_null());
+        assert!(matches!(parsed.data, McpInputData::ServerResponse { .. }));
+    }
+
+    #[test]
+    fn junk_after_the_object_is_rejected() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/list","id":1}garbage"#;
+        let err = McpInputPayload::try_parse(payload.as_bytes()).unwrap_err();
+        assert!(err.contains("at byte 46"), "{}", err);
+
+        // A second message is junk too: a body carries one.
+        let payload = r#"{"jsonrpc":"2.0","method":"ping","id":1} {"jsonrpc":"2.0","method":"ping","id":2}"#;
+        assert!(McpInputPayload::try_parse(payload.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn whitespace_after_the_object_is_accepted() {
+        let payload = "{\"jsonrpc\":\"2.0\",\"method\":\"tools/list\",\"id\":1}\n";
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        assert!(matches!(parsed.data, McpInputData::ToolsList(_)));
+
+        let payload = "  {\"jsonrpc\":\"2.0\",\"method\":\"tools/list\",\"id\":1}\r\n\t ";
+        assert!(McpInputPayload::try_parse(payload.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn untrusted_bytes_that_are_not_utf8_are_refused() {
+        for body in [
+            &b"\xff\xfe\xfd"[..],
+            b"{\"jsonrpc\":\"2.0\",\"method\":\"\xc3\x28\",\"id\":1}",
+            b"{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":\"\xed\xa0\x80\"}",
+            b"{\"jsonrpc\":\"2.0\",\"method\":\"tools/call\",\"id\":1,\"params\":{\"name\":\"\xf0\x9f\"}}",
+        ] {
+            let err = McpInputPayload::parse_untrusted(body).unwrap_err();
+            assert!(err.contains("not valid UTF-8"), "{:?}: {}", body, err);
+        }
+    }
+
+    fn parse_error(body: &str) -> String {
+        McpInputPayload::parse_untrusted(body.as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn an_empty_body_is_not_a_json_object() {
+        assert_eq!(parse_error(""), "Body is not a JSON object");
+        assert_eq!(parse_error("  \r\n"), "Body is not a JSON object");
+    }
+
+    #[test]
+    fn a_stray_closing_brace_is_not_a_json_object() {
+        assert_eq!(parse_error("}"), "Body is not a JSON object");
+    }
+
+    #[test]
+    fn an_array_of_messages_is_not_a_json_object() {
+        assert_eq!(
+            parse_error(r#"[{"jsonrpc":"2.0","method":"ping","id":1}]"#),
+            "Body is not a JSON object"
+        );
+    }
+
+    #[test]
+    fn an_unclosed_object_is_malformed() {
+        assert_eq!(
+            parse_error("{"),
+            "Malformed JSON-RPC message: EOF while parsing an object at line 1 column 1"
+        );
+    }
+
+    #[test]
+    fn an_unclosed_key_is_malformed() {
+        assert_eq!(
+            parse_error(r#"{""#),
+            "Malformed JSON-RPC message: EOF while parsing a string at line 1 column 2"
+        );
+    }
+
+    #[test]
+    fn a_key_cut_before_its_value_is_malformed() {
+        assert_eq!(
+            parse_error(r#"{"jsonrpc":"#),
+            "Malformed JSON-RPC message: EOF while parsing a value at line 1 column 11"
+        );
+    }
+
+    #[test]
+    fn a_key_without_a_value_is_malformed() {
+        assert_eq!(
+            parse_error(r#"{"jsonrpc":"2.0","id":}"#),
+            "Malformed JSON-RPC message: expected value at line 1 column 23"
+        );
+    }
+
+    #[test]
+    fn an_escape_cut_off_at_the_end_is_malformed() {
+        assert_eq!(
+            parse_error(r#"{"jsonrpc":"2.0","method":"ping","id":"\"#),
+            "Malformed JSON-RPC message: EOF while parsing a string at line 1 column 40"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_refused_before_parsing() {
+        let nested = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+        assert_eq!(parse_error(&nested), "Body nests deeper than 128 levels");
+
+        let deep_params = format!(
+            r#"{{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{}}}"#,
+            "[".repeat(10_000)
+        );
+        assert_eq!(
+            parse_error(&deep_params),
+            "Body nests deeper than 128 levels"
+        );
+    }
+
+    #[test]
+    fn every_cut_of_a_valid_message_is_malformed() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/call","id":7,"params":{"name":"echo","arguments":{"text":"h\u00e9 \"x\""}}}"#;
+        for end in 1..payload.len() {
+            let err = parse_error(&payload[..end]);
+            assert!(
+                err.starts_with("Malformed JSON-RPC message: EOF while parsing"),
+                "{}",
+                err
+            );
+        }
+        assert_eq!(parse_error(""), "Body is not a JSON object");
+        assert!(McpInputPayload::parse_untrusted(payload.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn braces_inside_strings_do_not_end_the_object() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/call","id":1,"params":{"name":"echo","arguments":{"text":"} \" {"}}}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        assert!(matches!(parsed.data, McpInputData::ExecuteToolCall(_)));
+    }
+
     #[test]
     fn string_request_id_is_preserved() {
         let payload = r#"{"jsonrpc":"2.0","method":"tools/list","id":"req-abc"}"#;
@@ -455,19 +949,107 @@ mod tests {
     }
 
     #[test]
-    fn tool_call_without_arguments_defaults_to_empty_object() {
-        let payload = r#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo"}}"#;
+    fn tool_call_without_arguments_has_none() {
+        // The middleware hands such a call `{}`.
+        for params in [r#"{"name":"echo"}"#, r#"{"name":"echo","arguments":null}"#] {
+            let payload = format!(
+                r#"{{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{}}}"#,
+                params
+            );
+            let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+            match parsed.data {
+                McpInputData::ExecuteToolCall(model) => {
+                    assert_eq!(model.name, "echo");
+                    assert!(model.arguments.is_none());
+                }
+                other => panic!("expected ExecuteToolCall, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn tool_call_arguments_keep_numbers_as_written() {
+        let payload = r#"{"jsonrpc":"2.0","method":"tools/call","id":2,"params":{"name":"echo","arguments":{"big":123456789012345678901234567890,"precise":0.1234567890123456789,"sci":1.5e-7}}}"#;
         let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
         match parsed.data {
-            McpInputData::ExecuteToolCall(model) => {
-                assert_eq!(model.name, "echo");
-                assert!(model.arguments.is_object());
-                assert_eq!(model.arguments.as_object().unwrap().len(), 0);
-            }
+            McpInputData::ExecuteToolCall(model) => assert_eq!(
+                model.arguments.unwrap().get(),
+                r#"{"big":123456789012345678901234567890,"precise":0.1234567890123456789,"sci":1.5e-7}"#
+            ),
             other => panic!("expected ExecuteToolCall, got {:?}", other),
         }
     }
 
+    #[test]
+    fn prompt_arguments_keep_numbers_as_written() {
+        let payload = r#"{"jsonrpc":"2.0","method":"prompts/get","id":2,"params":{"name":"report","arguments":{"big":123456789012345678901234567890,"precise":0.1234567890123456789,"sci":1.5e-7,"flag":true,"text":"a \"quoted\" word"}}}"#;
+        let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+        match parsed.data {
+            McpInputData::GetPrompt(model) => {
+                let arguments = model.arguments.unwrap();
+                assert_eq!(arguments["big"], "123456789012345678901234567890");
+                assert_eq!(arguments["precise"], "0.1234567890123456789");
+                assert_eq!(arguments["sci"], "1.5e-7");
+                assert_eq!(arguments["flag"], "true");
+                assert_eq!(arguments["text"], r#"a "quoted" word"#);
+            }
+            other => panic!("expected GetPrompt, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prompt_arguments_serialize_in_sorted_key_order() {
+        let names = ["zeta", "alpha", "mid", "beta", "omega", "kappa"];
+        let forward: HashMap<String, String> = names
+            .iter()
+            .map(|name| (name.to_string(), format!("value of {}", name)))
+            .collect();
+        let backward: HashMap<String, String> = names
+            .iter()
+            .rev()
+            .map(|name| (name.to_string(), format!("value of {}", name)))
+            .collect();
+
+        let key = crate::mcp_middleware::prompt_arguments_key(&forward);
+        assert_eq!(key, crate::mcp_middleware::prompt_arguments_key(&backward));
+        assert!(
+            key.starts_with(r#"{"alpha":"value of alpha","beta":"#),
+            "{}",
+            key
+        );
+        assert!(key.ends_with(r#""zeta":"value of zeta"}"#), "{}", key);
+
+        let serialized = |arguments: HashMap<String, String>| {
+            serde_json::to_string(&GetPromptModel {
+                name: "report".to_string(),
+                arguments: Some(arguments),
+            })
+            .unwrap()
+        };
+        let serialized_forward = serialized(forward);
+        assert_eq!(serialized_forward, serialized(backward));
+        assert_eq!(
+            serialized_forward,
+            format!(r#"{{"name":"report","arguments":{}}}"#, key)
+        );
+    }
+
+    #[test]
+    fn ping_ignores_whatever_params_it_gets() {
+        for params in [
+            r#""junk""#,
+            "42",
+            "[1,2,3]",
+            r#"{"uri":7,"nested":{"deep":[null,true]}}"#,
+            "null",
+        ] {
+            let payload = format!(r#"{{"jsonrpc":"2.0","method":"ping","id":5,"params":{}}}"#, params);
+            let parsed = McpInputPayload::try_parse(payload.as_bytes()).unwrap();
+            assert!(matches!(parsed.data, McpInputData::Ping), "{}", params);
+            assert_eq!(parsed.id, RequestId::Int(5));
+        }
+    }
+
     #[test]
     fn resource_templates_list_is_routed() {
         let payload = r#"{"jsonrpc":"2.0","method":"resources/templates/list","id":3}"#;
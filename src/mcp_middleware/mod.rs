@@ -1,11 +1,34 @@
+mod catch_panic;
+pub(crate) use catch_panic::*;
+mod audit_sink;
+pub use audit_sink::*;
+mod clock;
+pub use clock::*;
 mod connection_info;
 pub use connection_info::*;
 mod stream_updates;
 pub use stream_updates::*;
+mod server_version;
+pub use server_version::*;
 mod sessions;
 pub use sessions::*;
+mod session_buffer;
+pub use session_buffer::*;
+mod request_headers;
+pub use request_headers::*;
+mod request_filter;
+pub use request_filter::*;
+mod tool_call_scheduler;
+pub(crate) use tool_call_scheduler::*;
+pub use tool_call_scheduler::ToolCallLoad;
+mod pending_response;
+pub(crate) use pending_response::*;
 mod elicitations;
 pub use elicitations::*;
+mod completion_provider;
+pub use completion_provider::*;
+mod mcp_method;
+pub use mcp_method::*;
 mod mcp_payload;
 pub use mcp_payload::*;
 mod mcp_output_contract;
@@ -18,5 +41,9 @@ mod prompts;
 pub use prompts::*;
 mod resources;
 pub use resources::*;
+#[cfg(any(test, feature = "conformance"))]
+mod conformance;
+#[cfg(any(test, feature = "conformance"))]
+pub use conformance::*;
 
 pub const SESSION_HEADER: &'static str = "mcp-session-id";
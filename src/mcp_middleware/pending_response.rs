@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use tokio::sync::oneshot;
+
+use super::{AuditTrail, ContentEncoding, McpSessions, RequestId};
+
+/// A request's place in its session's response queue, held until the
+/// response is written. In ordered mode (see
+/// `McpMiddleware::with_ordered_responses`) every request of a session
+/// is chained to the one that arrived before it: the handler still runs
+/// right away, only the write waits for [`Self::wait_for_turn`].
+/// Dropping the handle — response written, request abandoned, client
+/// gone — lets the next one through.
+///
+/// It also carries the request's [`InFlightRequest`], so the request id
+/// stays taken exactly as long as the request is being served, and the
+/// compression the client negotiated for the response body, whether the
+/// response may go out as a plain JSON body instead of a stream, the
+/// longest SSE line the response may have, and the exchange's
+/// [`AuditTrail`] when an audit sink is registered.
+pub(crate) struct PendingResponse {
+    predecessor: Option<oneshot::Receiver<()>>,
+    _done: Option<oneshot::Sender<()>>,
+    in_flight: Option<InFlightRequest>,
+    /// Encoding and the body size from which on it is applied.
+    compression: Option<(ContentEncoding, usize)>,
+    json_response: bool,
+    max_line: Option<usize>,
+    audit: Option<AuditTrail>,
+}
+
+impl PendingResponse {
+    /// No ordering: the response goes out as soon as it is ready.
+    pub(crate) fn unordered() -> Self {
+        Self {
+            predecessor: None,
+            _done: None,
+            in_flight: None,
+            compression: None,
+            json_response: false,
+            max_line: None,
+            audit: None,
+        }
+    }
+
+    pub(crate) fn ordered(
+        predecessor: Option<oneshot::Receiver<()>>,
+        done: oneshot::Sender<()>,
+    ) -> Self {
+        Self {
+            predecessor,
+            _done: Some(done),
+            in_flight: None,
+            compression: None,
+            json_response: false,
+            max_line: None,
+            audit: None,
+        }
+    }
+
+    pub(crate) fn with_in_flight(mut self, in_flight: Option<InFlightRequest>) -> Self {
+        self.in_flight = in_flight;
+        self
+    }
+
+    pub(crate) fn with_compression(mut self, compression: Option<(ContentEncoding, usize)>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub(crate) fn with_json_response(mut self, json_response: bool) -> Self {
+        self.json_response = json_response;
+        self
+    }
+
+    pub(crate) fn with_max_line(mut self, max_line: Option<usize>) -> Self {
+        self.max_line = max_line;
+        self
+    }
+
+    pub(crate) fn with_audit(mut self, audit: Option<AuditTrail>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// `initialize` learns its session id only once it has minted it.
+    pub(crate) fn set_audit_session_id(&mut self, session_id: &str) {
+        if let Some(audit) = self.audit.as_mut() {
+            audit.set_session_id(session_id);
+        }
+    }
+
+    /// Hands the (framed) response to the audit sink, if there is one.
+    /// Called right before it is written.
+    pub(crate) fn audit_response(&mut self, response: &str) {
+        if let Some(audit) = self.audit.take() {
+            audit.record(super::unframe(response.to_string()));
+        }
+    }
+
+    /// The framed response as it goes on the wire: split into several
+    /// `data:` lines if it is longer than the line limit. See
+    /// `McpMiddleware::with_max_sse_line_size`.
+    pub(crate) fn split_lines(&self, response: String) -> String {
+        match self.max_line {
+            Some(max_line) => super::split_frame_lines(response, max_line),
+            None => response,
+        }
+    }
+
+    /// How to encode a response body of `len` bytes; `None` sends it
+    /// as is.
+    pub(crate) fn encoding_for(&self, len: usize) -> Option<ContentEncoding> {
+        match self.compression {
+            Some((encoding, threshold)) if len >= threshold => Some(encoding),
+            _ => None,
+        }
+    }
+
+    /// Whether a single-message response goes out as a plain
+    /// `application/json` body. See `McpMiddleware::with_json_responses`.
+    pub(crate) fn json_response(&self) -> bool {
+        self.json_response
+    }
+
+    /// Whether writing the response needs all of it at once — to
+    /// compress it, split its lines, send it as plain JSON or audit it —
+    /// so it can not go out part by part.
+    pub(crate) fn needs_whole_response(&self) -> bool {
+        self.compression.is_some()
+            || self.json_response
+            || self.max_line.is_some()
+            || self.audit.is_some()
+    }
+
+    /// Frees the request id. Called right before the response is
+    /// written, so a client that reuses the id as soon as it reads the
+    /// answer is never told it is still in flight.
+    pub(crate) fn finish_request(&mut self) {
+        self.in_flight = None;
+    }
+
+    /// Resolves once the previous response of the session is out.
+    /// Immediate for the first request and in unordered mode.
+    pub(crate) async fn wait_for_turn(&mut self) {
+        // Polled by reference, so a cancelled wait can be resumed.
+        if let Some(predecessor) = self.predecessor.as_mut() {
+            // Nobody ever sends: the predecessor is done when its
+            // sender is dropped, which is exactly this `Err`.
+            let _ = predecessor.await;
+            self.predecessor = None;
+        }
+    }
+}
+
+/// A request id taken in its session for as long as the request is
+/// served. See [`McpSessions::begin_request`].
+pub(crate) struct InFlightRequest {
+    sessions: Arc<McpSessions>,
+    session_id: String,
+    id: RequestId,
+}
+
+impl InFlightRequest {
+    pub(crate) fn new(sessions: Arc<McpSessions>, session_id: String, id: RequestId) -> Self {
+        Self {
+            sessions,
+            session_id,
+            id,
+        }
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.sessions.end_request(self.session_id.as_str(), &self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn turn_comes_when_the_predecessor_is_dropped() {
+        let (first_done, second_waits_on) = oneshot::channel();
+        let first = PendingResponse::ordered(None, first_done);
+        let (second_done, _) = oneshot::channel();
+        let mut second = PendingResponse::ordered(Some(second_waits_on), second_done);
+
+        let wait = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            second.wait_for_turn(),
+        )
+        .await;
+        assert!(wait.is_err(), "must wait while the first is pending");
+
+        drop(first);
+        second.wait_for_turn().await;
+    }
+}
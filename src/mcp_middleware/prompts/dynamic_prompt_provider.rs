@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use my_http_server::async_trait;
+
+use super::McpPromptAbstract;
+
+/// Source of prompts generated at runtime — from templates kept in a
+/// database, say — instead of registered one by one. Register it with
+/// [`crate::McpMiddleware::register_prompt_provider`]; its prompts are
+/// listed and served next to the registered ones, which win on a name
+/// clash.
+///
+/// Both methods run on the request path of `prompts/list` and
+/// `prompts/get`. Call [`crate::McpMiddleware::notify_prompts_changed`]
+/// when the provider's set changes.
+#[async_trait::async_trait]
+pub trait DynamicPromptProvider {
+    async fn list(&self) -> Vec<Arc<dyn McpPromptAbstract + Send + Sync + 'static>>;
+
+    async fn get(&self, name: &str) -> Option<Arc<dyn McpPromptAbstract + Send + Sync + 'static>>;
+}
@@ -4,5 +4,7 @@ mod prompt_service;
 pub use prompt_service::*;
 mod prompt_executor;
 pub use prompt_executor::*;
+mod dynamic_prompt_provider;
+pub use dynamic_prompt_provider::*;
 mod prompts_manager;
 pub use prompts_manager::*;
@@ -13,4 +13,77 @@ pub struct PromptArgumentDescription {
     pub description: String,
     #[serde(default)]
     pub required: bool,
+    /// JSON Schema of the argument value (type, format, constraints),
+    /// listed in `prompts/list` so clients can validate input and build
+    /// forms. Values still arrive as strings in `prompts/get`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
+    /// Value the argument takes when `prompts/get` omits it. Applied
+    /// before the prompt runs, so the handler always sees it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Longest value accepted, in bytes. A `prompts/get` that sends more
+    /// is answered with `-32602` before the prompt runs — a guard for
+    /// arguments that take whole documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<usize>,
+}
+
+/// Fills the arguments the client omitted from their declared defaults.
+/// An omitted required argument without a default is an error, named in
+/// the message; arguments the prompt does not declare are left alone.
+pub(crate) fn apply_prompt_argument_defaults(
+    descriptions: &[PromptArgumentDescription],
+    arguments: &mut std::collections::HashMap<String, String>,
+) -> Result<(), String> {
+    for description in descriptions {
+        if arguments.contains_key(&description.name) {
+            continue;
+        }
+
+        match &description.default {
+            Some(default) => {
+                arguments.insert(description.name.clone(), default.clone());
+            }
+            None if description.required => {
+                return Err(format!("Missing required argument: {}", description.name));
+            }
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks every argument against its declared [`PromptArgumentDescription::max_size`].
+/// The first one over its limit is an error, named in the message.
+pub(crate) fn check_prompt_argument_sizes(
+    descriptions: &[PromptArgumentDescription],
+    arguments: &std::collections::HashMap<String, String>,
+) -> Result<(), String> {
+    for description in descriptions {
+        let (Some(max_size), Some(value)) = (description.max_size, arguments.get(&description.name))
+        else {
+            continue;
+        };
+
+        if value.len() > max_size {
+            return Err(format!(
+                "Argument {} is {} bytes, over its limit of {} bytes",
+                description.name,
+                value.len(),
+                max_size
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `arguments` as a JSON object with its keys in sorted order: the same
+/// arguments give the same text whatever order they arrived in, so it
+/// can key a cache or go to a log.
+pub fn prompt_arguments_key(arguments: &std::collections::HashMap<String, String>) -> String {
+    let sorted: std::collections::BTreeMap<&String, &String> = arguments.iter().collect();
+    serde_json::to_string(&sorted).unwrap_or_else(|_| "{}".to_string())
 }
@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     PromptExecutionResult,
-    mcp_middleware::{McpPromptAbstract, McpPromptService},
+    mcp_middleware::{McpPromptAbstract, McpPromptService, PromptDefinition},
 };
 use my_http_server::async_trait;
 
@@ -13,6 +13,30 @@ pub struct PromptExecutor {
     pub holder: Arc<dyn McpPromptService + Send + Sync + 'static>,
 }
 
+impl PromptExecutor {
+    /// Builds the executor from a [`PromptDefinition`], so the name,
+    /// description and arguments advertised in `prompts/list` come from
+    /// the one place they are declared.
+    pub fn from_definition<TDefinition: PromptDefinition>(
+        holder: Arc<dyn McpPromptService + Send + Sync + 'static>,
+    ) -> Self {
+        Self {
+            prompt_name: TDefinition::PROMPT_NAME,
+            description: TDefinition::DESCRIPTION,
+            argument_descriptions: TDefinition::get_argument_descriptions(),
+            holder,
+        }
+    }
+}
+
+impl<TPrompt: McpPromptService + PromptDefinition + Send + Sync + 'static> From<Arc<TPrompt>>
+    for PromptExecutor
+{
+    fn from(service: Arc<TPrompt>) -> Self {
+        Self::from_definition::<TPrompt>(service)
+    }
+}
+
 #[async_trait::async_trait]
 impl McpPromptAbstract for PromptExecutor {
     fn get_prompt_name(&self) -> &str {
@@ -23,6 +47,13 @@ impl McpPromptAbstract for PromptExecutor {
         &self.description
     }
 
+    async fn resolve_description(&self) -> String {
+        match self.holder.get_dynamic_description().await {
+            Some(description) => description,
+            None => self.description.to_string(),
+        }
+    }
+
     fn get_argument_descriptions(&self) -> Vec<super::PromptArgumentDescription> {
         self.argument_descriptions.clone()
     }
@@ -34,3 +65,65 @@ impl McpPromptAbstract for PromptExecutor {
         self.holder.execute_prompt(input).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp_middleware::PromptArgumentDescription;
+
+    struct SummarizePrompt;
+
+    impl PromptDefinition for SummarizePrompt {
+        const PROMPT_NAME: &'static str = "summarize";
+        const DESCRIPTION: &'static str = "Summarizes a text";
+
+        fn get_argument_descriptions() -> Vec<PromptArgumentDescription> {
+            vec![
+                PromptArgumentDescription {
+                    name: "text".to_string(),
+                    description: "Text to summarize".to_string(),
+                    required: true,
+                    schema: None,
+                    default: None,
+                    max_size: None,
+                },
+                PromptArgumentDescription {
+                    name: "style".to_string(),
+                    description: "Bullet points or prose".to_string(),
+                    required: false,
+                    schema: None,
+                    default: None,
+                    max_size: None,
+                },
+            ]
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl McpPromptService for SummarizePrompt {
+        async fn execute_prompt(
+            &self,
+            _arguments: &HashMap<String, String>,
+        ) -> Result<PromptExecutionResult, String> {
+            Err("not used".to_string())
+        }
+    }
+
+    #[test]
+    fn executor_advertises_what_the_definition_declares() {
+        let executor: PromptExecutor = Arc::new(SummarizePrompt).into();
+
+        assert_eq!(executor.get_prompt_name(), "summarize");
+        assert_eq!(executor.get_description(), "Summarizes a text");
+
+        let arguments: Vec<(String, bool)> = executor
+            .get_argument_descriptions()
+            .into_iter()
+            .map(|arg| (arg.name, arg.required))
+            .collect();
+        assert_eq!(
+            arguments,
+            vec![("text".to_string(), true), ("style".to_string(), false)]
+        );
+    }
+}
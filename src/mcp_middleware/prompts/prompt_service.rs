@@ -1,9 +1,13 @@
 use my_http_server::async_trait;
 use std::collections::HashMap;
 
+#[derive(Default)]
 pub struct PromptExecutionResult {
     pub description: String,
     pub message: String,
+    /// Resources inlined into the prompt (file contents and the like),
+    /// each sent as an embedded-resource message after `message`.
+    pub embedded_resources: Vec<crate::mcp_middleware::ResourceContent>,
 }
 
 /// Trait that must be implemented by prompt services to handle prompt execution
@@ -14,6 +18,14 @@ pub trait McpPromptService {
         &self,
         arguments: &HashMap<String, String>,
     ) -> Result<PromptExecutionResult, String>;
+
+    /// Description computed at `prompts/list` time, for prompts whose
+    /// description depends on current state (e.g. which templates are
+    /// available). `None` (the default) keeps the static
+    /// `PromptDefinition::DESCRIPTION`.
+    async fn get_dynamic_description(&self) -> Option<String> {
+        None
+    }
 }
 
 /// Abstract trait for prompt services (similar to McpServiceAbstract for tools)
@@ -26,5 +38,12 @@ pub trait McpPromptAbstract {
 
     fn get_prompt_name(&self) -> &str;
     fn get_description(&self) -> &str;
+
+    /// The description `prompts/list` reports. Defaults to the static
+    /// [`Self::get_description`].
+    async fn resolve_description(&self) -> String {
+        self.get_description().to_string()
+    }
+
     fn get_argument_descriptions(&self) -> Vec<super::PromptArgumentDescription>;
 }
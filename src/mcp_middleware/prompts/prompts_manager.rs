@@ -1,25 +1,36 @@
 use super::*;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
 pub struct PromptSchemaData {
     pub prompt: Arc<dyn McpPromptAbstract + Send + Sync + 'static>,
+    pub description: String,
     pub argument_descriptions: Vec<PromptArgumentDescription>,
 }
 
+/// Cloning is cheap — prompts and providers are `Arc`s — and is how the
+/// middleware lists and resolves without holding its registry lock
+/// while the providers are awaited.
+#[derive(Clone)]
 pub struct McpPrompts {
     prompts: BTreeMap<String, Arc<dyn McpPromptAbstract + Send + Sync + 'static>>,
+    providers: Vec<Arc<dyn DynamicPromptProvider + Send + Sync + 'static>>,
 }
 
 impl McpPrompts {
     pub fn new() -> Self {
         Self {
             prompts: BTreeMap::new(),
+            providers: Vec::new(),
         }
     }
 
+    pub fn add_provider(&mut self, provider: Arc<dyn DynamicPromptProvider + Send + Sync + 'static>) {
+        self.providers.push(provider);
+    }
+
     pub fn add(&mut self, executor: Arc<dyn McpPromptAbstract + Send + Sync + 'static>) {
         let name = executor.get_prompt_name().to_string();
         self.prompts.insert(name, executor);
@@ -30,21 +41,36 @@ impl McpPrompts {
         prompt_name: &str,
         input: &HashMap<String, String>,
     ) -> Result<PromptExecutionResult, String> {
-        if let Some(executor) = self.prompts.get(prompt_name) {
+        if let Some(executor) = self.resolve(prompt_name).await {
             return executor.execute(input).await;
         }
 
         Err(format!("Prompt with name {} is not found", prompt_name))
     }
 
-    pub fn get_list(&self) -> Vec<PromptSchemaData> {
-        let mut result = Vec::with_capacity(self.prompts.len());
+    /// Registered prompts first, then the providers' in the order the
+    /// providers were added. A name already listed is skipped.
+    pub async fn get_list(&self) -> Vec<PromptSchemaData> {
+        let mut prompts: Vec<_> = self.prompts.values().cloned().collect();
+        let mut names: HashSet<String> = self.prompts.keys().cloned().collect();
+
+        for provider in self.providers.iter() {
+            for prompt in provider.list().await {
+                if names.insert(prompt.get_prompt_name().to_string()) {
+                    prompts.push(prompt);
+                }
+            }
+        }
+
+        let mut result = Vec::with_capacity(prompts.len());
 
-        for prompt in self.prompts.values() {
+        for prompt in prompts {
             let argument_descriptions = prompt.get_argument_descriptions();
+            let description = prompt.resolve_description().await;
 
             result.push(PromptSchemaData {
-                prompt: prompt.clone(),
+                prompt,
+                description,
                 argument_descriptions,
             });
         }
@@ -54,12 +80,50 @@ impl McpPrompts {
 }
 
 impl McpPrompts {
+    /// A registered prompt only; see [`Self::resolve`] for one a
+    /// provider may supply.
     pub fn get(&self, name: &str) -> Option<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
         self.prompts.get(name).map(|p| p.clone())
     }
 
+    /// The registered prompt with that name or, failing that, the first
+    /// provider's that has one.
+    pub async fn resolve(
+        &self,
+        name: &str,
+    ) -> Option<Arc<dyn McpPromptAbstract + Send + Sync + 'static>> {
+        if let Some(prompt) = self.get(name) {
+            return Some(prompt);
+        }
+
+        for provider in self.providers.iter() {
+            if let Some(prompt) = provider.get(name).await {
+                return Some(prompt);
+            }
+        }
+
+        None
+    }
+
+    /// A provider counts even while it lists nothing: its prompts can
+    /// come and go after `initialize`.
     pub fn has_prompts(&self) -> bool {
-        !self.prompts.is_empty()
+        !self.prompts.is_empty() || !self.providers.is_empty()
+    }
+
+    /// Registered prompts only: the providers' are not counted.
+    pub fn count(&self) -> usize {
+        self.prompts.len()
+    }
+
+    /// Whether a prompt with that name was registered.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.prompts.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.prompts.clear();
+        self.providers.clear();
     }
 }
 
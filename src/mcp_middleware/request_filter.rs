@@ -0,0 +1,22 @@
+use my_http_server::{HttpContext, HttpFailResult, HttpOkResult, async_trait};
+
+/// Host hook that sees every POST to the MCP endpoint before anything
+/// is parsed — WAF-style rules, audit logging of raw traffic. Register
+/// it with [`crate::McpMiddleware::register_request_filter`].
+///
+/// `body` is the raw request body exactly as received, `ctx` gives the
+/// headers (`ctx.request.get_headers()`) and the client IP. Returning
+/// `None` passes the request on to the middleware; `Some(result)` is
+/// sent back as is and the request goes no further — no parsing, no
+/// session lookup.
+///
+/// With a filter registered the body is copied once per request; with
+/// none the request path is untouched.
+#[async_trait::async_trait]
+pub trait McpRequestFilter {
+    async fn filter(
+        &self,
+        ctx: &HttpContext,
+        body: &[u8],
+    ) -> Option<Result<HttpOkResult, HttpFailResult>>;
+}
@@ -0,0 +1,229 @@
+use std::io::Write;
+
+use my_http_server::HttpContext;
+
+/// The request headers the middleware acts on besides
+/// `mcp-session-id`, read once before the body is borrowed.
+#[derive(Debug, Clone, Default)]
+pub struct McpRequestHeaders {
+    pub accept: Option<String>,
+    pub accept_encoding: Option<String>,
+}
+
+impl McpRequestHeaders {
+    pub fn from_http_context(ctx: &HttpContext) -> Self {
+        let header = |name: &str| {
+            ctx.request
+                .get_headers()
+                .try_get_case_insensitive(name)
+                .and_then(|value| value.as_str().ok().map(|value| value.to_string()))
+        };
+
+        Self {
+            accept: header("accept"),
+            accept_encoding: header("accept-encoding"),
+        }
+    }
+
+    /// Whether the client takes a response as a plain `application/json`
+    /// body rather than only as an SSE stream. Only an explicit listing
+    /// counts: `*/*` alone does not ask for JSON.
+    pub fn accepts_json(&self) -> bool {
+        let Some(accept) = self.accept.as_deref() else {
+            return false;
+        };
+
+        listing(accept, "application/json") == Listing::Accepted
+    }
+
+    /// The encoding to compress a response with, if the client accepts
+    /// one we support. `gzip` wins over `deflate`; `*` stands for the
+    /// codings the header does not list.
+    pub fn preferred_encoding(&self) -> Option<ContentEncoding> {
+        let accept_encoding = self.accept_encoding.as_deref()?;
+
+        let acceptable = |coding: &str| match listing(accept_encoding, coding) {
+            Listing::Accepted => true,
+            Listing::Refused => false,
+            Listing::Unlisted => listing(accept_encoding, "*") == Listing::Accepted,
+        };
+
+        if acceptable("gzip") {
+            return Some(ContentEncoding::Gzip);
+        }
+
+        if acceptable("deflate") {
+            return Some(ContentEncoding::Deflate);
+        }
+
+        None
+    }
+}
+
+/// What an `Accept*` header says of one value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Listing {
+    Unlisted,
+    /// Listed with a `q` above 0.
+    Accepted,
+    /// Listed with `q=0`, "not acceptable". A value refused once stays
+    /// refused however else it is listed.
+    Refused,
+}
+
+/// How `header` lists `value`, compared case-insensitively.
+fn listing(header: &str, value: &str) -> Listing {
+    let mut result = Listing::Unlisted;
+
+    for item in header.split(',') {
+        let mut parts = item.split(';');
+        let listed = parts.next().unwrap_or_default().trim();
+        if !listed.eq_ignore_ascii_case(value) {
+            continue;
+        }
+
+        if quality(parts) > 0.0 {
+            if result == Listing::Unlisted {
+                result = Listing::Accepted;
+            }
+        } else {
+            result = Listing::Refused;
+        }
+    }
+
+    result
+}
+
+/// The `q` among an `Accept*` item's parameters, as a number: `q=0`,
+/// `q=0.0` and `Q = 0.000` are all 0. Without one, or with one that is
+/// not a number, it is 1.
+fn quality<'s>(params: impl Iterator<Item = &'s str>) -> f32 {
+    for param in params {
+        let Some((name, value)) = param.split_once('=') else {
+            continue;
+        };
+
+        if name.trim().eq_ignore_ascii_case("q") {
+            return value.trim().parse::<f32>().unwrap_or(1.0);
+        }
+    }
+
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Value of the `Content-Encoding` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                // Writing into a Vec can not fail.
+                encoder.write_all(payload).unwrap();
+                encoder.finish().unwrap()
+            }
+            // HTTP `deflate` is the zlib format, not raw deflate.
+            Self::Deflate => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(payload).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(accept_encoding: &str) -> McpRequestHeaders {
+        McpRequestHeaders {
+            accept: None,
+            accept_encoding: Some(accept_encoding.to_string()),
+        }
+    }
+
+    fn accepting(accept: &str) -> McpRequestHeaders {
+        McpRequestHeaders {
+            accept: Some(accept.to_string()),
+            accept_encoding: None,
+        }
+    }
+
+    #[test]
+    fn json_is_accepted_only_when_listed() {
+        assert!(accepting("application/json, text/event-stream").accepts_json());
+        assert!(accepting("text/event-stream;q=1, Application/JSON;q=0.5").accepts_json());
+
+        assert!(!accepting("text/event-stream").accepts_json());
+        assert!(!accepting("application/json;q=0, text/event-stream").accepts_json());
+        assert!(!McpRequestHeaders::default().accepts_json());
+    }
+
+    #[test]
+    fn gzip_is_preferred_over_deflate() {
+        assert_eq!(
+            headers("deflate, gzip;q=0.8").preferred_encoding(),
+            Some(ContentEncoding::Gzip)
+        );
+        assert_eq!(
+            headers("br, deflate").preferred_encoding(),
+            Some(ContentEncoding::Deflate)
+        );
+    }
+
+    #[test]
+    fn refused_or_unknown_encodings_mean_no_compression() {
+        assert_eq!(headers("gzip;q=0").preferred_encoding(), None);
+        assert_eq!(headers("gzip; Q = 0.000").preferred_encoding(), None);
+        assert_eq!(headers("br, identity").preferred_encoding(), None);
+        assert_eq!(headers("*;q=0").preferred_encoding(), None);
+        assert_eq!(McpRequestHeaders::default().preferred_encoding(), None);
+    }
+
+    #[test]
+    fn the_wildcard_covers_only_the_codings_not_listed() {
+        assert_eq!(
+            headers("gzip;q=0, *").preferred_encoding(),
+            Some(ContentEncoding::Deflate)
+        );
+        assert_eq!(
+            headers("gzip;q=0, deflate;q=0, *").preferred_encoding(),
+            None
+        );
+        assert_eq!(
+            headers("br, *;q=0.1").preferred_encoding(),
+            Some(ContentEncoding::Gzip)
+        );
+        // Refused once, refused for good.
+        assert_eq!(
+            headers("gzip, gzip;q=0, deflate").preferred_encoding(),
+            Some(ContentEncoding::Deflate)
+        );
+    }
+
+    #[test]
+    fn q_is_read_as_a_number() {
+        assert_eq!(
+            headers("gzip;q=0.001").preferred_encoding(),
+            Some(ContentEncoding::Gzip)
+        );
+        assert!(!accepting("application/json;q=0.000, text/event-stream").accepts_json());
+        assert!(accepting("application/json; q=0.1, text/event-stream").accepts_json());
+        assert!(!accepting("*/*").accepts_json());
+    }
+}
@@ -49,7 +49,7 @@ impl McpResourceAbstract for DynamicResourceExecutor {
         self.icons.clone()
     }
 
-    async fn read(&self) -> Result<ResourceReadResult, String> {
-        self.holder.read_resource().await
+    async fn read(&self, uri: &str) -> Result<ResourceReadResult, String> {
+        self.holder.read_resource(uri).await
     }
 }
@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::sync::Arc;
 
-use super::{DynamicResourceExecutor, ResourceReadResult, ResourceSchemaData};
+use super::{DynamicResourceExecutor, McpResourceAbstract, ResourceReadResult, ResourceSchemaData};
 
 /// Runtime-mutable resource registry, mirroring [`McpResources`] for the
 /// dynamic case. Lookup / list / has_resources match the static API so
@@ -31,30 +32,74 @@ impl DynamicResources {
         !self.items.is_empty()
     }
 
+    pub fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
     pub fn contains(&self, uri: &str) -> bool {
         self.items.contains_key(uri)
     }
 
+    /// The resource registered as `uri`, to read once the registry lock
+    /// is released.
+    pub fn get(&self, uri: &str) -> Option<Arc<dyn McpResourceAbstract + Send + Sync + 'static>> {
+        let executor = self.items.get(uri)?.clone();
+        Some(executor)
+    }
+
+    pub fn title(&self, uri: &str) -> Option<String> {
+        self.items.get(uri)?.title.clone()
+    }
+
     pub async fn read(&self, uri: &str) -> Result<ResourceReadResult, String> {
         if let Some(executor) = self.items.get(uri) {
-            return executor.holder.read_resource().await;
+            return executor.read(uri).await;
         }
         Err(format!("Dynamic resource {} not found", uri))
     }
 
-    /// Snapshot of every dynamic resource as `ResourceSchemaData`. No
-    /// pagination — dynamic registries are expected to stay in the
-    /// "tens to low thousands" range for our use cases (per-message
-    /// media in `telegram-ingest`). If a registry ever grows large
-    /// enough that the unpaginated list becomes a problem, this is the
-    /// place to bolt on cursor-based slicing.
-    pub fn list(&self) -> Vec<ResourceSchemaData> {
-        self.items
-            .values()
-            .map(|e| ResourceSchemaData {
-                resource: e.clone(),
-            })
-            .collect()
+    /// One page of at most `page_size` dynamic resources, starting after
+    /// `cursor`, and the cursor of the next page — as
+    /// [`McpResources::get_list`] pages the static ones, so one cursor
+    /// walks both.
+    pub fn get_list(
+        &self,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> (Vec<ResourceSchemaData>, Option<String>) {
+        let lower = match cursor {
+            Some(c) => Bound::Excluded(c.to_string()),
+            None => Bound::Unbounded,
+        };
+
+        let mut iter = self.items.range((lower, Bound::Unbounded));
+
+        let mut result = Vec::with_capacity(page_size);
+        let mut last_uri: Option<String> = None;
+
+        for _ in 0..page_size {
+            match iter.next() {
+                Some((uri, executor)) => {
+                    last_uri = Some(uri.clone());
+                    result.push(ResourceSchemaData {
+                        resource: executor.clone(),
+                    });
+                }
+                None => break,
+            }
+        }
+
+        let next_cursor = if iter.next().is_some() {
+            last_uri
+        } else {
+            None
+        };
+
+        (result, next_cursor)
     }
 }
 
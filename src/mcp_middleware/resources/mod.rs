@@ -2,6 +2,8 @@ mod resource_definition;
 pub use resource_definition::*;
 mod resource_service;
 pub use resource_service::*;
+mod resource_read_result_builder;
+pub use resource_read_result_builder::*;
 mod resource_executor;
 pub use resource_executor::*;
 mod resources_manager;
@@ -10,3 +12,5 @@ mod dynamic_resource_executor;
 pub use dynamic_resource_executor::*;
 mod dynamic_resources;
 pub use dynamic_resources::*;
+mod resource_tail;
+pub use resource_tail::*;
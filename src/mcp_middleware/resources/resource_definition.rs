@@ -19,6 +19,12 @@ pub trait ResourceDefinition {
     fn get_icons(&self) -> Vec<ResourceIcon> {
         Vec::new()
     }
+
+    /// Optional cache directives, stamped on every content a read
+    /// returns that does not carry its own
+    fn get_cache_control(&self) -> Option<super::ResourceCacheControl> {
+        None
+    }
 }
 
 /// Represents an icon for a resource
@@ -30,3 +36,85 @@ pub struct ResourceIcon {
     #[serde(default)]
     pub sizes: Vec<String>,
 }
+
+impl ResourceIcon {
+    /// `size` in the form the spec allows — `"WxH"` in pixels or
+    /// `"any"` — trimmed and lowercased. `Err` says what is wrong with
+    /// it.
+    pub fn normalize_size(size: &str) -> Result<String, String> {
+        let size = size.trim().to_ascii_lowercase();
+        if size == "any" {
+            return Ok(size);
+        }
+
+        let dimension = |value: &str| {
+            value.bytes().all(|b| b.is_ascii_digit())
+                && value.parse::<u32>().is_ok_and(|value| value > 0)
+        };
+
+        match size.split_once('x') {
+            Some((width, height)) if dimension(width) && dimension(height) => Ok(size),
+            _ => Err(format!(
+                "Icon size {:?} is neither \"WxH\" nor \"any\"",
+                size
+            )),
+        }
+    }
+
+    /// `icons` with their sizes normalized; a malformed size is dropped
+    /// with a warning naming `uri`, so it never reaches a client.
+    pub(crate) fn normalize_icons(uri: &str, icons: Vec<ResourceIcon>) -> Vec<ResourceIcon> {
+        icons
+            .into_iter()
+            .map(|mut icon| {
+                icon.sizes = icon
+                    .sizes
+                    .iter()
+                    .filter_map(|size| match Self::normalize_size(size) {
+                        Ok(size) => Some(size),
+                        Err(err) => {
+                            eprintln!("MCP resource {} icon {}: {}. Dropped", uri, icon.src, err);
+                            None
+                        }
+                    })
+                    .collect();
+                icon
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_sizes_are_wxh_or_any() {
+        assert_eq!(ResourceIcon::normalize_size("48x48").unwrap(), "48x48");
+        assert_eq!(ResourceIcon::normalize_size("any").unwrap(), "any");
+        assert_eq!(ResourceIcon::normalize_size(" 96X96 ").unwrap(), "96x96");
+        assert_eq!(ResourceIcon::normalize_size("ANY").unwrap(), "any");
+
+        for bad in [
+            "bad-size", "48", "48x", "x48", "0x48", "48x48x48", "-1x4", "+4x4", "",
+        ] {
+            assert!(ResourceIcon::normalize_size(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn malformed_sizes_are_dropped_at_registration() {
+        let icons = vec![ResourceIcon {
+            src: "https://example.com/icon.png".to_string(),
+            mime_type: "image/png".to_string(),
+            sizes: vec![
+                "48x48".to_string(),
+                "bad-size".to_string(),
+                "any".to_string(),
+            ],
+        }];
+
+        let icons = ResourceIcon::normalize_icons("notes://1", icons);
+        assert_eq!(icons[0].sizes, vec!["48x48", "any"]);
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
 use crate::mcp_middleware::{
-    McpResourceAbstract, McpResourceService, ResourceIcon, ResourceReadResult,
+    McpResourceAbstract, McpResourceService, ResourceBody, ResourceCacheControl, ResourceIcon,
+    ResourceReadResult,
 };
 use my_http_server::async_trait;
 
@@ -13,6 +14,7 @@ pub struct ResourceExecutor {
     pub title: Option<String>,
     pub size: Option<u64>,
     pub icons: Vec<ResourceIcon>,
+    pub cache_control: Option<ResourceCacheControl>,
     pub holder: Arc<dyn McpResourceService + Send + Sync + 'static>,
 }
 
@@ -46,7 +48,23 @@ impl McpResourceAbstract for ResourceExecutor {
         self.icons.clone()
     }
 
-    async fn read(&self) -> Result<ResourceReadResult, String> {
-        self.holder.read_resource().await
+    async fn read(&self, uri: &str) -> Result<ResourceReadResult, String> {
+        let mut result = self.holder.read_resource(uri).await?;
+
+        if self.cache_control.is_some() {
+            for content in result.contents.iter_mut() {
+                // A failed part or a link is not the resource's content:
+                // nothing about it is worth keeping.
+                let cacheable = !matches!(
+                    content.body,
+                    ResourceBody::Error(_) | ResourceBody::Redirect(_)
+                );
+                if cacheable && content.cache_control.is_none() {
+                    content.cache_control = self.cache_control;
+                }
+            }
+        }
+
+        Ok(result)
     }
 }
@@ -0,0 +1,134 @@
+use base64::Engine;
+
+use super::{ResourceContent, ResourceReadResult};
+
+/// Builds a [`ResourceReadResult`] one content block at a time. Every
+/// block it adds carries exactly one of `text` or `blob`, and blobs are
+/// base64-encoded here, so a handler never gets either wrong. Start one
+/// with [`ResourceReadResult::builder`].
+#[derive(Debug, Default)]
+pub struct ResourceReadResultBuilder {
+    contents: Vec<ResourceContent>,
+}
+
+impl ResourceReadResultBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A text block. An empty `mime_type` goes out as `text/plain`.
+    pub fn add_text(
+        mut self,
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        text: impl Into<String>,
+    ) -> Self {
+        self.contents
+            .push(ResourceContent::text(uri, mime_type, text));
+        self
+    }
+
+    /// A binary block; `bytes` are base64-encoded for the wire. An empty
+    /// `mime_type` goes out as `application/octet-stream`.
+    pub fn add_blob(
+        mut self,
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        bytes: impl AsRef<[u8]>,
+    ) -> Self {
+        let blob = base64::engine::general_purpose::STANDARD.encode(bytes);
+        self.contents
+            .push(ResourceContent::blob(uri, mime_type, blob));
+        self
+    }
+
+    /// A text block stored in `charset`, transcoded to UTF-8 — or a
+    /// blob of the bytes as they are when it can not be. See
+    /// [`ResourceContent::encoded_text`].
+    pub fn add_encoded_text(
+        mut self,
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        bytes: impl AsRef<[u8]>,
+        charset: &str,
+    ) -> Self {
+        self.contents.push(ResourceContent::encoded_text(
+            uri, mime_type, bytes, charset,
+        ));
+        self
+    }
+
+    /// Any other block — a [`ResourceContent::listing`], an
+    /// [`ResourceContent::error`] placeholder, a redirect.
+    pub fn add_content(mut self, content: ResourceContent) -> Self {
+        self.contents.push(content);
+        self
+    }
+
+    pub fn build(self) -> ResourceReadResult {
+        ResourceReadResult {
+            contents: self.contents,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_a_mixed_text_and_blob_result() {
+        let result = ResourceReadResult::builder()
+            .add_text("docs://readme", "text/markdown", "# Readme")
+            .add_blob("docs://logo", "image/png", [0x89, b'P', b'N', b'G'])
+            .add_blob("docs://raw", "", b"hello")
+            .build();
+
+        assert_eq!(result.contents.len(), 3);
+
+        let text = &result.contents[0];
+        assert_eq!(text.uri, "docs://readme");
+        assert_eq!(text.mime_type, "text/markdown");
+        assert_eq!(text.as_text(), Some("# Readme"));
+        assert!(text.as_blob().is_none());
+
+        let blob = &result.contents[1];
+        assert_eq!(blob.uri, "docs://logo");
+        assert_eq!(blob.mime_type, "image/png");
+        assert_eq!(blob.as_blob(), Some("iVBORw=="));
+        assert!(blob.as_text().is_none());
+
+        let raw = &result.contents[2];
+        assert_eq!(raw.as_blob(), Some("aGVsbG8="));
+        assert_eq!(raw.effective_mime_type(), "application/octet-stream");
+    }
+
+    #[test]
+    fn encoded_text_is_transcoded_or_kept_as_a_blob() {
+        let result = ResourceReadResult::builder()
+            .add_encoded_text(
+                "logs://cafe",
+                "text/plain",
+                [0x63, 0x61, 0x66, 0xE9],
+                "ISO-8859-1",
+            )
+            .add_encoded_text("logs://ascii", "text/plain", [0xE9], "us-ascii")
+            .add_encoded_text("logs://sjis", "", [0x82, 0xA0], "Shift_JIS")
+            .build();
+
+        let latin1 = &result.contents[0];
+        assert_eq!(latin1.as_text(), Some("café"));
+        assert_eq!(latin1.mime_type, "text/plain");
+        assert!(latin1.as_blob().is_none());
+
+        let ascii = &result.contents[1];
+        assert!(ascii.as_text().is_none());
+        assert_eq!(ascii.as_blob(), Some("6Q=="));
+        assert_eq!(ascii.mime_type, "text/plain; charset=us-ascii");
+
+        let sjis = &result.contents[2];
+        assert!(sjis.as_text().is_none());
+        assert_eq!(sjis.as_blob(), Some("gqA="));
+        assert_eq!(sjis.mime_type, "text/plain; charset=Shift_JIS");
+    }
+}
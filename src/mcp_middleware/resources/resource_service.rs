@@ -1,4 +1,6 @@
+use base64::Engine;
 use my_http_server::async_trait;
+use rust_extensions::date_time::DateTimeAsMicroseconds;
 
 use crate::mcp_middleware::ResourceIcon;
 
@@ -6,25 +8,417 @@ pub struct ResourceReadResult {
     pub contents: Vec<ResourceContent>,
 }
 
+/// How many content blocks one `resources/read` response carries at most.
+pub const READ_PAGE_SIZE: usize = 100;
+
+impl ResourceReadResult {
+    /// See [`ResourceReadResultBuilder`](super::ResourceReadResultBuilder).
+    pub fn builder() -> super::ResourceReadResultBuilder {
+        super::ResourceReadResultBuilder::new()
+    }
+
+    /// Cuts one page of at most [`READ_PAGE_SIZE`] content blocks out of
+    /// the result, starting where `cursor` points. Returns the page and
+    /// the cursor of the next one, `None` on the last page — a result
+    /// that fits one page goes out as is. The cursor is the index of
+    /// the first block of the page; anything else is an error.
+    pub fn page(self, cursor: Option<&str>) -> Result<(Self, Option<String>), String> {
+        self.page_of(cursor, READ_PAGE_SIZE)
+    }
+
+    /// [`Self::page`] with pages of at most `page_size` blocks.
+    pub fn page_of(
+        self,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<(Self, Option<String>), String> {
+        let start = match cursor {
+            Some(cursor) => match cursor.parse::<usize>() {
+                Ok(start) if start <= self.contents.len() => start,
+                _ => return Err(format!("Invalid cursor: {}", cursor)),
+            },
+            None => 0,
+        };
+
+        let end = (start + page_size.max(1)).min(self.contents.len());
+        let next_cursor = if end < self.contents.len() {
+            Some(end.to_string())
+        } else {
+            None
+        };
+
+        let contents = self
+            .contents
+            .into_iter()
+            .skip(start)
+            .take(end - start)
+            .collect();
+
+        Ok((Self { contents }, next_cursor))
+    }
+
+    /// Hash of everything a client would see of the read. Equal hashes
+    /// mean the resource did not change between two reads.
+    pub fn content_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.contents.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Where the read points when it is nothing but a redirect.
+    pub fn redirect_target(&self) -> Option<&str> {
+        match self.contents.as_slice() {
+            [only] => match &only.body {
+                ResourceBody::Redirect(target) => Some(target.as_str()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// One content block of a read. Made with [`Self::text`],
+/// [`Self::blob`] or one of the other constructors, so it always has
+/// exactly one [`ResourceBody`].
+#[derive(Debug, Clone, Hash)]
 pub struct ResourceContent {
     pub uri: String,
     pub mime_type: String,
+    pub body: ResourceBody,
+    /// When this content last changed, as an ISO 8601 timestamp. See
+    /// [`Self::with_last_modified`].
+    pub(crate) last_modified: Option<String>,
+    /// Which bytes of the whole this content is. See [`Self::slice`].
+    pub(crate) range: Option<ContentRange>,
+    /// How long a client may keep this content. See
+    /// [`Self::with_cache_control`].
+    pub(crate) cache_control: Option<ResourceCacheControl>,
+}
+
+/// What a [`ResourceContent`] holds — one of these, never two.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceBody {
     /// Text content (direct string, not nested)
-    pub text: Option<String>,
+    Text(String),
     /// Binary content (base64-encoded string)
-    pub blob: Option<String>,
+    Blob(String),
+    /// Children of a directory-like resource. See
+    /// [`ResourceContent::listing`].
+    Listing(Vec<ResourceListingEntry>),
+    /// Why this part could not be read. See [`ResourceContent::error`].
+    Error(String),
+    /// URI this resource is an alias of. See
+    /// [`ResourceContent::redirect`].
+    Redirect(String),
+}
+
+/// Bytes `start..end` of a content `total` bytes long, as `_meta.range`
+/// of a partial read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    pub total: u64,
+}
+
+/// Cache directives of a resource, after HTTP's `Cache-Control`: they
+/// go out as `_meta.cacheControl` of its contents, `{maxAge, immutable}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ResourceCacheControl {
+    /// Seconds a client may reuse its copy without reading again.
+    pub max_age: Option<u64>,
+    /// The content never changes under its URI: a client may keep its
+    /// copy for good.
+    pub immutable: bool,
+}
+
+impl ResourceCacheControl {
+    pub fn max_age(seconds: u64) -> Self {
+        Self {
+            max_age: Some(seconds),
+            immutable: false,
+        }
+    }
+
+    pub fn immutable() -> Self {
+        Self {
+            max_age: None,
+            immutable: true,
+        }
+    }
+}
+
+impl ResourceContent {
+    fn new(uri: impl Into<String>, mime_type: impl Into<String>, body: ResourceBody) -> Self {
+        Self {
+            uri: uri.into(),
+            mime_type: mime_type.into(),
+            body,
+            last_modified: None,
+            range: None,
+            cache_control: None,
+        }
+    }
+
+    /// Text content. An empty `mime_type` goes out as `text/plain`.
+    pub fn text(
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        text: impl Into<String>,
+    ) -> Self {
+        Self::new(uri, mime_type, ResourceBody::Text(text.into()))
+    }
+
+    /// Binary content, `base64` being the bytes already encoded. An
+    /// empty `mime_type` goes out as `application/octet-stream`.
+    pub fn blob(
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        base64: impl Into<String>,
+    ) -> Self {
+        Self::new(uri, mime_type, ResourceBody::Blob(base64.into()))
+    }
+
+    /// Content of a resource that stands for a collection (a folder, a
+    /// table, a bucket prefix) rather than a file. On the wire it is a
+    /// regular text content whose `text` is the JSON array of
+    /// `{uri, name, mimeType?}` children, so any client can display it
+    /// and a client that knows `inode/directory` can browse the tree by
+    /// reading the child URIs in turn.
+    pub fn listing(uri: impl Into<String>, children: Vec<ResourceListingEntry>) -> Self {
+        Self::new(uri, DIRECTORY_MIME_TYPE, ResourceBody::Listing(children))
+    }
+
+    /// Placeholder for one part of a multi-part read that failed, so
+    /// the read as a whole still succeeds with the parts that did not.
+    /// On the wire it is a text content carrying `message`, marked with
+    /// `_meta.isError: true`.
+    pub fn error(uri: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(uri, "", ResourceBody::Error(message.into()))
+    }
+
+    /// Marks the resource at `uri` as an alias of `target`. A read whose
+    /// only content is a redirect is followed by the middleware — the
+    /// client gets `target`'s contents — unless following is turned off
+    /// with [`crate::McpMiddleware::disabled_resource_redirects`]; then,
+    /// and whenever it is not the only content, it goes out as a link:
+    /// a [`REDIRECT_MIME_TYPE`] text content whose `text` is `target`,
+    /// with `_meta.redirect` set to it as well.
+    pub fn redirect(uri: impl Into<String>, target: impl Into<String>) -> Self {
+        Self::new(
+            uri,
+            REDIRECT_MIME_TYPE,
+            ResourceBody::Redirect(target.into()),
+        )
+    }
+
+    /// Text stored in `charset` rather than UTF-8 — a latin-1 log, say.
+    /// A charset [`decode_text`] knows is transcoded into `text`;
+    /// anything else, or bytes that are not valid in it, go out as a
+    /// `blob` of the original bytes with `charset` noted in the MIME
+    /// type (`text/plain; charset=shift_jis`), so nothing is lost.
+    pub fn encoded_text(
+        uri: impl Into<String>,
+        mime_type: impl Into<String>,
+        bytes: impl AsRef<[u8]>,
+        charset: &str,
+    ) -> Self {
+        let bytes = bytes.as_ref();
+        let mut mime_type = mime_type.into();
+
+        match decode_text(bytes, charset) {
+            Some(text) => Self::text(uri, mime_type, text),
+            None => {
+                if mime_type.is_empty() {
+                    mime_type = DEFAULT_TEXT_MIME_TYPE.to_string();
+                }
+                let mime_type = format!("{}; charset={}", mime_type, charset.trim());
+                let blob = base64::engine::general_purpose::STANDARD.encode(bytes);
+                Self::blob(uri, mime_type, blob)
+            }
+        }
+    }
+
+    /// The text of a text content; `None` for any other body.
+    pub fn as_text(&self) -> Option<&str> {
+        match &self.body {
+            ResourceBody::Text(text) => Some(text.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The base64 of a blob content; `None` for any other body.
+    pub fn as_blob(&self) -> Option<&str> {
+        match &self.body {
+            ResourceBody::Blob(blob) => Some(blob.as_str()),
+            _ => None,
+        }
+    }
+
+    /// When the content last changed, as set by
+    /// [`Self::with_last_modified`].
+    pub fn last_modified(&self) -> Option<&str> {
+        self.last_modified.as_deref()
+    }
+
+    /// Which bytes of the whole this content is, after [`Self::slice`].
+    pub fn range(&self) -> Option<ContentRange> {
+        self.range
+    }
+
+    /// Cache directives of the content, see [`Self::with_cache_control`].
+    pub fn cache_control(&self) -> Option<ResourceCacheControl> {
+        self.cache_control
+    }
+
+    /// Stamps the content with the time it last changed. It goes out as
+    /// `annotations.lastModified`, so a client can tell whether its copy
+    /// is stale — and, later on, read conditionally.
+    pub fn with_last_modified(mut self, last_modified: DateTimeAsMicroseconds) -> Self {
+        self.last_modified = Some(last_modified.to_rfc3339());
+        self
+    }
+
+    /// Declares how long a client may cache the content, see
+    /// [`ResourceCacheControl`]. Overrides what the resource declares
+    /// with [`super::ResourceDefinition::get_cache_control`].
+    pub fn with_cache_control(mut self, cache_control: ResourceCacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Marks the content as bytes `range` of a larger whole, for a
+    /// handler that holds only part of it; [`Self::slice`] then cuts
+    /// within that part.
+    pub fn with_range(mut self, range: ContentRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Cuts bytes `start..end` (to the end when `end` is `None`) out of
+    /// a blob — of its decoded bytes — or a text, and records the range
+    /// the content now holds. An `end` past the end is clamped; a
+    /// `start` past it, or a cut inside a UTF-8 character, is an error.
+    /// A content marked with [`Self::with_range`] is cut by the offsets
+    /// of the whole, and a `start` before the part it holds is clamped
+    /// to where that part begins. Listings, errors and redirects are
+    /// left as they are.
+    pub fn slice(mut self, start: u64, end: Option<u64>) -> Result<Self, String> {
+        let bytes = match &self.body {
+            ResourceBody::Blob(blob) => base64::engine::general_purpose::STANDARD
+                .decode(blob)
+                .map_err(|err| format!("{} is not valid base64. Msg: {}", self.uri, err))?,
+            ResourceBody::Text(text) => text.as_bytes().to_vec(),
+            _ => return Ok(self),
+        };
+
+        // Where the bytes held lie in the whole.
+        let (offset, total) = match self.range {
+            Some(range) => (range.start, range.total),
+            None => (0, bytes.len() as u64),
+        };
+        let held_end = offset + bytes.len() as u64;
+
+        let start = start.max(offset);
+        let end = end.unwrap_or(held_end).min(held_end);
+        if start > end {
+            return Err(format!(
+                "Range {}..{} is out of {} ({} bytes)",
+                start, end, self.uri, total
+            ));
+        }
+
+        let part = &bytes[(start - offset) as usize..(end - offset) as usize];
+        if let ResourceBody::Blob(_) = &self.body {
+            self.body = ResourceBody::Blob(base64::engine::general_purpose::STANDARD.encode(part));
+        } else {
+            let text = std::str::from_utf8(part).map_err(|_| {
+                format!(
+                    "Range {}..{} splits a character of {}",
+                    start, end, self.uri
+                )
+            })?;
+            self.body = ResourceBody::Text(text.to_string());
+        }
+
+        self.range = Some(ContentRange { start, end, total });
+        Ok(self)
+    }
+
+    /// `mimeType` as it goes on the wire: an empty one falls back to
+    /// [`DEFAULT_BLOB_MIME_TYPE`] for blobs and [`DEFAULT_TEXT_MIME_TYPE`]
+    /// for everything else.
+    pub fn effective_mime_type(&self) -> &str {
+        if !self.mime_type.is_empty() {
+            return self.mime_type.as_str();
+        }
+
+        if let ResourceBody::Blob(_) = &self.body {
+            DEFAULT_BLOB_MIME_TYPE
+        } else {
+            DEFAULT_TEXT_MIME_TYPE
+        }
+    }
+}
+
+/// `bytes` in `charset` as UTF-8 text. Knows UTF-8, US-ASCII and
+/// ISO-8859-1 (latin-1), under their common names and case-blind; `None`
+/// for any other charset and for bytes not valid in the one named.
+pub fn decode_text(bytes: &[u8], charset: &str) -> Option<String> {
+    match charset.trim().to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" => String::from_utf8(bytes.to_vec()).ok(),
+        "us-ascii" | "ascii" => {
+            if bytes.is_ascii() {
+                Some(bytes.iter().map(|b| *b as char).collect())
+            } else {
+                None
+            }
+        }
+        // Every latin-1 byte is the code point of the same number.
+        "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1" => {
+            Some(bytes.iter().map(|b| *b as char).collect())
+        }
+        _ => None,
+    }
+}
+
+/// `mimeType` of text content (and of `resources/list` entries) whose
+/// handler left it empty.
+pub const DEFAULT_TEXT_MIME_TYPE: &str = "text/plain";
+
+/// `mimeType` of blob content whose handler left it empty.
+pub const DEFAULT_BLOB_MIME_TYPE: &str = "application/octet-stream";
+
+/// MIME type of a [`ResourceContent::redirect`] that is not followed.
+pub const REDIRECT_MIME_TYPE: &str = "text/uri-list";
+
+/// MIME type of a [`ResourceContent::listing`].
+pub const DIRECTORY_MIME_TYPE: &str = "inode/directory";
+
+/// One child of a directory-like resource.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ResourceListingEntry {
+    pub uri: String,
+    pub name: String,
+    #[serde(rename = "mimeType", default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
 }
 
-/// Trait that must be implemented by resource services to handle resource reading
+/// Trait that must be implemented by resource services to handle resource reading.
+/// `uri` is the URI being read, so one service can back several resources.
 #[async_trait::async_trait]
 pub trait McpResourceService {
-    async fn read_resource(&self) -> Result<ResourceReadResult, String>;
+    async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String>;
 }
 
 /// Abstract trait for resource services (similar to McpPromptAbstract for prompts)
 #[async_trait::async_trait]
 pub trait McpResourceAbstract {
-    async fn read(&self) -> Result<ResourceReadResult, String>;
+    /// Reads the resource at `uri` — the URI from the `resources/read`
+    /// request, handed through to [`McpResourceService::read_resource`].
+    async fn read(&self, uri: &str) -> Result<ResourceReadResult, String>;
 
     fn get_resource_uri(&self) -> &str;
     fn get_resource_name(&self) -> &str;
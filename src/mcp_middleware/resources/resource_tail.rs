@@ -0,0 +1,141 @@
+use std::sync::{Arc, Weak};
+
+use my_http_server::async_trait;
+
+use crate::mcp_middleware::{
+    ContentRange, McpMiddleware, McpResourceService, ResourceContent, ResourceReadResult,
+};
+
+/// How much of a tailed resource is kept: the last this many bytes.
+/// Older bytes are dropped as new ones come in.
+pub const TAIL_WINDOW_BYTES: usize = 1024 * 1024;
+
+/// A resource that is never complete — a log being tailed, a feed —
+/// produced as a stream of text chunks instead of in one read. See
+/// `McpMiddleware::register_tailing_resource`.
+#[async_trait::async_trait]
+pub trait McpResourceTail {
+    /// Starts producing `uri`: the chunks go down the returned channel
+    /// in order, the first of them being what a reader sees right
+    /// away. Called once, on the first read; an `Err` fails that read,
+    /// and the next one calls again. Dropping the sender ends the tail:
+    /// the resource keeps what it has.
+    async fn open_tail(&self, uri: &str) -> Result<tokio::sync::mpsc::Receiver<String>, String>;
+}
+
+/// The last [`TAIL_WINDOW_BYTES`] of a tail, and how many bytes came
+/// before them.
+#[derive(Default)]
+struct TailWindow {
+    text: String,
+    dropped: u64,
+}
+
+impl TailWindow {
+    fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        if self.text.len() <= TAIL_WINDOW_BYTES {
+            return;
+        }
+
+        let mut cut = self.text.len() - TAIL_WINDOW_BYTES;
+        while !self.text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.text.drain(..cut);
+        self.dropped += cut as u64;
+    }
+
+    /// The window as a content. Once bytes were dropped it is marked as
+    /// the range it is of the whole, so offsets stay those of the tail.
+    fn to_content(&self, uri: &str, mime_type: &str) -> ResourceContent {
+        let content = ResourceContent::text(uri, mime_type, self.text.clone());
+        if self.dropped == 0 {
+            return content;
+        }
+
+        let total = self.dropped + self.text.len() as u64;
+        content.with_range(ContentRange {
+            start: self.dropped,
+            end: total,
+            total,
+        })
+    }
+}
+
+/// [`McpResourceTail`] as a resource: holds the last
+/// [`TAIL_WINDOW_BYTES`] received, and has the middleware announce each
+/// new chunk. The chunks are collected by a task that ends with the
+/// resource — when it is unregistered, or the middleware reset.
+pub(crate) struct TailedResource {
+    tail: Arc<dyn McpResourceTail + Send + Sync + 'static>,
+    mime_type: String,
+    window: Arc<parking_lot::Mutex<TailWindow>>,
+    opened: tokio::sync::OnceCell<()>,
+    middleware: Weak<McpMiddleware>,
+    collector: parking_lot::Mutex<Option<tokio::task::AbortHandle>>,
+}
+
+impl TailedResource {
+    pub fn new(
+        tail: Arc<dyn McpResourceTail + Send + Sync + 'static>,
+        mime_type: String,
+        middleware: Weak<McpMiddleware>,
+    ) -> Self {
+        Self {
+            tail,
+            mime_type,
+            window: Arc::new(parking_lot::Mutex::new(TailWindow::default())),
+            opened: tokio::sync::OnceCell::new(),
+            middleware,
+            collector: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Opens the tail and waits for its first chunk; the rest is
+    /// collected in the background.
+    async fn open(&self, uri: &str) -> Result<(), String> {
+        let mut chunks = self.tail.open_tail(uri).await?;
+
+        if let Some(first) = chunks.recv().await {
+            self.window.lock().push(first.as_str());
+        }
+
+        let uri = uri.to_string();
+        let window = self.window.clone();
+        let middleware = self.middleware.clone();
+
+        let collector = tokio::spawn(async move {
+            while let Some(chunk) = chunks.recv().await {
+                window.lock().push(chunk.as_str());
+
+                let Some(middleware) = middleware.upgrade() else {
+                    return;
+                };
+                middleware.notify_resource_updated(uri.as_str()).await;
+            }
+        });
+        *self.collector.lock() = Some(collector.abort_handle());
+
+        Ok(())
+    }
+}
+
+impl Drop for TailedResource {
+    fn drop(&mut self) {
+        if let Some(collector) = self.collector.lock().take() {
+            collector.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl McpResourceService for TailedResource {
+    async fn read_resource(&self, uri: &str) -> Result<ResourceReadResult, String> {
+        self.opened.get_or_try_init(|| self.open(uri)).await?;
+
+        Ok(ResourceReadResult {
+            contents: vec![self.window.lock().to_content(uri, self.mime_type.as_str())],
+        })
+    }
+}
@@ -2,7 +2,8 @@ use super::*;
 use std::ops::Bound;
 use std::sync::Arc;
 
-const PAGE_SIZE: usize = 100;
+/// How many resources one `resources/list` page holds by default.
+pub const LIST_PAGE_SIZE: usize = 100;
 
 pub struct ResourceSchemaData {
     pub resource: Arc<dyn McpResourceAbstract + Send + Sync + 'static>,
@@ -27,15 +28,18 @@ impl McpResources {
 
     pub async fn read(&self, uri: &str) -> Result<ResourceReadResult, String> {
         if let Some(executor) = self.resources.get(uri) {
-            return executor.read().await;
+            return executor.read(uri).await;
         }
 
         Err(format!("Resource with URI {} is not found", uri))
     }
 
+    /// One page of at most `page_size` resources, starting after
+    /// `cursor`, and the cursor of the next page.
     pub fn get_list(
         &self,
         cursor: Option<&str>,
+        page_size: usize,
     ) -> (Vec<ResourceSchemaData>, Option<String>) {
         let lower = match cursor {
             Some(c) => Bound::Excluded(c.to_string()),
@@ -44,10 +48,10 @@ impl McpResources {
 
         let mut iter = self.resources.range((lower, Bound::Unbounded));
 
-        let mut result = Vec::with_capacity(PAGE_SIZE);
+        let mut result = Vec::with_capacity(page_size);
         let mut last_uri: Option<String> = None;
 
-        for _ in 0..PAGE_SIZE {
+        for _ in 0..page_size {
             match iter.next() {
                 Some((uri, resource)) => {
                     last_uri = Some(uri.clone());
@@ -73,6 +77,19 @@ impl McpResources {
     pub fn has_resources(&self) -> bool {
         !self.resources.is_empty()
     }
+
+    pub fn count(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Whether a resource with that URI was registered.
+    pub fn remove(&mut self, uri: &str) -> bool {
+        self.resources.remove(uri).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.resources.clear();
+    }
 }
 
 impl Default for McpResources {
@@ -80,3 +97,91 @@ impl Default for McpResources {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use my_http_server::async_trait;
+
+    /// Implements the abstract trait by hand, the way a host with its
+    /// own registry would — pins the `read(&self, uri)` signature.
+    struct EchoUriResource;
+
+    #[async_trait::async_trait]
+    impl McpResourceAbstract for EchoUriResource {
+        async fn read(&self, uri: &str) -> Result<ResourceReadResult, String> {
+            Ok(ResourceReadResult {
+                contents: vec![ResourceContent::text(uri, "text/plain", uri)],
+            })
+        }
+
+        fn get_resource_uri(&self) -> &str {
+            "echo://self"
+        }
+
+        fn get_resource_name(&self) -> &str {
+            "echo"
+        }
+
+        fn get_description(&self) -> &str {
+            "Echoes the URI it was read by"
+        }
+
+        fn get_mime_type(&self) -> &str {
+            "text/plain"
+        }
+    }
+
+    #[tokio::test]
+    async fn manager_hands_the_uri_to_the_resource() {
+        let mut resources = McpResources::new();
+        resources.add(Arc::new(EchoUriResource));
+
+        let result = resources.read("echo://self").await.unwrap();
+        assert_eq!(result.contents[0].as_text(), Some("echo://self"));
+
+        assert!(resources.read("echo://other").await.is_err());
+    }
+
+    #[test]
+    fn list_pages_hold_at_most_the_page_size() {
+        struct Named(String);
+
+        #[async_trait::async_trait]
+        impl McpResourceAbstract for Named {
+            async fn read(&self, _uri: &str) -> Result<ResourceReadResult, String> {
+                Err("not readable".to_string())
+            }
+
+            fn get_resource_uri(&self) -> &str {
+                self.0.as_str()
+            }
+
+            fn get_resource_name(&self) -> &str {
+                self.0.as_str()
+            }
+
+            fn get_description(&self) -> &str {
+                ""
+            }
+
+            fn get_mime_type(&self) -> &str {
+                "text/plain"
+            }
+        }
+
+        let mut resources = McpResources::new();
+        for i in 0..5 {
+            resources.add(Arc::new(Named(format!("notes://{}", i))));
+        }
+
+        let (page, next_cursor) = resources.get_list(None, 2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(next_cursor.as_deref(), Some("notes://1"));
+
+        let (page, next_cursor) = resources.get_list(Some("notes://3"), 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].resource.get_resource_uri(), "notes://4");
+        assert!(next_cursor.is_none());
+    }
+}
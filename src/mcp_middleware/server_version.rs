@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A semantic version (`MAJOR.MINOR.PATCH[-PRE][+BUILD]`, per semver
+/// 2.0.0) for `serverInfo.version`. Only valid versions can be built, so
+/// [`crate::McpMiddleware::with_version`] never sends one a strict client
+/// would reject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerVersion {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre_release: Option<String>,
+    build: Option<String>,
+}
+
+impl ServerVersion {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre_release: None,
+            build: None,
+        }
+    }
+
+    pub fn parse(version: &str) -> Result<Self, String> {
+        let invalid = |why: &str| format!("{} is not a semantic version: {}", version, why);
+
+        let (rest, build) = match version.split_once('+') {
+            Some((rest, build)) => (rest, Some(build)),
+            None => (version, None),
+        };
+
+        let (core, pre_release) = match rest.split_once('-') {
+            Some((core, pre_release)) => (core, Some(pre_release)),
+            None => (rest, None),
+        };
+
+        let numbers: Vec<&str> = core.split('.').collect();
+        let &[major, minor, patch] = numbers.as_slice() else {
+            return Err(invalid("expected MAJOR.MINOR.PATCH"));
+        };
+
+        let number = |part: &str| -> Result<u64, String> {
+            if !is_numeric_identifier(part) {
+                return Err(invalid("versions are numbers without leading zeros"));
+            }
+            part.parse::<u64>().map_err(|_| invalid("version number too large"))
+        };
+
+        if let Some(pre_release) = pre_release {
+            let valid = pre_release.split('.').all(|identifier| {
+                is_identifier(identifier)
+                    && (!identifier.bytes().all(|b| b.is_ascii_digit())
+                        || is_numeric_identifier(identifier))
+            });
+            if !valid {
+                return Err(invalid("malformed pre-release"));
+            }
+        }
+
+        if let Some(build) = build {
+            if !build.split('.').all(is_identifier) {
+                return Err(invalid("malformed build metadata"));
+            }
+        }
+
+        Ok(Self {
+            major: number(major)?,
+            minor: number(minor)?,
+            patch: number(patch)?,
+            pre_release: pre_release.map(str::to_string),
+            build: build.map(str::to_string),
+        })
+    }
+
+    pub fn major(&self) -> u64 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.minor
+    }
+
+    pub fn patch(&self) -> u64 {
+        self.patch
+    }
+
+    pub fn pre_release(&self) -> Option<&str> {
+        self.pre_release.as_deref()
+    }
+
+    pub fn build(&self) -> Option<&str> {
+        self.build.as_deref()
+    }
+}
+
+/// `[0-9A-Za-z-]+`.
+fn is_identifier(identifier: &str) -> bool {
+    !identifier.is_empty()
+        && identifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Digits only, no leading zero unless it is `0` itself.
+fn is_numeric_identifier(identifier: &str) -> bool {
+    !identifier.is_empty()
+        && identifier.bytes().all(|b| b.is_ascii_digit())
+        && (identifier == "0" || !identifier.starts_with('0'))
+}
+
+impl FromStr for ServerVersion {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        Self::parse(version)
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(pre_release) = &self.pre_release {
+            write!(f, "-{}", pre_release)?;
+        }
+
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The warning logged for a configured `serverInfo.version` that is not
+/// semver; `None` for one that is.
+pub(crate) fn non_semver_version_warning(version: &str) -> Option<String> {
+    let err = ServerVersion::parse(version).err()?;
+    Some(format!(
+        "MCP server version {:?} is served as is, but some clients expect semver. {}",
+        version, err
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_semver() {
+        for version in [
+            "0.0.1",
+            "1.2.3",
+            "10.20.30",
+            "1.0.0-alpha.1",
+            "1.0.0-rc.1+build.5",
+            "2.0.0+sha-abc",
+        ] {
+            let parsed = ServerVersion::parse(version).unwrap();
+            assert_eq!(parsed.to_string(), version);
+        }
+
+        let parsed = ServerVersion::parse("1.0.0-beta.2+exp.sha.5114f85").unwrap();
+        assert_eq!((parsed.major(), parsed.minor(), parsed.patch()), (1, 0, 0));
+        assert_eq!(parsed.pre_release(), Some("beta.2"));
+        assert_eq!(parsed.build(), Some("exp.sha.5114f85"));
+    }
+
+    #[test]
+    fn rejects_what_is_not_semver() {
+        for version in [
+            "", "1", "1.2", "v1.2.3", "1.2.3.4", "01.2.3", "1.2.3-", "1.2.3-01", "1.2.3+", "1.2.x",
+            "latest",
+        ] {
+            assert!(ServerVersion::parse(version).is_err(), "{}", version);
+            assert!(non_semver_version_warning(version).is_some(), "{}", version);
+        }
+
+        assert!(non_semver_version_warning("1.2.3").is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::mcp_middleware::McpSocketUpdateEvent;
+
+/// What happens to a notification for a session whose stream buffer is
+/// full — a client that reads its `GET` stream slower than the server
+/// writes to it. See `McpMiddleware::with_session_buffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowClientPolicy {
+    /// The sender waits until the client makes room. Nothing is lost,
+    /// but a broadcast waits for the slowest session.
+    Block,
+    /// The oldest buffered notification is dropped to make room for the
+    /// new one. The client misses notifications, never the latest.
+    /// Server→client requests (`elicitation/create`) are never dropped:
+    /// a tool is waiting on their answer. When only requests are
+    /// buffered, a new notification is dropped instead, and a new
+    /// request is kept past the capacity.
+    DropOldest,
+    /// The session loses its stream: the client reads what is already
+    /// buffered, then the stream ends and it has to open a new one.
+    Disconnect,
+}
+
+/// How many notifications a session's stream holds before
+/// [`SlowClientPolicy`] kicks in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SessionBuffer {
+    pub capacity: usize,
+    pub policy: SlowClientPolicy,
+}
+
+impl Default for SessionBuffer {
+    fn default() -> Self {
+        Self {
+            capacity: 32,
+            policy: SlowClientPolicy::Block,
+        }
+    }
+}
+
+impl SessionBuffer {
+    /// The channel of one stream. Under [`SlowClientPolicy::DropOldest`]
+    /// a relay task sits between the two ends and keeps the buffer, so
+    /// the sender side never fills up; it must be called inside the
+    /// tokio runtime then.
+    pub fn open_channel(&self) -> (Sender<McpSocketUpdateEvent>, Receiver<McpSocketUpdateEvent>) {
+        let capacity = self.capacity.max(1);
+
+        if self.policy != SlowClientPolicy::DropOldest {
+            return tokio::sync::mpsc::channel(capacity);
+        }
+
+        let (sender, inbound) = tokio::sync::mpsc::channel(capacity);
+        let (outbound, receiver) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(relay_dropping_oldest(inbound, outbound, capacity));
+        (sender, receiver)
+    }
+}
+
+async fn relay_dropping_oldest(
+    mut inbound: Receiver<McpSocketUpdateEvent>,
+    outbound: Sender<McpSocketUpdateEvent>,
+    capacity: usize,
+) {
+    let mut buffered: VecDeque<McpSocketUpdateEvent> = VecDeque::with_capacity(capacity);
+
+    loop {
+        let Some(next) = buffered.pop_front() else {
+            match inbound.recv().await {
+                Some(event) => buffered.push_back(event),
+                None => return,
+            }
+            continue;
+        };
+
+        tokio::select! {
+            event = inbound.recv() => {
+                buffered.push_front(next);
+                let Some(event) = event else {
+                    break;
+                };
+                push_dropping_oldest(&mut buffered, event, capacity);
+            }
+            permit = outbound.reserve() => {
+                let Ok(permit) = permit else {
+                    return;
+                };
+                permit.send(next);
+            }
+        }
+    }
+
+    // The session let go of the stream — shut down or replaced. What is
+    // buffered still goes out: the closing notification is among it.
+    for event in buffered {
+        if outbound.send(event).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Buffers `event`, making room by dropping the oldest notification if
+/// the buffer is full. Requests are never dropped.
+fn push_dropping_oldest(
+    buffered: &mut VecDeque<McpSocketUpdateEvent>,
+    event: McpSocketUpdateEvent,
+    capacity: usize,
+) {
+    if buffered.len() < capacity {
+        buffered.push_back(event);
+        return;
+    }
+
+    match buffered.iter().position(|e| !e.is_request()) {
+        Some(oldest) => {
+            buffered.remove(oldest);
+            eprintln!("MCP session stream buffer is full. Dropped the oldest notification");
+        }
+        None if !event.is_request() => {
+            eprintln!("MCP session stream buffer is full of requests. Dropped a notification");
+            return;
+        }
+        None => {}
+    }
+    buffered.push_back(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn updated(n: usize) -> McpSocketUpdateEvent {
+        McpSocketUpdateEvent::ResourceUpdated {
+            uri: format!("notes://{}", n),
+            title: None,
+        }
+    }
+
+    fn elicitation(id: i64) -> McpSocketUpdateEvent {
+        McpSocketUpdateEvent::ElicitationRequest {
+            id,
+            message: "Confirm?".to_string(),
+            requested_schema: "{}".to_string(),
+        }
+    }
+
+    fn describe(buffered: &VecDeque<McpSocketUpdateEvent>) -> Vec<String> {
+        buffered
+            .iter()
+            .map(|event| match event {
+                McpSocketUpdateEvent::ResourceUpdated { uri, .. } => uri.clone(),
+                McpSocketUpdateEvent::ElicitationRequest { id, .. } => format!("request {}", id),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_full_buffer_drops_the_oldest_notification_not_a_request() {
+        let mut buffered = VecDeque::new();
+        push_dropping_oldest(&mut buffered, elicitation(-1), 2);
+        push_dropping_oldest(&mut buffered, updated(1), 2);
+        push_dropping_oldest(&mut buffered, updated(2), 2);
+
+        assert_eq!(describe(&buffered), vec!["request -1", "notes://2"]);
+    }
+
+    #[test]
+    fn a_buffer_full_of_requests_keeps_them_all() {
+        let mut buffered = VecDeque::new();
+        push_dropping_oldest(&mut buffered, elicitation(-1), 2);
+        push_dropping_oldest(&mut buffered, elicitation(-2), 2);
+
+        push_dropping_oldest(&mut buffered, updated(1), 2);
+        assert_eq!(describe(&buffered), vec!["request -1", "request -2"]);
+
+        push_dropping_oldest(&mut buffered, elicitation(-3), 2);
+        assert_eq!(
+            describe(&buffered),
+            vec!["request -1", "request -2", "request -3"]
+        );
+    }
+}
@@ -6,7 +6,10 @@ use my_http_server::HttpContext;
 use parking_lot::Mutex;
 use rust_extensions::date_time::{AtomicDateTimeAsMicroseconds, DateTimeAsMicroseconds};
 
-use crate::mcp_middleware::{McpConnectionInfo, McpSocketUpdateEvent};
+use crate::mcp_middleware::{
+    InFlightRequest, McpClock, McpConnectionInfo, McpSocketUpdateEvent, PendingResponse, RequestId,
+    ServerCapabilities, SessionBuffer, SlowClientPolicy,
+};
 
 /// How often the background GC sweeps idle sessions.
 pub(crate) const GC_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
@@ -34,6 +37,11 @@ pub struct McpSession {
     /// it is a snapshot frozen at clone time, not a window into the
     /// live entry.
     pub last_access: AtomicDateTimeAsMicroseconds,
+    /// The client confirmed the handshake with
+    /// `notifications/initialized`. Sessions adopted by lazy session
+    /// creation start out initialized — their client is long past the
+    /// handshake.
+    pub initialized: bool,
 }
 
 /// `AtomicDateTimeAsMicroseconds` is not `Clone`, and cloning it as a
@@ -49,6 +57,7 @@ impl Clone for McpSession {
             last_access: AtomicDateTimeAsMicroseconds::new(
                 self.last_access.get_unix_microseconds(),
             ),
+            initialized: self.initialized,
         }
     }
 }
@@ -66,10 +75,19 @@ impl McpSession {
             create: now,
             supports_elicitation,
             last_access: AtomicDateTimeAsMicroseconds::new(now.unix_microseconds),
+            initialized: false,
         }
     }
 }
 
+/// What `initialize` negotiated for a session, read in one piece. See
+/// [`McpSessions::session_capabilities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCapabilities {
+    pub protocol_version: String,
+    pub supports_elicitation: bool,
+}
+
 /// What the sessions map actually stores.
 struct SessionEntry {
     session: McpSession,
@@ -78,6 +96,15 @@ struct SessionEntry {
     /// `resources/subscribe`. `notify_resource_updated` fans
     /// `notifications/resources/updated` out to exactly these sessions.
     subscriptions: HashSet<String>,
+    /// Released when the latest ordered response of this session is
+    /// written. See [`McpSessions::take_response_turn`].
+    last_response: Option<tokio::sync::oneshot::Receiver<()>>,
+    /// Ids of the client requests being served right now. See
+    /// [`McpSessions::begin_request`].
+    in_flight: HashSet<RequestId>,
+    /// What `initialize` advertised to the session. `None` for a
+    /// session created lazily, without one.
+    advertised: Option<ServerCapabilities>,
 }
 
 impl SessionEntry {
@@ -86,6 +113,9 @@ impl SessionEntry {
             session,
             sender: None,
             subscriptions: HashSet::new(),
+            last_response: None,
+            in_flight: HashSet::new(),
+            advertised: None,
         }
     }
 }
@@ -106,6 +136,8 @@ pub struct McpSessions {
     /// start-up, before the server serves anything, so reading it is a
     /// plain atomic load — a host that registers nothing pays nothing.
     connection_info: OnceLock<Arc<dyn McpConnectionInfo + Send + Sync + 'static>>,
+    /// Size and overflow policy of every stream opened from now on.
+    buffer: Mutex<SessionBuffer>,
 }
 
 impl McpSessions {
@@ -113,6 +145,51 @@ impl McpSessions {
         Self {
             data: Mutex::new(HashMap::new()),
             connection_info: OnceLock::new(),
+            buffer: Mutex::new(SessionBuffer::default()),
+        }
+    }
+
+    pub(crate) fn set_buffer(&self, capacity: usize, policy: SlowClientPolicy) {
+        *self.buffer.lock() = SessionBuffer { capacity, policy };
+    }
+
+    /// Puts `event` on the stream `sender` of session `session_id` the
+    /// way the buffer policy says. `Err` when the event did not go out:
+    /// the stream is closed, or it was full and the policy gave up on it.
+    pub(crate) async fn deliver(
+        &self,
+        session_id: &str,
+        sender: &tokio::sync::mpsc::Sender<McpSocketUpdateEvent>,
+        event: McpSocketUpdateEvent,
+    ) -> Result<(), ()> {
+        let policy = self.buffer.lock().policy;
+
+        if policy != SlowClientPolicy::Disconnect {
+            return sender.send(event).await.map_err(|_| ());
+        }
+
+        match sender.try_send(event) {
+            Ok(()) => Ok(()),
+            Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                eprintln!(
+                    "MCP session {} reads its stream too slowly. Disconnecting it",
+                    session_id
+                );
+                let mut write_access = self.data.lock();
+                if let Some(entry) = write_access.get_mut(session_id) {
+                    // A newer stream of the session is not the slow one.
+                    if entry
+                        .sender
+                        .as_ref()
+                        .is_some_and(|s| s.same_channel(sender))
+                    {
+                        entry.sender = None;
+                        entry.subscriptions.clear();
+                    }
+                }
+                Err(())
+            }
+            Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => Err(()),
         }
     }
 
@@ -189,13 +266,69 @@ impl McpSessions {
             return None;
         }
 
-        let session = McpSession::new(session_id.to_string(), version, now, supports_elicitation);
+        let mut session =
+            McpSession::new(session_id.to_string(), version, now, supports_elicitation);
+        session.initialized = true;
 
         write_access.insert(session.id.clone(), SessionEntry::new(session.clone()));
 
         Some(session)
     }
 
+    /// Records `notifications/initialized`. Returns false when there is
+    /// nothing to record — the session is unknown or already initialized
+    /// (a duplicate notification is a no-op).
+    pub fn mark_initialized(&self, session_id: &str) -> bool {
+        let mut write_access = self.data.lock();
+        match write_access.get_mut(session_id) {
+            Some(entry) if !entry.session.initialized => {
+                entry.session.initialized = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The protocol version negotiated at `initialize`; `None` for an
+    /// unknown session.
+    pub fn session_protocol_version(&self, session_id: &str) -> Option<String> {
+        let access = self.data.lock();
+        access
+            .get(session_id)
+            .map(|entry| entry.session.version.clone())
+    }
+
+    /// Everything `initialize` negotiated for the session, taken under
+    /// a single lock: a handler never pairs the protocol version of one
+    /// `initialize` with the client capabilities of another. Negotiated
+    /// state is never changed in place — a re-initialize mints a new
+    /// session — so the snapshot stays true for the session's lifetime.
+    /// `None` for an unknown session.
+    pub fn session_capabilities(&self, session_id: &str) -> Option<SessionCapabilities> {
+        let access = self.data.lock();
+        access.get(session_id).map(|entry| SessionCapabilities {
+            protocol_version: entry.session.version.clone(),
+            supports_elicitation: entry.session.supports_elicitation,
+        })
+    }
+
+    /// Records what `initialize` advertised to the session, for
+    /// [`Self::advertised_capabilities`].
+    pub fn set_advertised_capabilities(&self, session_id: &str, capabilities: ServerCapabilities) {
+        if let Some(entry) = self.data.lock().get_mut(session_id) {
+            entry.advertised = Some(capabilities);
+        }
+    }
+
+    /// What `initialize` advertised to the session; `None` for an
+    /// unknown session and one created without `initialize`.
+    pub fn advertised_capabilities(&self, session_id: &str) -> Option<ServerCapabilities> {
+        let access = self.data.lock();
+        access
+            .get(session_id)
+            .and_then(|entry| entry.advertised.clone())
+    }
+
     pub fn session_supports_elicitation(&self, session_id: &str) -> bool {
         let access = self.data.lock();
         access
@@ -215,6 +348,23 @@ impl McpSessions {
         access.get(session_id).and_then(|s| s.sender.clone())
     }
 
+    /// Puts `event` on the stream of `session_id`, the way the buffer
+    /// policy says. `Err` when the session is unknown, has no live
+    /// stream, or the event did not make it onto it.
+    pub async fn send_to_session(
+        &self,
+        session_id: &str,
+        event: McpSocketUpdateEvent,
+    ) -> Result<(), String> {
+        let Some(sender) = self.get_sender(session_id) else {
+            return Err(format!("MCP session {} has no live stream", session_id));
+        };
+
+        self.deliver(session_id, &sender, event)
+            .await
+            .map_err(|_| format!("MCP session {} stream is closed", session_id))
+    }
+
     pub fn subscribe_to_notifications(
         &self,
         session_id: &str,
@@ -223,7 +373,7 @@ impl McpSessions {
         let mut write_access = self.data.lock();
         let session = write_access.get_mut(session_id)?;
         session.session.last_access.update(now);
-        let (sender, receiver) = tokio::sync::mpsc::channel(32);
+        let (sender, receiver) = self.buffer.lock().open_channel();
         session.sender = Some(sender);
         Some(receiver)
     }
@@ -287,10 +437,62 @@ impl McpSessions {
         true
     }
 
+    /// Queues a response behind the previous one of the same session
+    /// (ordered mode). Unknown sessions are not ordered.
+    pub(crate) fn take_response_turn(&self, session_id: &str) -> PendingResponse {
+        let (done, released) = tokio::sync::oneshot::channel();
+
+        let mut write_access = self.data.lock();
+        let Some(entry) = write_access.get_mut(session_id) else {
+            return PendingResponse::unordered();
+        };
+
+        let predecessor = entry.last_response.replace(released);
+        PendingResponse::ordered(predecessor, done)
+    }
+
+    /// Marks `id` as in flight in the session. `None` when a request
+    /// with the same id is still being served — a second response under
+    /// that id would be impossible for the client to correlate. The id
+    /// is free again once the returned guard is dropped.
+    pub(crate) fn begin_request(
+        self: &Arc<Self>,
+        session_id: &str,
+        id: &RequestId,
+    ) -> Option<InFlightRequest> {
+        {
+            let mut write_access = self.data.lock();
+            if let Some(entry) = write_access.get_mut(session_id) {
+                if !entry.in_flight.insert(id.clone()) {
+                    return None;
+                }
+            }
+        }
+
+        Some(InFlightRequest::new(
+            self.clone(),
+            session_id.to_string(),
+            id.clone(),
+        ))
+    }
+
+    pub(crate) fn end_request(&self, session_id: &str, id: &RequestId) {
+        let mut write_access = self.data.lock();
+        if let Some(entry) = write_access.get_mut(session_id) {
+            entry.in_flight.remove(id);
+        }
+    }
+
+    /// Called when the session's SSE stream is gone. Its subscriptions
+    /// go with it, under the same lock: nothing could deliver their
+    /// updates anyway, and a client that opens a new stream subscribes
+    /// again. DELETE and idle expiry remove the whole entry, and with
+    /// it the subscriptions.
     pub fn clear_sender(&self, session_id: &str) {
         let mut write_access = self.data.lock();
         if let Some(session) = write_access.get_mut(session_id) {
             session.sender = None;
+            session.subscriptions.clear();
         }
     }
 
@@ -313,24 +515,37 @@ impl McpSessions {
         }
     }
 
+    /// Ids of the sessions subscribed to `uri`, sorted.
+    pub fn get_subscribers(&self, uri: &str) -> Vec<String> {
+        let read_access = self.data.lock();
+        let mut result: Vec<String> = read_access
+            .iter()
+            .filter(|(_, entry)| entry.subscriptions.contains(uri))
+            .map(|(id, _)| id.clone())
+            .collect();
+        result.sort();
+        result
+    }
+
     /// Sends `notifications/resources/updated` to every session that
-    /// subscribed to `uri` and has a live SSE channel.
-    pub async fn notify_resource_updated(&self, uri: &str) {
+    /// subscribed to `uri` and has a live SSE channel, with `title` in
+    /// its params when given.
+    pub async fn notify_resource_updated(&self, uri: &str, title: Option<&str>) {
         let senders: Vec<_> = {
             let read_access = self.data.lock();
             read_access
                 .values()
                 .filter(|s| s.subscriptions.contains(uri))
-                .filter_map(|s| s.sender.clone())
+                .filter_map(|s| Some((s.session.id.clone(), s.sender.clone()?)))
                 .collect()
         };
 
-        for sender in senders {
-            let _ = sender
-                .send(McpSocketUpdateEvent::ResourceUpdated {
-                    uri: uri.to_string(),
-                })
-                .await;
+        for (session_id, sender) in senders {
+            let event = McpSocketUpdateEvent::ResourceUpdated {
+                uri: uri.to_string(),
+                title: title.map(|title| title.to_string()),
+            };
+            let _ = self.deliver(session_id.as_str(), &sender, event).await;
         }
     }
 
@@ -339,13 +554,79 @@ impl McpSessions {
             let read_access = self.data.lock();
             read_access
                 .values()
-                .filter_map(|s| s.sender.clone())
+                .filter_map(|s| Some((s.session.id.clone(), s.sender.clone()?)))
+                .collect()
+        };
+
+        for (session_id, sender) in senders {
+            let _ = self
+                .deliver(session_id.as_str(), &sender, event.clone())
+                .await;
+        }
+    }
+
+    /// Sends `events` to every live stream as one
+    /// [`McpSocketUpdateEvent::Batch`]. `ResourceUpdated` entries are
+    /// kept only for sessions subscribed to their URI; a session left
+    /// with nothing gets nothing.
+    pub async fn broadcast_batch(&self, events: Vec<McpSocketUpdateEvent>) {
+        let batches: Vec<_> = {
+            let read_access = self.data.lock();
+            read_access
+                .values()
+                .filter_map(|entry| {
+                    let sender = entry.sender.clone()?;
+                    let batch: Vec<_> = events
+                        .iter()
+                        .filter(|event| match event {
+                            McpSocketUpdateEvent::ResourceUpdated { uri, .. } => {
+                                entry.subscriptions.contains(uri)
+                            }
+                            _ => true,
+                        })
+                        .cloned()
+                        .collect();
+                    if batch.is_empty() {
+                        return None;
+                    }
+                    Some((entry.session.id.clone(), sender, batch))
+                })
                 .collect()
         };
 
+        for (session_id, sender, batch) in batches {
+            let event = McpSocketUpdateEvent::Batch(batch);
+            let _ = self.deliver(session_id.as_str(), &sender, event).await;
+        }
+    }
+
+    /// Graceful-shutdown fan-out: every session with a live SSE channel
+    /// gets `notifications/shutdown` followed by the close of its
+    /// stream. The senders are taken out of the map first, so nothing
+    /// else can slip onto a stream after the notification. Sessions
+    /// themselves stay — a client that comes back to this very process
+    /// keeps its id. Returns how many streams were notified.
+    pub async fn shutdown(&self, reason: &str) -> usize {
+        let senders: Vec<_> = {
+            let mut write_access = self.data.lock();
+            write_access
+                .values_mut()
+                .filter_map(|entry| entry.sender.take())
+                .collect()
+        };
+
+        let result = senders.len();
+
         for sender in senders {
-            let _ = sender.send(event.clone()).await;
+            let _ = sender
+                .send(McpSocketUpdateEvent::ServerShutdown {
+                    reason: reason.to_string(),
+                })
+                .await;
+            let _ = sender.send(McpSocketUpdateEvent::Shutdown).await;
         }
+
+        result
     }
 
     /// Drops sessions that have no live SSE channel and have not been
@@ -400,7 +681,12 @@ impl McpSessions {
 
 /// Background sweeper for idle sessions. Holds a `Weak` so the task
 /// dies together with the middleware instead of keeping it alive.
-pub(crate) fn spawn_session_gc(sessions: Weak<McpSessions>, idle_timeout: Duration) {
+/// Idleness is measured on `clock`, the sweep cadence on tokio time.
+pub(crate) fn spawn_session_gc(
+    sessions: Weak<McpSessions>,
+    idle_timeout: Duration,
+    clock: Arc<dyn McpClock + Send + Sync>,
+) {
     tokio::spawn(async move {
         let mut sweep = tokio::time::interval(GC_SWEEP_INTERVAL);
         // interval()'s first tick fires immediately — skip it.
@@ -414,7 +700,7 @@ pub(crate) fn spawn_session_gc(sessions: Weak<McpSessions>, idle_timeout: Durati
             };
 
             sessions
-                .remove_idle_sessions(DateTimeAsMicroseconds::now(), idle_timeout)
+                .remove_idle_sessions(clock.now(), idle_timeout)
                 .await;
         }
     });
@@ -471,20 +757,96 @@ mod tests {
         assert!(sessions.subscribe(subscribed.id.as_str(), "res://a".to_string()));
         assert!(!sessions.subscribe("unknown-session", "res://a".to_string()));
 
-        sessions.notify_resource_updated("res://a").await;
+        sessions.notify_resource_updated("res://a", None).await;
 
         match subscribed_rx.try_recv() {
-            Ok(McpSocketUpdateEvent::ResourceUpdated { uri }) => assert_eq!(uri, "res://a"),
+            Ok(McpSocketUpdateEvent::ResourceUpdated { uri, .. }) => assert_eq!(uri, "res://a"),
             other => panic!("expected ResourceUpdated, got {:?}", other),
         }
         assert!(other_rx.try_recv().is_err());
 
         // After unsubscribe no more events arrive.
         sessions.unsubscribe(subscribed.id.as_str(), "res://a");
-        sessions.notify_resource_updated("res://a").await;
+        sessions.notify_resource_updated("res://a", None).await;
         assert!(subscribed_rx.try_recv().is_err());
     }
 
+    #[tokio::test]
+    async fn session_end_drops_its_subscriptions() {
+        let sessions = McpSessions::new();
+        let now = DateTimeAsMicroseconds::now();
+
+        let deleted = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let disconnected = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let expired = sessions.generate_session("2025-06-18".to_string(), now_minus(3600), false);
+
+        let mut deleted_rx = sessions
+            .subscribe_to_notifications(deleted.id.as_str(), now)
+            .unwrap();
+        let _disconnected_rx = sessions
+            .subscribe_to_notifications(disconnected.id.as_str(), now)
+            .unwrap();
+
+        for session in [&deleted, &disconnected, &expired] {
+            assert!(sessions.subscribe(session.id.as_str(), "res://a".to_string()));
+        }
+        assert_eq!(sessions.get_subscribers("res://a").len(), 3);
+
+        assert!(sessions.delete_session(deleted.id.as_str()).await);
+        sessions.clear_sender(disconnected.id.as_str());
+        assert_eq!(
+            sessions
+                .remove_idle_sessions(now, Duration::from_secs(60))
+                .await,
+            1
+        );
+
+        assert!(sessions.get_subscribers("res://a").is_empty());
+
+        // The deleted session's stream only ever sees its shutdown.
+        sessions.notify_resource_updated("res://a", None).await;
+        match deleted_rx.recv().await {
+            Some(McpSocketUpdateEvent::Shutdown) => {}
+            other => panic!("expected Shutdown, got {:?}", other),
+        }
+        assert!(deleted_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_notifies_every_live_stream_before_closing_it() {
+        let sessions = McpSessions::new();
+        let now = DateTimeAsMicroseconds::now();
+
+        let first = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let second = sessions.generate_session("2025-06-18".to_string(), now, false);
+        // No GET stream — nothing to notify.
+        sessions.generate_session("2025-06-18".to_string(), now, false);
+
+        let mut first_rx = sessions
+            .subscribe_to_notifications(first.id.as_str(), now)
+            .unwrap();
+        let mut second_rx = sessions
+            .subscribe_to_notifications(second.id.as_str(), now)
+            .unwrap();
+
+        assert_eq!(sessions.shutdown("maintenance").await, 2);
+
+        for rx in [&mut first_rx, &mut second_rx] {
+            match rx.try_recv() {
+                Ok(McpSocketUpdateEvent::ServerShutdown { reason }) => {
+                    assert_eq!(reason, "maintenance")
+                }
+                other => panic!("expected ServerShutdown, got {:?}", other),
+            }
+            assert!(matches!(rx.try_recv(), Ok(McpSocketUpdateEvent::Shutdown)));
+        }
+
+        // The senders are gone, so a later broadcast reaches nobody.
+        assert!(sessions.get_sender(first.id.as_str()).is_none());
+        assert!(sessions.get_sender(second.id.as_str()).is_none());
+        assert_eq!(sessions.get_sessions().len(), 3);
+    }
+
     #[tokio::test]
     async fn get_stream_refreshes_last_access() {
         let sessions = McpSessions::new();
@@ -581,4 +943,139 @@ mod tests {
         assert!(!ids.contains(&deleted.id));
         assert!(!ids.contains(&collected.id));
     }
+
+    #[tokio::test]
+    async fn batch_keeps_resource_updates_to_subscribers() {
+        let sessions = McpSessions::new();
+        let now = DateTimeAsMicroseconds::now();
+
+        let subscribed = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let other = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let mut subscribed_rx = sessions
+            .subscribe_to_notifications(subscribed.id.as_str(), now)
+            .unwrap();
+        let mut other_rx = sessions
+            .subscribe_to_notifications(other.id.as_str(), now)
+            .unwrap();
+        sessions.subscribe(subscribed.id.as_str(), "res://a".to_string());
+
+        sessions
+            .broadcast_batch(vec![
+                McpSocketUpdateEvent::ResourceUpdated {
+                    uri: "res://a".to_string(),
+                    title: None,
+                },
+                McpSocketUpdateEvent::ResourcesListChanged,
+            ])
+            .await;
+
+        match subscribed_rx.try_recv() {
+            Ok(McpSocketUpdateEvent::Batch(events)) => assert_eq!(events.len(), 2),
+            other => panic!("expected a batch, got {:?}", other),
+        }
+        match other_rx.try_recv() {
+            Ok(McpSocketUpdateEvent::Batch(events)) => {
+                assert!(matches!(
+                    events.as_slice(),
+                    [McpSocketUpdateEvent::ResourcesListChanged]
+                ))
+            }
+            other => panic!("expected a batch, got {:?}", other),
+        }
+    }
+
+    fn updated(n: usize) -> McpSocketUpdateEvent {
+        McpSocketUpdateEvent::ResourceUpdated {
+            uri: format!("notes://{}", n),
+            title: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_slow_client_blocks_the_sender_by_default() {
+        let sessions = McpSessions::new();
+        sessions.set_buffer(2, SlowClientPolicy::Block);
+        let now = DateTimeAsMicroseconds::now();
+
+        let session = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let mut receiver = sessions
+            .subscribe_to_notifications(session.id.as_str(), now)
+            .unwrap();
+
+        sessions.broadcast(updated(1)).await;
+        sessions.broadcast(updated(2)).await;
+
+        let third = sessions.broadcast(updated(3));
+        let third = tokio::time::timeout(Duration::from_millis(50), third).await;
+        assert!(third.is_err(), "a full buffer must hold the sender");
+
+        assert!(receiver.try_recv().is_ok());
+        sessions.broadcast(updated(3)).await;
+        assert!(sessions.get_sender(session.id.as_str()).is_some());
+    }
+
+    #[tokio::test]
+    async fn a_slow_client_loses_the_oldest_notifications() {
+        let sessions = McpSessions::new();
+        sessions.set_buffer(2, SlowClientPolicy::DropOldest);
+        let now = DateTimeAsMicroseconds::now();
+
+        let session = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let mut receiver = sessions
+            .subscribe_to_notifications(session.id.as_str(), now)
+            .unwrap();
+
+        // The client reads nothing while all of these go out.
+        for n in 1..=5 {
+            sessions.broadcast(updated(n)).await;
+        }
+        sessions.clear_sender(session.id.as_str());
+
+        let mut received = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            match event {
+                McpSocketUpdateEvent::ResourceUpdated { uri, .. } => received.push(uri),
+                other => panic!("expected ResourceUpdated, got {:?}", other),
+            }
+        }
+
+        // Two buffered plus the one already handed to the stream.
+        assert!(received.len() <= 3, "{:?}", received);
+        assert_eq!(received.last().map(|s| s.as_str()), Some("notes://5"));
+        let mut in_order = received.clone();
+        in_order.sort();
+        assert_eq!(received, in_order);
+    }
+
+    #[tokio::test]
+    async fn a_slow_client_is_disconnected() {
+        let sessions = McpSessions::new();
+        sessions.set_buffer(2, SlowClientPolicy::Disconnect);
+        let now = DateTimeAsMicroseconds::now();
+
+        let session = sessions.generate_session("2025-06-18".to_string(), now, false);
+        let mut receiver = sessions
+            .subscribe_to_notifications(session.id.as_str(), now)
+            .unwrap();
+        assert!(sessions.subscribe(session.id.as_str(), "notes://1".to_string()));
+
+        for n in 1..=3 {
+            sessions.broadcast(updated(n)).await;
+        }
+
+        assert!(sessions.get_sender(session.id.as_str()).is_none());
+        assert!(sessions.get_subscribers("notes://1").is_empty());
+
+        // What was buffered is still read, then the stream ends.
+        for _ in 0..2 {
+            assert!(matches!(
+                receiver.recv().await,
+                Some(McpSocketUpdateEvent::ResourceUpdated { .. })
+            ));
+        }
+        assert!(receiver.recv().await.is_none());
+
+        // The session itself stays; the client opens a new stream.
+        assert!(sessions.check_session_and_update_last_used(session.id.as_str(), now));
+    }
 }
@@ -1,8 +1,13 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::time::Duration;
 
+use futures_core::Stream;
 use my_ai_agent::my_json::json_writer::{JsonObjectWriter, RawJsonObject};
 use my_http_server::HttpOutputProducer;
 
+use super::{FRAME_PREFIX, FRAME_SUFFIX};
+
 /// Interval between SSE comment frames sent on an otherwise idle stream.
 /// Without them an idle SSE connection produces no bytes for minutes, so
 /// neither the client nor any reverse proxy in between can tell when the
@@ -11,19 +16,34 @@ use my_http_server::HttpOutputProducer;
 /// not to add meaningful traffic.
 pub(crate) const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
+/// What a keepalive tick writes. An SSE comment line is ignored by
+/// the client; without SSE framing a blank line is the no-op.
+#[cfg(feature = "sse-framing")]
+pub(crate) const KEEPALIVE_FRAME: &[u8] = b": keepalive\n\n";
+#[cfg(not(feature = "sse-framing"))]
+pub(crate) const KEEPALIVE_FRAME: &[u8] = b"\n";
+
 /// Initial `retry:` hint sent to EventSource-style clients so reconnect
 /// backoff has a sane default even if the client doesn't pick one.
+#[cfg(feature = "sse-framing")]
 const SSE_RETRY_MS: u64 = 3000;
 
 #[derive(Debug, Clone)]
 pub enum McpSocketUpdateEvent {
     Shutdown,
+    /// `notifications/shutdown` — an experimental, non-spec notification
+    /// telling the client the server is going down on purpose, so it can
+    /// reconnect elsewhere instead of reporting a dropped connection.
+    /// Always followed by [`Self::Shutdown`], which closes the stream.
+    ServerShutdown { reason: String },
     ToolsListChanged,
     ResourcesListChanged,
     PromptsListChanged,
     /// `notifications/resources/updated` — sent to sessions that
-    /// subscribed to the URI via `resources/subscribe`.
-    ResourceUpdated { uri: String },
+    /// subscribed to the URI via `resources/subscribe`. `params` are
+    /// `{uri, title?}`: the title, when the resource has one, lets a
+    /// client name what changed without a `resources/list`.
+    ResourceUpdated { uri: String, title: Option<String> },
     /// Server→client request asking the client to elicit user input.
     /// Carries a server-allocated request id (negative, distinct from
     /// client-allocated ids), a prompt message and a pre-serialized
@@ -33,18 +53,97 @@ pub enum McpSocketUpdateEvent {
         message: String,
         requested_schema: String,
     },
+    /// Several notifications written to the stream in one go, in
+    /// order, with nothing in between. See [`McpNotificationBatch`].
+    /// A [`Self::Shutdown`] inside a batch is ignored — a batch can not
+    /// close the stream.
+    Batch(Vec<McpSocketUpdateEvent>),
+    /// Any notification, as handed to `McpMiddleware::send_notification`.
+    /// `params` is JSON text; `None` sends the notification without.
+    Notification {
+        method: String,
+        params: Option<String>,
+    },
+}
+
+/// A group of notifications that belong together — say, a resource
+/// updated *and* the resource list changed — to be flushed to every
+/// live stream as one contiguous write. Build it up, then hand it to
+/// `McpMiddleware::notify_batch`.
+#[derive(Debug, Clone, Default)]
+pub struct McpNotificationBatch {
+    events: Vec<McpSocketUpdateEvent>,
+}
+
+impl McpNotificationBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tools_list_changed(mut self) -> Self {
+        self.events.push(McpSocketUpdateEvent::ToolsListChanged);
+        self
+    }
+
+    pub fn resources_list_changed(mut self) -> Self {
+        self.events.push(McpSocketUpdateEvent::ResourcesListChanged);
+        self
+    }
+
+    pub fn prompts_list_changed(mut self) -> Self {
+        self.events.push(McpSocketUpdateEvent::PromptsListChanged);
+        self
+    }
+
+    /// Delivered, like `notify_resource_updated`, only to sessions that
+    /// subscribed to `uri`; the rest of the batch goes to everyone.
+    /// `McpMiddleware::notify_batch` adds the resource's title.
+    pub fn resource_updated(mut self, uri: impl Into<String>) -> Self {
+        self.events.push(McpSocketUpdateEvent::ResourceUpdated {
+            uri: uri.into(),
+            title: None,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub(crate) fn into_events(self) -> Vec<McpSocketUpdateEvent> {
+        self.events
+    }
 }
 
 impl McpSocketUpdateEvent {
+    /// A server→client request, or a batch holding one: the client is
+    /// expected to answer it, so it must reach the client.
+    pub(crate) fn is_request(&self) -> bool {
+        match self {
+            Self::ElicitationRequest { .. } => true,
+            Self::Batch(events) => events.iter().any(Self::is_request),
+            _ => false,
+        }
+    }
+
     fn into_sse_frame(self) -> Option<Vec<u8>> {
         match self {
             Self::Shutdown => return None,
+            Self::Batch(events) => {
+                let mut frames = Vec::new();
+                for event in events {
+                    if let Some(frame) = event.into_sse_frame() {
+                        frames.extend_from_slice(frame.as_slice());
+                    }
+                }
+                return Some(frames);
+            }
             Self::ElicitationRequest {
                 id,
                 message,
                 requested_schema,
             } => {
-                let mut frame = "data: ".to_string();
+                let mut frame = FRAME_PREFIX.to_string();
                 JsonObjectWriter::new()
                     .write("jsonrpc", "2.0")
                     .write("id", id)
@@ -54,19 +153,47 @@ impl McpSocketUpdateEvent {
                             .write("requestedSchema", RawJsonObject::AsStr(&requested_schema))
                     })
                     .build_into(&mut frame);
-                frame.push('\n');
-                frame.push('\n');
+                frame.push_str(FRAME_SUFFIX);
+                return Some(frame.into_bytes());
+            }
+            Self::ServerShutdown { reason } => {
+                let mut frame = FRAME_PREFIX.to_string();
+                JsonObjectWriter::new()
+                    .write("jsonrpc", "2.0")
+                    .write("method", "notifications/shutdown")
+                    .write_json_object("params", |p| p.write("reason", reason.as_str()))
+                    .build_into(&mut frame);
+                frame.push_str(FRAME_SUFFIX);
+                return Some(frame.into_bytes());
+            }
+            Self::Notification { method, params } => {
+                let mut frame = FRAME_PREFIX.to_string();
+                JsonObjectWriter::new()
+                    .write("jsonrpc", "2.0")
+                    .write("method", method.as_str())
+                    .write_if(
+                        "params",
+                        RawJsonObject::AsStr(params.as_deref().unwrap_or_default()),
+                        params.is_some(),
+                    )
+                    .build_into(&mut frame);
+                frame.push_str(FRAME_SUFFIX);
                 return Some(frame.into_bytes());
             }
-            Self::ResourceUpdated { uri } => {
-                let mut frame = "data: ".to_string();
+            Self::ResourceUpdated { uri, title } => {
+                let mut frame = FRAME_PREFIX.to_string();
                 JsonObjectWriter::new()
                     .write("jsonrpc", "2.0")
                     .write("method", "notifications/resources/updated")
-                    .write_json_object("params", |p| p.write("uri", uri.as_str()))
+                    .write_json_object("params", |p| {
+                        p.write("uri", uri.as_str()).write_if(
+                            "title",
+                            title.as_deref().unwrap_or_default(),
+                            title.is_some(),
+                        )
+                    })
                     .build_into(&mut frame);
-                frame.push('\n');
-                frame.push('\n');
+                frame.push_str(FRAME_SUFFIX);
                 return Some(frame.into_bytes());
             }
             _ => {}
@@ -76,24 +203,141 @@ impl McpSocketUpdateEvent {
             Self::ToolsListChanged => "notifications/tools/list_changed",
             Self::ResourcesListChanged => "notifications/resources/list_changed",
             Self::PromptsListChanged => "notifications/prompts/list_changed",
-            Self::Shutdown | Self::ElicitationRequest { .. } | Self::ResourceUpdated { .. } => {
+            Self::Shutdown
+            | Self::ServerShutdown { .. }
+            | Self::ElicitationRequest { .. }
+            | Self::ResourceUpdated { .. }
+            | Self::Notification { .. }
+            | Self::Batch(_) => {
                 unreachable!()
             }
         };
 
-        let mut frame = "data: ".to_string();
+        let mut frame = FRAME_PREFIX.to_string();
         JsonObjectWriter::new()
             .write("jsonrpc", "2.0")
             .write("method", method)
             .write_json_object("params", |p| p)
             .build_into(&mut frame);
-        frame.push('\n');
-        frame.push('\n');
+        frame.push_str(FRAME_SUFFIX);
 
         Some(frame.into_bytes())
     }
 }
 
+/// One write of a session's outbound stream: a notification or
+/// server→client request as framed for the wire (see
+/// [`super::FRAME_PREFIX`]), a keepalive, or the `retry:` preamble.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseFrame(Vec<u8>);
+
+impl SseFrame {
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn is_keepalive(&self) -> bool {
+        self.0 == KEEPALIVE_FRAME
+    }
+}
+
+impl From<SseFrame> for Vec<u8> {
+    fn from(frame: SseFrame) -> Self {
+        frame.0
+    }
+}
+
+/// A session's outbound channel — what the `GET` stream writes — as a
+/// [`Stream`], for embedders that serve MCP from their own HTTP server.
+/// Yields the same bytes in the same order, keepalives included, and
+/// ends when the session is shut down or replaced by a newer stream.
+/// See `McpMiddleware::open_session_stream`.
+///
+/// Dropping it before it ends is the client going away: the session
+/// loses its channel, as when a `GET` stream's write fails.
+pub struct McpSessionStream {
+    receiver: tokio::sync::mpsc::Receiver<McpSocketUpdateEvent>,
+    keepalive: tokio::time::Interval,
+    preamble: Option<SseFrame>,
+    sessions: std::sync::Arc<super::McpSessions>,
+    session_id: String,
+    ended: bool,
+}
+
+impl McpSessionStream {
+    /// Must be called inside the tokio runtime: the keepalive timer
+    /// starts right away.
+    pub(crate) fn new(
+        receiver: tokio::sync::mpsc::Receiver<McpSocketUpdateEvent>,
+        sessions: std::sync::Arc<super::McpSessions>,
+        session_id: String,
+    ) -> Self {
+        #[cfg(feature = "sse-framing")]
+        let preamble = Some(SseFrame(format!("retry: {}\n\n", SSE_RETRY_MS).into_bytes()));
+        #[cfg(not(feature = "sse-framing"))]
+        let preamble = None;
+
+        // Unlike `interval()`, the first tick is one period away.
+        let keepalive = tokio::time::interval_at(
+            tokio::time::Instant::now() + KEEPALIVE_INTERVAL,
+            KEEPALIVE_INTERVAL,
+        );
+
+        Self {
+            receiver,
+            keepalive,
+            preamble,
+            sessions,
+            session_id,
+            ended: false,
+        }
+    }
+}
+
+impl Stream for McpSessionStream {
+    type Item = SseFrame;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SseFrame>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+
+        if let Some(preamble) = self.preamble.take() {
+            return Poll::Ready(Some(preamble));
+        }
+
+        match self.receiver.poll_recv(cx) {
+            Poll::Ready(event) => {
+                match event.and_then(McpSocketUpdateEvent::into_sse_frame) {
+                    Some(frame) => Poll::Ready(Some(SseFrame(frame))),
+                    None => {
+                        self.ended = true;
+                        Poll::Ready(None)
+                    }
+                }
+            }
+            Poll::Pending => match self.keepalive.poll_tick(cx) {
+                Poll::Ready(_) => Poll::Ready(Some(SseFrame(KEEPALIVE_FRAME.to_vec()))),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl Drop for McpSessionStream {
+    fn drop(&mut self) {
+        // A closed channel was shut down or replaced by a newer stream,
+        // whose sender must stay.
+        if !self.ended && !self.receiver.is_closed() {
+            self.sessions.clear_sender(self.session_id.as_str());
+        }
+    }
+}
+
 pub async fn stream_updates(
     mut producer: HttpOutputProducer,
     mut receiver: tokio::sync::mpsc::Receiver<McpSocketUpdateEvent>,
@@ -103,10 +347,13 @@ pub async fn stream_updates(
     // Kick the stream immediately so reverse proxies that buffer until
     // the first byte flush response headers downstream, and so EventSource
     // clients get a reconnect-backoff hint.
-    let preamble = format!("retry: {}\n\n", SSE_RETRY_MS).into_bytes();
-    if producer.send(preamble).await.is_err() {
-        sessions.clear_sender(session_id.as_str());
-        return;
+    #[cfg(feature = "sse-framing")]
+    {
+        let preamble = format!("retry: {}\n\n", SSE_RETRY_MS).into_bytes();
+        if producer.send(preamble).await.is_err() {
+            sessions.clear_sender(session_id.as_str());
+            return;
+        }
     }
 
     let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
@@ -132,7 +379,7 @@ pub async fn stream_updates(
                 // SSE comment line — ignored by the client per spec, but
                 // forces a write so a broken socket is detected promptly
                 // and intermediaries keep the connection alive.
-                if producer.send(b": keepalive\n\n".to_vec()).await.is_err() {
+                if producer.send(KEEPALIVE_FRAME.to_vec()).await.is_err() {
                     sessions.clear_sender(session_id.as_str());
                     return;
                 }
@@ -140,3 +387,102 @@ pub async fn stream_updates(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_json(event: McpSocketUpdateEvent) -> serde_json::Value {
+        let frame = event.into_sse_frame().expect("event must produce a frame");
+        let frame = String::from_utf8(frame).unwrap();
+        let body = frame
+            .strip_prefix(FRAME_PREFIX)
+            .expect("frame must start with the frame prefix")
+            .trim_end();
+        serde_json::from_str(body).expect("valid json")
+    }
+
+    #[cfg(not(feature = "sse-framing"))]
+    #[test]
+    fn unframed_build_emits_bare_notifications() {
+        let frame = McpSocketUpdateEvent::ToolsListChanged
+            .into_sse_frame()
+            .expect("a notification produces a frame");
+        let frame = String::from_utf8(frame).unwrap();
+
+        assert!(!frame.starts_with("data: "), "{}", frame);
+        let parsed: serde_json::Value = serde_json::from_str(&frame).expect("bare json");
+        assert_eq!(parsed["method"], "notifications/tools/list_changed");
+    }
+
+    #[test]
+    fn server_shutdown_is_a_notification_with_a_reason() {
+        let parsed = frame_json(McpSocketUpdateEvent::ServerShutdown {
+            reason: "maintenance".to_string(),
+        });
+
+        assert_eq!(parsed["method"], "notifications/shutdown");
+        assert_eq!(parsed["params"]["reason"], "maintenance");
+        assert!(parsed.get("id").is_none());
+    }
+
+    #[test]
+    fn resource_updated_params_are_uri_and_optional_title() {
+        let parsed = frame_json(McpSocketUpdateEvent::ResourceUpdated {
+            uri: "res://a".to_string(),
+            title: Some("Release notes".to_string()),
+        });
+        assert_eq!(parsed["method"], "notifications/resources/updated");
+        assert_eq!(
+            parsed["params"],
+            serde_json::json!({"uri": "res://a", "title": "Release notes"})
+        );
+        assert!(parsed.get("id").is_none());
+
+        let parsed = frame_json(McpSocketUpdateEvent::ResourceUpdated {
+            uri: "res://b".to_string(),
+            title: None,
+        });
+        assert_eq!(parsed["params"], serde_json::json!({"uri": "res://b"}));
+    }
+
+    #[test]
+    fn batch_is_one_contiguous_write_in_order() {
+        let batch = McpNotificationBatch::new()
+            .resource_updated("res://a")
+            .resources_list_changed()
+            .tools_list_changed();
+
+        let mut events = batch.into_events();
+        events.insert(1, McpSocketUpdateEvent::Shutdown);
+
+        let frames = McpSocketUpdateEvent::Batch(events)
+            .into_sse_frame()
+            .expect("a batch produces frames");
+        let frames = String::from_utf8(frames).unwrap();
+
+        let methods: Vec<String> = frames
+            .split(FRAME_SUFFIX)
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| {
+                let json: serde_json::Value =
+                    serde_json::from_str(frame.strip_prefix(FRAME_PREFIX).unwrap()).unwrap();
+                json["method"].as_str().unwrap().to_string()
+            })
+            .collect();
+
+        assert_eq!(
+            methods,
+            vec![
+                "notifications/resources/updated",
+                "notifications/resources/list_changed",
+                "notifications/tools/list_changed",
+            ]
+        );
+    }
+
+    #[test]
+    fn shutdown_produces_no_frame() {
+        assert!(McpSocketUpdateEvent::Shutdown.into_sse_frame().is_none());
+    }
+}
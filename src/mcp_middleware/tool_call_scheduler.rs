@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+/// Server-wide cap on concurrently running tool calls, shared fairly
+/// between sessions. See `McpMiddleware::with_max_concurrent_tool_calls`.
+///
+/// Calls over the cap wait in a FIFO queue per session, and a freed slot
+/// goes to the sessions with waiting calls in turn, one call each. A
+/// session that floods the server only gets its share of the slots that
+/// free up: another session's single call is at most one turn per
+/// waiting session away, however long the flooder's queue is.
+pub(crate) struct ToolCallScheduler {
+    state: Mutex<SchedulerState>,
+}
+
+struct SchedulerState {
+    capacity: usize,
+    running: usize,
+    waiting: HashMap<String, VecDeque<oneshot::Sender<ToolCallSlot>>>,
+    /// Sessions with waiting calls, in the order their turn comes.
+    turns: VecDeque<String>,
+}
+
+impl SchedulerState {
+    fn next_waiter(&mut self) -> Option<oneshot::Sender<ToolCallSlot>> {
+        while let Some(session_id) = self.turns.pop_front() {
+            let Some(queue) = self.waiting.get_mut(&session_id) else {
+                continue;
+            };
+
+            let next = queue.pop_front();
+
+            if queue.is_empty() {
+                self.waiting.remove(&session_id);
+            } else {
+                self.turns.push_back(session_id);
+            }
+
+            if next.is_some() {
+                return next;
+            }
+        }
+
+        None
+    }
+}
+
+/// How busy the [`ToolCallScheduler`] was at the moment
+/// `McpMiddleware::get_tool_call_load` was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ToolCallLoad {
+    /// Calls holding a slot — running, or handed one and about to.
+    pub in_flight: usize,
+    /// Calls waiting for a slot, over all sessions.
+    pub queued: usize,
+}
+
+/// A running call's slot. Dropping it — the call finished, failed or
+/// was cancelled — hands the slot to the next call in turn.
+pub(crate) struct ToolCallSlot {
+    scheduler: Option<Arc<ToolCallScheduler>>,
+}
+
+impl Drop for ToolCallSlot {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release();
+        }
+    }
+}
+
+impl ToolCallScheduler {
+    /// `capacity` of `0` is treated as `1`.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(SchedulerState {
+                capacity: capacity.max(1),
+                running: 0,
+                waiting: HashMap::new(),
+                turns: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn load(&self) -> ToolCallLoad {
+        let state = self.state.lock();
+        ToolCallLoad {
+            in_flight: state.running,
+            queued: state.waiting.values().map(|queue| queue.len()).sum(),
+        }
+    }
+
+    /// Waits for a slot for a call of `session_id`.
+    pub(crate) async fn acquire(self: &Arc<Self>, session_id: &str) -> ToolCallSlot {
+        let slot = {
+            let mut state = self.state.lock();
+            let state = &mut *state;
+
+            // A freed slot goes straight to a waiter, so a free one
+            // means nobody is waiting.
+            if state.running < state.capacity {
+                state.running += 1;
+                return ToolCallSlot {
+                    scheduler: Some(self.clone()),
+                };
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            let queue = state.waiting.entry(session_id.to_string()).or_default();
+
+            if queue.is_empty() {
+                state.turns.push_back(session_id.to_string());
+            }
+
+            queue.push_back(sender);
+            receiver
+        };
+
+        slot.await
+            .expect("queued tool calls are only dropped by a scheduler that is gone")
+    }
+
+    fn release(self: Arc<Self>) {
+        loop {
+            // Sent outside the lock: a slot that cannot be delivered is
+            // disarmed and dropped here, and must not re-enter.
+            let next = {
+                let mut state = self.state.lock();
+                match state.next_waiter() {
+                    Some(next) => next,
+                    None => {
+                        state.running -= 1;
+                        return;
+                    }
+                }
+            };
+
+            let slot = ToolCallSlot {
+                scheduler: Some(self.clone()),
+            };
+
+            match next.send(slot) {
+                Ok(()) => return,
+                // The waiting call was cancelled; try the next one.
+                Err(mut slot) => slot.scheduler = None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_cancelled_waiter_does_not_keep_the_slot() {
+        let scheduler = Arc::new(ToolCallScheduler::new(1));
+        let running = scheduler.acquire("a").await;
+
+        let cancelled = tokio::time::timeout(
+            std::time::Duration::from_millis(10),
+            scheduler.acquire("b"),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        drop(running);
+        let next = tokio::time::timeout(std::time::Duration::from_secs(1), scheduler.acquire("c"))
+            .await
+            .expect("the slot is free again");
+        drop(next);
+
+        assert_eq!(scheduler.state.lock().running, 0);
+    }
+
+    #[tokio::test]
+    async fn freed_slots_go_to_sessions_in_turn() {
+        let scheduler = Arc::new(ToolCallScheduler::new(1));
+        let running = scheduler.acquire("noisy").await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+
+        for session_id in ["noisy", "noisy", "noisy", "quiet"] {
+            let scheduler = scheduler.clone();
+            let order = order.clone();
+            waiters.push(tokio::spawn(async move {
+                let _slot = scheduler.acquire(session_id).await;
+                order.lock().push(session_id);
+            }));
+            // Queue in a known order.
+            tokio::task::yield_now().await;
+        }
+
+        drop(running);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+
+        assert_eq!(order.lock().clone(), vec!["noisy", "quiet", "noisy", "noisy"]);
+    }
+
+    #[tokio::test]
+    async fn the_load_counts_running_and_queued_calls() {
+        let scheduler = Arc::new(ToolCallScheduler::new(1));
+        assert_eq!(scheduler.load(), ToolCallLoad::default());
+
+        let running = scheduler.acquire("a").await;
+        let mut waiters = Vec::new();
+        for session_id in ["a", "b"] {
+            let scheduler = scheduler.clone();
+            waiters.push(tokio::spawn(async move {
+                drop(scheduler.acquire(session_id).await);
+            }));
+            tokio::task::yield_now().await;
+        }
+
+        let load = scheduler.load();
+        assert_eq!(load.in_flight, 1);
+        assert_eq!(load.queued, 2);
+
+        drop(running);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert_eq!(scheduler.load(), ToolCallLoad::default());
+    }
+}
@@ -0,0 +1,206 @@
+/// One way the arguments of a `tools/call` miss the tool's input schema.
+/// `pointer` is the JSON pointer (RFC 6901) of the offending value, or
+/// of where a missing one belongs; the arguments themselves are `""`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentError {
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Checks `arguments` against `schema` and returns every failure, not
+/// just the first. Covers the keywords the tools' derived schemas use:
+/// `type`, `enum`, `required`, `properties`, `additionalProperties:
+/// false` and `items`. Anything else in the schema is not checked.
+pub fn validate_arguments(
+    schema: &serde_json::Value,
+    arguments: &serde_json::Value,
+) -> Vec<ArgumentError> {
+    let mut errors = Vec::new();
+    validate_value(schema, arguments, "", &mut errors);
+    errors
+}
+
+/// `errors` as the `data` of the `-32602` error: a JSON array of
+/// `{pointer, message}`.
+pub(crate) fn argument_errors_json(errors: &[ArgumentError]) -> String {
+    let errors: Vec<serde_json::Value> = errors
+        .iter()
+        .map(|error| serde_json::json!({"pointer": error.pointer, "message": error.message}))
+        .collect();
+    serde_json::Value::from(errors).to_string()
+}
+
+fn validate_value(
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    pointer: &str,
+    errors: &mut Vec<ArgumentError>,
+) {
+    let fail = |errors: &mut Vec<ArgumentError>, message: String| {
+        errors.push(ArgumentError {
+            pointer: pointer.to_string(),
+            message,
+        });
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            serde_json::Value::String(single) => vec![single.as_str()],
+            serde_json::Value::Array(many) => many.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+
+        if !types.is_empty() && !types.iter().any(|t| is_of_type(value, t)) {
+            fail(
+                errors,
+                format!("Expected {}, got {}", types.join(" or "), type_name(value)),
+            );
+            // Nothing below the type is worth checking once it is wrong.
+            return;
+        }
+    }
+
+    let allowed = schema.get("enum").and_then(|e| e.as_array());
+    if let Some(allowed) = allowed.filter(|allowed| !allowed.contains(value)) {
+        fail(errors, format!("Expected one of {}", serde_json::Value::from(allowed.clone())));
+    }
+
+    match value {
+        serde_json::Value::Object(object) => {
+            if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+                for name in required.iter().filter_map(|n| n.as_str()) {
+                    if !object.contains_key(name) {
+                        errors.push(ArgumentError {
+                            pointer: child_pointer(pointer, name),
+                            message: "Required field is missing".to_string(),
+                        });
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            let closed = schema.get("additionalProperties") == Some(&serde_json::Value::Bool(false));
+
+            for (name, child) in object {
+                let child_pointer = child_pointer(pointer, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(child_schema) => {
+                        validate_value(child_schema, child, child_pointer.as_str(), errors)
+                    }
+                    None if closed => errors.push(ArgumentError {
+                        pointer: child_pointer,
+                        message: "Field is not allowed".to_string(),
+                    }),
+                    None => {}
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items").filter(|i| i.is_object()) {
+                for (index, item) in items.iter().enumerate() {
+                    let item_pointer = format!("{}/{}", pointer, index);
+                    validate_value(item_schema, item, item_pointer.as_str(), errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_of_type(value: &serde_json::Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        // A type this check does not know is not held against the value.
+        _ => true,
+    }
+}
+
+fn type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Object(_) => "object",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Null => "null",
+        serde_json::Value::Number(_) => "number",
+    }
+}
+
+/// `pointer` extended with `name`, escaped per RFC 6901.
+fn child_pointer(pointer: &str, name: &str) -> String {
+    format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "count": {"type": "integer"},
+                "mode": {"type": "string", "enum": ["fast", "safe"]},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "a/b": {"type": "boolean"}
+            },
+            "required": ["name", "count"],
+            "additionalProperties": false
+        })
+    }
+
+    fn pointers(errors: &[ArgumentError]) -> Vec<&str> {
+        let mut pointers: Vec<&str> = errors.iter().map(|e| e.pointer.as_str()).collect();
+        pointers.sort();
+        pointers
+    }
+
+    #[test]
+    fn valid_arguments_pass() {
+        let arguments = serde_json::json!({"name": "x", "count": 3.0, "mode": "safe", "tags": ["a"]});
+        assert!(validate_arguments(&schema(), &arguments).is_empty());
+    }
+
+    #[test]
+    fn every_failure_is_reported_with_its_pointer() {
+        let arguments = serde_json::json!({
+            "count": "three",
+            "mode": "slow",
+            "tags": ["a", 1],
+            "a/b": "yes",
+            "extra": true
+        });
+
+        let errors = validate_arguments(&schema(), &arguments);
+        assert_eq!(
+            pointers(&errors),
+            vec!["/a~1b", "/count", "/extra", "/mode", "/name", "/tags/1"]
+        );
+
+        let count = errors.iter().find(|e| e.pointer == "/count").unwrap();
+        assert_eq!(count.message, "Expected integer, got string");
+    }
+
+    #[test]
+    fn a_wrong_root_type_is_one_failure() {
+        let errors = validate_arguments(&schema(), &serde_json::json!([1, 2]));
+        assert_eq!(
+            errors,
+            vec![ArgumentError {
+                pointer: "".to_string(),
+                message: "Expected object, got array".to_string(),
+            }]
+        );
+    }
+}
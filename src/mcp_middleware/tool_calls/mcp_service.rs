@@ -1,3 +1,4 @@
+use base64::Engine;
 use my_ai_agent::{json_schema::*, my_json};
 use my_http_server::async_trait;
 
@@ -5,7 +6,23 @@ use super::ToolCallContext;
 
 pub struct ToolCallOutput<T> {
     pub data: T,
+    /// An inline instruction for the model, sent as the text content
+    /// block in place of the JSON of `data`. A [`Self::with_text`] text
+    /// takes precedence: once `content` holds a text block, the
+    /// instruction is not sent at all.
     pub instruction: Option<String>,
+    /// `_meta` of the result's first text content block — tracing ids,
+    /// source citations. See [`Self::with_content_meta`].
+    pub content_meta: Option<serde_json::Value>,
+    /// Sources backing spans of the first text content block. See
+    /// [`Self::with_citation`].
+    pub citations: Vec<TextCitation>,
+    /// Served from the tool's cache. See [`Self::from_cache`].
+    pub cached: bool,
+    /// Text, image and audio blocks in the order they were added — the
+    /// order they go out in. See [`Self::with_text`] and
+    /// [`Self::with_binary`].
+    pub content: Vec<ToolContentBlock>,
 }
 
 impl<T> ToolCallOutput<T> {
@@ -13,15 +30,146 @@ impl<T> ToolCallOutput<T> {
         Self {
             data,
             instruction: None,
+            content_meta: None,
+            citations: Vec::new(),
+            cached: false,
+            content: Vec::new(),
         }
     }
 
+    /// `data` with an inline instruction for the model. Not sent when a
+    /// [`Self::with_text`] text is added as well; see [`Self::instruction`].
     pub fn with_instruction(data: T, instruction: impl Into<String>) -> Self {
         Self {
             data,
             instruction: Some(instruction.into()),
+            content_meta: None,
+            citations: Vec::new(),
+            cached: false,
+            content: Vec::new(),
         }
     }
+
+    /// Marks the output as a cache hit rather than a fresh computation.
+    /// The result then carries `_meta.cached: true`, so observability
+    /// tooling can tell the two apart; the content is unchanged.
+    pub fn from_cache(mut self) -> Self {
+        self.cached = true;
+        self
+    }
+
+    /// Cites `citation.uri` as the source of a span of the result's
+    /// first text content block — the first [`Self::with_text`] text or
+    /// the instruction when there is one, the JSON of `data` otherwise.
+    /// Clients that render footnotes read them from
+    /// `annotations.citations` of the block.
+    pub fn with_citation(mut self, citation: TextCitation) -> Self {
+        self.citations.push(citation);
+        self
+    }
+
+    /// Adds `text` as a text block to the result's `content`. Once there
+    /// is one, the JSON of `data` is not sent as text — it still goes
+    /// out as `structuredContent` — so this is a readable rendering for
+    /// the model and the user next to the machine value. Takes
+    /// precedence over the instruction, which is then not sent.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.content.push(ToolContentBlock::Text(text.into()));
+        self
+    }
+
+    /// Adds an image or audio block to the result's `content` — a
+    /// chart, a recording. Blocks go out in the order they were added,
+    /// text ones included; without a text block of its own the result
+    /// starts with the instruction, or the JSON of `data`.
+    pub fn with_binary(mut self, content: ToolBinaryContent) -> Self {
+        self.content.push(ToolContentBlock::Binary(content));
+        self
+    }
+
+    /// Attaches `_meta` to the first text content block of the result, e.g.
+    /// `{"source": "https://..."}` for a citation. A result without a
+    /// content block (see [`VoidOutput`]) has nowhere to carry it.
+    pub fn with_content_meta(mut self, meta: serde_json::Value) -> Self {
+        self.content_meta = Some(meta);
+        self
+    }
+}
+
+/// A block of a tool result's `content`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolContentBlock {
+    /// `{"type": "text", "text"}`.
+    Text(String),
+    /// An image or an audio block.
+    Binary(ToolBinaryContent),
+}
+
+impl ToolContentBlock {
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text(_))
+    }
+}
+
+/// A binary content block of a tool result: `{"type": "image" | "audio",
+/// "data", "mimeType"}`. The bytes are base64-encoded as the block is
+/// made, so whatever they hold — not UTF-8, not text at all — the
+/// response only ever carries ASCII.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolBinaryContent {
+    /// `image` or `audio`.
+    pub content_type: &'static str,
+    pub mime_type: String,
+    /// The bytes, base64-encoded.
+    pub data: String,
+}
+
+impl ToolBinaryContent {
+    pub fn image(mime_type: impl Into<String>, bytes: impl AsRef<[u8]>) -> Self {
+        Self::new("image", mime_type, bytes)
+    }
+
+    pub fn audio(mime_type: impl Into<String>, bytes: impl AsRef<[u8]>) -> Self {
+        Self::new("audio", mime_type, bytes)
+    }
+
+    fn new(
+        content_type: &'static str,
+        mime_type: impl Into<String>,
+        bytes: impl AsRef<[u8]>,
+    ) -> Self {
+        Self {
+            content_type,
+            mime_type: mime_type.into(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }
+    }
+}
+
+/// A span `[start, end)` of a text content block, in characters, and
+/// the source it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextCitation {
+    pub start: usize,
+    pub end: usize,
+    pub uri: String,
+    pub title: Option<String>,
+}
+
+impl TextCitation {
+    pub fn new(start: usize, end: usize, uri: impl Into<String>) -> Self {
+        Self {
+            start,
+            end,
+            uri: uri.into(),
+            title: None,
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
 }
 
 impl<T> From<T> for ToolCallOutput<T> {
@@ -30,9 +178,75 @@ impl<T> From<T> for ToolCallOutput<T> {
     }
 }
 
+/// Output type for tools that only perform a side effect. Use it as
+/// `OutputData` and return `VoidOutput`: the result goes out with an
+/// empty `content` array and `isError: false`, so the handler does not
+/// have to invent placeholder text.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct VoidOutput;
+
+/// How [`VoidOutput`] looks once serialized. A result is not told
+/// void by this — `null` is valid output of other types too — but by
+/// [`ExecutedToolCall::void_output`].
+pub const VOID_TOOL_OUTPUT: &str = "null";
+
+#[async_trait::async_trait]
+impl JsonTypeDescription for VoidOutput {
+    async fn get_description(
+        _has_default: bool,
+        _with_enum: Option<Vec<rust_extensions::StrOrString<'static>>>,
+        _output: bool,
+    ) -> my_json::json_writer::JsonObjectWriter {
+        my_json::json_writer::JsonObjectWriter::new()
+            .write("type", "object")
+            .write_json_object("properties", |o| o)
+    }
+}
+
 pub struct ExecutedToolCall {
     pub structured_json: String,
     pub instruction: Option<String>,
+    pub content_meta: Option<serde_json::Value>,
+    pub citations: Vec<TextCitation>,
+    pub cached: bool,
+    /// The blocks the tool added, in `content` order. When none of them
+    /// is text, the instruction, or else `structured_json`, goes before
+    /// them as the text block. See [`ToolCallOutput::with_text`].
+    pub content: Vec<ToolContentBlock>,
+    /// The tool returned [`VoidOutput`]: `structured_json` is nothing to
+    /// show. Set by the executors of typed tools; a raw tool sets it
+    /// itself.
+    pub void_output: bool,
+}
+
+/// Why a tool call produced no [`ExecutedToolCall`]. The split mirrors
+/// the two error channels of the MCP spec:
+///
+/// * [`Self::Failed`] — the tool ran and failed. Reported in-band as a
+///   normal result with `isError: true`, so the model sees the message
+///   and can react to it.
+/// * [`Self::InvalidParams`] — the request never reached the tool
+///   (arguments do not fit its input type). Reported as a JSON-RPC
+///   `-32602` error, like an unknown tool name.
+/// * [`Self::ServerError`] — an application-specific JSON-RPC error with
+///   a code of the host's own in the `-32099..=-32000` server-error
+///   range. Build it with [`Self::server_error`], which rejects codes
+///   outside the range and the ones the middleware answers with itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum McpToolCallError {
+    Failed(String),
+    InvalidParams(String),
+    ServerError { code: i64, message: String },
+}
+
+impl McpToolCallError {
+    pub fn server_error(code: i64, message: impl Into<String>) -> Result<Self, String> {
+        crate::check_server_error_code(code)?;
+        Ok(Self::ServerError {
+            code,
+            message: message.into(),
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -42,6 +256,31 @@ where
     OutputData: JsonTypeDescription + Sized + Send + Sync + 'static,
 {
     async fn execute_tool_call(&self, model: InputData) -> Result<OutputData, String>;
+
+    /// Opt-in to `_meta.dryRun` calls: the arguments are checked
+    /// against `InputData` and the tool is not run. Off by default —
+    /// a tool that does not support dry run rejects such a call.
+    fn supports_dry_run(&self) -> bool {
+        false
+    }
+
+    /// Flags the tool in `tools/list` as one that streams its output,
+    /// so a client can open an SSE stream before calling it. Off by
+    /// default.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// The output schema for clients that negotiated `protocol_version`,
+    /// when it differs from the one of `OutputData`. `None` (the
+    /// default) keeps that one for every version. See
+    /// [`McpToolCallAbstract::get_output_params_for_version`].
+    async fn get_output_params_for_version(
+        &self,
+        _protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -54,6 +293,24 @@ where
         &self,
         model: InputData,
     ) -> Result<ToolCallOutput<OutputData>, String>;
+
+    /// See [`McpToolCall::supports_dry_run`].
+    fn supports_dry_run(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::supports_streaming`].
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::get_output_params_for_version`].
+    async fn get_output_params_for_version(
+        &self,
+        _protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -70,6 +327,25 @@ where
         let data = <T as McpToolCall<InputData, OutputData>>::execute_tool_call(self, model).await?;
         Ok(ToolCallOutput::new(data))
     }
+
+    fn supports_dry_run(&self) -> bool {
+        <T as McpToolCall<InputData, OutputData>>::supports_dry_run(self)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        <T as McpToolCall<InputData, OutputData>>::supports_streaming(self)
+    }
+
+    async fn get_output_params_for_version(
+        &self,
+        protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        <T as McpToolCall<InputData, OutputData>>::get_output_params_for_version(
+            self,
+            protocol_version,
+        )
+        .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -77,16 +353,39 @@ pub trait McpToolCallAbstract {
     /// `ctx` is built by the middleware for every tool call. Existing
     /// `McpToolCall` impls ignore it; new `McpToolCallEx` impls use
     /// it for elicitation and other server→client interactions.
-    async fn execute(
-        &self,
-        input: &str,
-        ctx: ToolCallContext,
-    ) -> Result<ExecutedToolCall, String>;
+    async fn execute(&self, ctx: ToolCallContext) -> Result<ExecutedToolCall, McpToolCallError>;
 
     fn get_fn_name(&self) -> &str;
     fn get_description(&self) -> &str;
     async fn get_input_params(&self) -> my_json::json_writer::JsonObjectWriter;
     async fn get_output_params(&self) -> my_json::json_writer::JsonObjectWriter;
+
+    /// The output schema for clients that negotiated `protocol_version`,
+    /// when it differs from [`Self::get_output_params`] — for a tool whose
+    /// output shape changed and that still serves the old shape to older
+    /// clients (it learns the client's version from
+    /// [`ToolCallContext::protocol_version`]). `None` (the default) keeps
+    /// the one schema for every version. Unlike that schema, this one is
+    /// built on every `tools/list`.
+    async fn get_output_params_for_version(
+        &self,
+        _protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        None
+    }
+
+    /// Answers a `_meta.dryRun` call: checks the arguments in `ctx`
+    /// without running the tool. `None` (the default) means the tool
+    /// does not support dry run.
+    async fn dry_run(&self, _ctx: &ToolCallContext) -> Option<Result<(), McpToolCallError>> {
+        None
+    }
+
+    /// Whether `tools/list` flags the tool as streaming. See
+    /// [`McpToolCall::supports_streaming`].
+    fn supports_streaming(&self) -> bool {
+        false
+    }
 }
 
 /// Context-aware tool call. Implement this instead of [`McpToolCall`]
@@ -102,6 +401,24 @@ where
         model: InputData,
         ctx: &ToolCallContext,
     ) -> Result<OutputData, String>;
+
+    /// See [`McpToolCall::supports_dry_run`].
+    fn supports_dry_run(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::supports_streaming`].
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::get_output_params_for_version`].
+    async fn get_output_params_for_version(
+        &self,
+        _protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        None
+    }
 }
 
 /// Context-aware counterpart of [`McpToolCallWithInstruction`]:
@@ -119,6 +436,24 @@ where
         model: InputData,
         ctx: &ToolCallContext,
     ) -> Result<ToolCallOutput<OutputData>, String>;
+
+    /// See [`McpToolCall::supports_dry_run`].
+    fn supports_dry_run(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::supports_streaming`].
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// See [`McpToolCall::get_output_params_for_version`].
+    async fn get_output_params_for_version(
+        &self,
+        _protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -138,6 +473,25 @@ where
                 .await?;
         Ok(ToolCallOutput::new(data))
     }
+
+    fn supports_dry_run(&self) -> bool {
+        <T as McpToolCallEx<InputData, OutputData>>::supports_dry_run(self)
+    }
+
+    fn supports_streaming(&self) -> bool {
+        <T as McpToolCallEx<InputData, OutputData>>::supports_streaming(self)
+    }
+
+    async fn get_output_params_for_version(
+        &self,
+        protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        <T as McpToolCallEx<InputData, OutputData>>::get_output_params_for_version(
+            self,
+            protocol_version,
+        )
+        .await
+    }
 }
 
 #[cfg(test)]
@@ -174,12 +528,17 @@ mod tests {
     }
 
     fn test_ctx() -> ToolCallContext {
-        ToolCallContext {
-            session_id: "test".to_string(),
-            supports_elicitation: false,
-            elicitations: Arc::new(McpElicitations::new()),
-            sessions: Arc::new(McpSessions::new()),
-        }
+        test_ctx_with(serde_json::json!({}))
+    }
+
+    fn test_ctx_with(arguments: serde_json::Value) -> ToolCallContext {
+        ToolCallContext::new(
+            "test".to_string(),
+            false,
+            arguments.to_string(),
+            Arc::new(McpElicitations::new()),
+            Arc::new(McpSessions::new()),
+        )
     }
 
     #[tokio::test]
@@ -203,4 +562,102 @@ mod tests {
         assert_eq!(out.data, "y");
         assert_eq!(out.instruction.as_deref(), Some("hint"));
     }
+
+    #[derive(serde::Deserialize)]
+    struct SumArgs {
+        a: i64,
+        b: i64,
+    }
+
+    /// A hand-rolled tool that reads its arguments through the context.
+    struct RawSumTool;
+
+    #[async_trait::async_trait]
+    impl McpToolCallAbstract for RawSumTool {
+        async fn execute(
+            &self,
+            ctx: ToolCallContext,
+        ) -> Result<ExecutedToolCall, McpToolCallError> {
+            let args: SumArgs = ctx.parse_arguments()?;
+            Ok(ExecutedToolCall {
+                structured_json: format!(r#"{{"sum":{}}}"#, args.a + args.b),
+                instruction: None,
+                content_meta: None,
+                citations: Vec::new(),
+                cached: false,
+                content: Vec::new(),
+                void_output: false,
+            })
+        }
+
+        fn get_fn_name(&self) -> &str {
+            "sum"
+        }
+
+        fn get_description(&self) -> &str {
+            "Adds two numbers"
+        }
+
+        async fn get_input_params(&self) -> my_json::json_writer::JsonObjectWriter {
+            my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+
+        async fn get_output_params(&self) -> my_json::json_writer::JsonObjectWriter {
+            my_json::json_writer::JsonObjectWriter::new().write("type", "object")
+        }
+    }
+
+    #[test]
+    fn base64_argument_is_decoded_or_rejected_as_invalid_params() {
+        let ctx = test_ctx_with(serde_json::json!({"blob": "aGVsbG8=", "bad": "not base64!", "num": 1}));
+
+        assert_eq!(ctx.decode_base64_argument("blob").unwrap(), b"hello");
+
+        for name in ["bad", "num", "missing"] {
+            match ctx.decode_base64_argument(name) {
+                Err(McpToolCallError::InvalidParams(msg)) => assert!(msg.contains(name), "{}", msg),
+                _ => panic!("{} must be InvalidParams", name),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_arguments_yields_typed_input_or_invalid_params() {
+        let ctx = test_ctx_with(serde_json::json!({"a": 2, "b": 3}));
+        let out = RawSumTool.execute(ctx).await.unwrap();
+        assert_eq!(out.structured_json, r#"{"sum":5}"#);
+
+        let ctx = test_ctx_with(serde_json::json!({"a": "two", "b": 3}));
+        match RawSumTool.execute(ctx).await {
+            Err(McpToolCallError::InvalidParams(msg)) => {
+                assert!(msg.starts_with("Can not deserialize input data"), "{}", msg);
+                assert!(!msg.contains(r#"{"a":"two""#), "{}", msg);
+            }
+            _ => panic!("malformed arguments must be InvalidParams"),
+        }
+    }
+
+    #[test]
+    fn parse_arguments_keeps_numbers_exact() {
+        #[derive(serde::Deserialize)]
+        struct Numbers {
+            big: u128,
+            precise: f64,
+            sci: f64,
+        }
+
+        let ctx = ToolCallContext::new(
+            "test".to_string(),
+            false,
+            r#"{"big":123456789012345678901234567890,"precise":0.1234567890123456789,"sci":1.5e-7}"#
+                .to_string(),
+            Arc::new(McpElicitations::new()),
+            Arc::new(McpSessions::new()),
+        );
+
+        let numbers: Numbers = ctx.parse_arguments().unwrap();
+        assert_eq!(numbers.big, 123456789012345678901234567890u128);
+        assert_eq!(numbers.precise, 0.1234567890123456789f64);
+        assert_eq!(numbers.sci, 1.5e-7);
+    }
 }
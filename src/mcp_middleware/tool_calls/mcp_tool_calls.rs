@@ -1,28 +1,78 @@
-use my_ai_agent::my_json;
-
 use super::*;
 use crate::mcp_middleware::ToolCallContext;
-use std::{collections::BTreeMap, sync::Arc};
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// `inputSchema` / `outputSchema` of one tool, already serialized.
+pub struct ToolSchema {
+    pub input: String,
+    pub output: String,
+}
 
 pub struct ToolCallSchemaData {
     pub mcp: Arc<dyn McpToolCallAbstract + Send + Sync + 'static>,
-    pub input: my_json::json_writer::JsonObjectWriter,
-    pub output: my_json::json_writer::JsonObjectWriter,
+    pub schema: Arc<ToolSchema>,
+}
+
+/// `list` as a client that negotiated `protocol_version` sees it: the
+/// output schema of every tool with one for that version replaced. See
+/// [`McpToolCallAbstract::get_output_params_for_version`].
+pub(crate) async fn tools_list_for_version(
+    mut list: Vec<ToolCallSchemaData>,
+    protocol_version: &str,
+) -> Vec<ToolCallSchemaData> {
+    for tool in list.iter_mut() {
+        if let Some(output) = tool.mcp.get_output_params_for_version(protocol_version).await {
+            tool.schema = Arc::new(ToolSchema {
+                input: tool.schema.input.clone(),
+                output: output.build(),
+            });
+        }
+    }
+
+    list
+}
+
+/// Hash of everything `tools/list` shows of `list` — whatever
+/// `compile_tool_calls` writes of a tool: equal hashes mean a client
+/// would see the same list.
+pub(crate) fn tools_list_hash(list: &[ToolCallSchemaData]) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for tool in list {
+        tool.mcp.get_fn_name().hash(&mut hasher);
+        tool.mcp.get_description().hash(&mut hasher);
+        tool.schema.input.hash(&mut hasher);
+        tool.schema.output.hash(&mut hasher);
+        tool.mcp.supports_streaming().hash(&mut hasher);
+    }
+    hasher.finish()
 }
 
 pub struct McpToolCalls {
     tool_calls: BTreeMap<String, Arc<dyn McpToolCallAbstract + Send + Sync + 'static>>,
+    /// Schemas are derived from the tools' Rust types, so they can not
+    /// change while a tool stays registered. Built on the first
+    /// `tools/list` and reused until the tool is replaced.
+    schemas: Mutex<HashMap<String, Arc<ToolSchema>>>,
 }
 
 impl McpToolCalls {
     pub fn new() -> Self {
         Self {
             tool_calls: BTreeMap::new(),
+            schemas: Mutex::new(HashMap::new()),
         }
     }
 
     pub fn add(&mut self, executor: Arc<dyn McpToolCallAbstract + Send + Sync + 'static>) {
         let name = executor.get_fn_name().to_string();
+        // A re-registered name may come with different types.
+        self.schemas.lock().remove(&name);
         self.tool_calls.insert(name, executor);
     }
 
@@ -36,33 +86,162 @@ impl McpToolCalls {
     pub async fn execute(
         &self,
         fn_name: &str,
-        input: &str,
         ctx: ToolCallContext,
-    ) -> Result<ExecutedToolCall, String> {
+    ) -> Result<ExecutedToolCall, McpToolCallError> {
         if let Some(executor) = self.tool_calls.get(fn_name) {
-            return executor.execute(input, ctx).await;
+            return executor.execute(ctx).await;
         }
 
-        Err(format!("Tool call with name {} is not found", fn_name))
+        Err(McpToolCallError::InvalidParams(format!(
+            "Tool call with name {} is not found",
+            fn_name
+        )))
     }
 
     pub async fn get_list(&self) -> Vec<ToolCallSchemaData> {
         let mut result = Vec::with_capacity(self.tool_calls.len());
 
-        for tool_call in self.tool_calls.values() {
-            let input = tool_call.get_input_params().await;
-            let output = tool_call.get_output_params().await;
+        for (name, tool_call) in self.tool_calls.iter() {
             result.push(ToolCallSchemaData {
                 mcp: tool_call.clone(),
-                input,
-                output,
+                schema: self.schema_of(name, tool_call).await,
             });
         }
 
         result
     }
 
+    /// The schemas of the tool named `fn_name`, from the same cache
+    /// `tools/list` uses.
+    pub async fn get_schema(&self, fn_name: &str) -> Option<Arc<ToolSchema>> {
+        let tool_call = self.tool_calls.get(fn_name)?;
+        Some(self.schema_of(fn_name, tool_call).await)
+    }
+
+    async fn schema_of(
+        &self,
+        name: &str,
+        tool_call: &Arc<dyn McpToolCallAbstract + Send + Sync + 'static>,
+    ) -> Arc<ToolSchema> {
+        let cached = self.schemas.lock().get(name).cloned();
+        if let Some(schema) = cached {
+            return schema;
+        }
+
+        let schema = Arc::new(ToolSchema {
+            input: tool_call.get_input_params().await.build(),
+            output: tool_call.get_output_params().await.build(),
+        });
+        self.schemas.lock().insert(name.to_string(), schema.clone());
+        schema
+    }
+
     pub fn has_tools(&self) -> bool {
         !self.tool_calls.is_empty()
     }
+
+    pub fn count(&self) -> usize {
+        self.tool_calls.len()
+    }
+
+    /// Whether a tool with that name was registered.
+    pub fn remove(&mut self, fn_name: &str) -> bool {
+        self.schemas.lock().remove(fn_name);
+        self.tool_calls.remove(fn_name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.tool_calls.clear();
+        self.schemas.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use my_ai_agent::my_json::json_writer::JsonObjectWriter;
+    use my_http_server::async_trait;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingTool {
+        schema_builds: AtomicUsize,
+        streaming: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl McpToolCallAbstract for CountingTool {
+        async fn execute(
+            &self,
+            _ctx: ToolCallContext,
+        ) -> Result<ExecutedToolCall, McpToolCallError> {
+            Err(McpToolCallError::Failed("not used".to_string()))
+        }
+
+        fn get_fn_name(&self) -> &str {
+            "counting"
+        }
+
+        fn get_description(&self) -> &str {
+            "Counts schema builds"
+        }
+
+        async fn get_input_params(&self) -> JsonObjectWriter {
+            self.schema_builds.fetch_add(1, Ordering::SeqCst);
+            JsonObjectWriter::new().write("type", "object")
+        }
+
+        async fn get_output_params(&self) -> JsonObjectWriter {
+            JsonObjectWriter::new().write("type", "object")
+        }
+
+        fn supports_streaming(&self) -> bool {
+            self.streaming
+        }
+    }
+
+    #[tokio::test]
+    async fn schemas_are_built_once_across_list_calls() {
+        let tool = Arc::new(CountingTool::default());
+        let mut tool_calls = McpToolCalls::new();
+        tool_calls.add(tool.clone());
+
+        for _ in 0..10 {
+            let list = tool_calls.get_list().await;
+            assert_eq!(list.len(), 1);
+            assert_eq!(list[0].schema.input, r#"{"type":"object"}"#);
+        }
+
+        assert_eq!(tool.schema_builds.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn re_registering_a_tool_drops_its_cached_schema() {
+        let tool = Arc::new(CountingTool::default());
+        let mut tool_calls = McpToolCalls::new();
+        tool_calls.add(tool.clone());
+        tool_calls.get_list().await;
+
+        tool_calls.add(tool.clone());
+        tool_calls.get_list().await;
+
+        assert_eq!(tool.schema_builds.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn the_list_hash_covers_the_streaming_hint() {
+        let mut tool_calls = McpToolCalls::new();
+        tool_calls.add(Arc::new(CountingTool::default()));
+        let without_hint = tools_list_hash(&tool_calls.get_list().await);
+
+        tool_calls.add(Arc::new(CountingTool {
+            streaming: true,
+            ..Default::default()
+        }));
+        let with_hint = tools_list_hash(&tool_calls.get_list().await);
+
+        assert_ne!(without_hint, with_hint);
+    }
 }
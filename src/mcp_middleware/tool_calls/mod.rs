@@ -6,3 +6,5 @@ mod tool_call_executor;
 pub use tool_call_executor::*;
 mod tool_call_context;
 pub use tool_call_context::*;
+mod argument_validation;
+pub use argument_validation::*;
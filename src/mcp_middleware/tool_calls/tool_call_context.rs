@@ -1,8 +1,11 @@
 use std::sync::Arc;
 use std::time::Duration;
 
+use base64::Engine;
+use serde::de::DeserializeOwned;
+
 use crate::mcp_middleware::{
-    ElicitationResponse, McpElicitations, McpSessions, McpSocketUpdateEvent,
+    ElicitationResponse, McpElicitations, McpSessions, McpSocketUpdateEvent, McpToolCallError,
 };
 
 /// Per-call context handed to tools that opt in to context-aware
@@ -10,13 +13,109 @@ use crate::mcp_middleware::{
 /// to the client for things the model can't or shouldn't see — most
 /// notably MCP elicitation (`elicitation/create`).
 pub struct ToolCallContext {
+    /// Empty in stateless mode, where there is no session; a session
+    /// header is never empty.
     pub session_id: String,
     pub supports_elicitation: bool,
+    /// `params.arguments` of the call; `{}` when the client sent none.
+    pub(crate) arguments: serde_json::Value,
+    /// The same arguments exactly as the client wrote them. Typed
+    /// parsing reads these: a `Value` keeps big integers and long
+    /// decimals as `f64`, the text keeps every digit.
+    pub(crate) raw_arguments: String,
     pub(crate) elicitations: Arc<McpElicitations>,
     pub(crate) sessions: Arc<McpSessions>,
+    /// Negotiated at `initialize`. See [`Self::protocol_version`].
+    pub(crate) protocol_version: String,
 }
 
 impl ToolCallContext {
+    /// `raw_arguments` is the JSON text of `params.arguments`.
+    pub(crate) fn new(
+        session_id: String,
+        supports_elicitation: bool,
+        raw_arguments: String,
+        elicitations: Arc<McpElicitations>,
+        sessions: Arc<McpSessions>,
+    ) -> Self {
+        let arguments = serde_json::from_str(raw_arguments.as_str())
+            .unwrap_or_else(|_| serde_json::Value::Object(Default::default()));
+
+        Self {
+            session_id,
+            supports_elicitation,
+            arguments,
+            raw_arguments,
+            elicitations,
+            sessions,
+            protocol_version: crate::mcp_middleware::latest_protocol_version().to_string(),
+        }
+    }
+
+    pub(crate) fn with_protocol_version(mut self, protocol_version: String) -> Self {
+        self.protocol_version = protocol_version;
+        self
+    }
+
+    /// The protocol version the session negotiated at `initialize` — the
+    /// latest supported one in stateless mode. A tool with
+    /// version-conditioned output (see
+    /// [`super::McpToolCallAbstract::get_output_params_for_version`])
+    /// shapes its result by it.
+    pub fn protocol_version(&self) -> &str {
+        self.protocol_version.as_str()
+    }
+
+    /// The arguments exactly as the client sent them, as JSON text.
+    pub fn raw_arguments(&self) -> &str {
+        self.raw_arguments.as_str()
+    }
+
+    /// The raw `arguments` of the call.
+    pub fn arguments(&self) -> &serde_json::Value {
+        &self.arguments
+    }
+
+    /// Deserializes the call's `arguments` into `T`. Arguments that do
+    /// not fit are [`McpToolCallError::InvalidParams`], which the
+    /// middleware answers with the standard `-32602` error — the same
+    /// one the typed executors produce, so a tool never words its own.
+    /// The message names what did not fit but does not repeat the
+    /// arguments, which may carry secrets.
+    ///
+    /// Numbers are read from the arguments as sent, so a `u128`, an
+    /// `f64` with many decimals or one in scientific notation gets the
+    /// value the client wrote.
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> Result<T, McpToolCallError> {
+        serde_json::from_str::<T>(self.raw_arguments.as_str()).map_err(|err| {
+            McpToolCallError::InvalidParams(format!("Can not deserialize input data. Msg: {}", err))
+        })
+    }
+
+    /// Decodes the base64 string argument `name` into bytes. A missing
+    /// or non-string argument, or one that is not valid standard base64,
+    /// is [`McpToolCallError::InvalidParams`] — answered with `-32602`
+    /// before the tool looks at the data.
+    pub fn decode_base64_argument(&self, name: &str) -> Result<Vec<u8>, McpToolCallError> {
+        let value = self
+            .arguments
+            .get(name)
+            .ok_or_else(|| McpToolCallError::InvalidParams(format!("Missing argument {}", name)))?;
+
+        let encoded = value.as_str().ok_or_else(|| {
+            McpToolCallError::InvalidParams(format!("Argument {} must be a base64 string", name))
+        })?;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|err| {
+                McpToolCallError::InvalidParams(format!(
+                    "Argument {} is not valid base64. Msg: {}",
+                    name, err
+                ))
+            })
+    }
+
     /// Server→client `elicitation/create` request. Asks the connected
     /// client to prompt the user for input matching `requested_schema`.
     ///
@@ -50,7 +149,12 @@ impl ToolCallContext {
             requested_schema: requested_schema_str,
         };
 
-        if sender.send(event).await.is_err() {
+        if self
+            .sessions
+            .deliver(&self.session_id, &sender, event)
+            .await
+            .is_err()
+        {
             self.elicitations.cancel(id);
             return Err("Failed to deliver elicitation/create — SSE channel closed".to_string());
         }
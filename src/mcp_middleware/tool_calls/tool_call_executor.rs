@@ -4,11 +4,18 @@ use my_ai_agent::{json_schema::*, my_json};
 use serde::{Serialize, de::DeserializeOwned};
 
 use crate::mcp_middleware::{
-    ExecutedToolCall, McpToolCallAbstract, McpToolCallExWithInstruction,
-    McpToolCallWithInstruction, ToolCallContext,
+    ExecutedToolCall, McpToolCallAbstract, McpToolCallError, McpToolCallExWithInstruction,
+    McpToolCallWithInstruction, ToolCallContext, VoidOutput,
 };
 use my_http_server::async_trait;
 
+/// Whether a tool with output type `OutputData` returns nothing to
+/// show. Told by the type, not by the JSON: other types serialize to
+/// `null` as well.
+fn is_void_output<OutputData: 'static>() -> bool {
+    std::any::TypeId::of::<OutputData>() == std::any::TypeId::of::<VoidOutput>()
+}
+
 pub struct ToolCallExecutor<InputData, OutputData>
 where
     InputData: JsonTypeDescription + Sized + Send + Sync + 'static,
@@ -41,31 +48,51 @@ where
         OutputData::get_description(false, None, true).await
     }
 
-    async fn execute(
-        &self,
-        input: &str,
-        _ctx: ToolCallContext,
-    ) -> Result<ExecutedToolCall, String> {
-        let parse_result: Result<InputData, serde_json::Error> = serde_json::from_str(input);
-
-        let output = match parse_result {
+    async fn execute(&self, ctx: ToolCallContext) -> Result<ExecutedToolCall, McpToolCallError> {
+        let output = match ctx.parse_arguments::<InputData>() {
             Ok(input) => {
                 self.holder
                     .execute_tool_call_with_instruction(input)
-                    .await?
+                    .await
+                    .map_err(McpToolCallError::Failed)?
             }
             Err(err) => {
-                let msg = format!("Can not deserialize input data {}. Msg: {:?}", input, err);
-                println!("{}", msg);
-                return Err(msg);
+                eprintln!("Tool call {} got invalid arguments. Err: {:?}", self.fn_name, err);
+                return Err(err);
             }
         };
 
         Ok(ExecutedToolCall {
             structured_json: serde_json::to_string(&output.data).unwrap(),
             instruction: output.instruction,
+            content_meta: output.content_meta,
+            citations: output.citations,
+            cached: output.cached,
+            content: output.content,
+            void_output: is_void_output::<OutputData>(),
         })
     }
+
+    async fn dry_run(&self, ctx: &ToolCallContext) -> Option<Result<(), McpToolCallError>> {
+        if !self.holder.supports_dry_run() {
+            return None;
+        }
+
+        Some(ctx.parse_arguments::<InputData>().map(|_| ()))
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.holder.supports_streaming()
+    }
+
+    async fn get_output_params_for_version(
+        &self,
+        protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        self.holder
+            .get_output_params_for_version(protocol_version)
+            .await
+    }
 }
 
 /// Context-aware executor — used by [`super::McpMiddleware::register_tool_call_with_context`].
@@ -102,29 +129,49 @@ where
         OutputData::get_description(false, None, true).await
     }
 
-    async fn execute(
-        &self,
-        input: &str,
-        ctx: ToolCallContext,
-    ) -> Result<ExecutedToolCall, String> {
-        let parse_result: Result<InputData, serde_json::Error> = serde_json::from_str(input);
-
-        let output = match parse_result {
+    async fn execute(&self, ctx: ToolCallContext) -> Result<ExecutedToolCall, McpToolCallError> {
+        let output = match ctx.parse_arguments::<InputData>() {
             Ok(input) => {
                 self.holder
                     .execute_tool_call_with_instruction(input, &ctx)
-                    .await?
+                    .await
+                    .map_err(McpToolCallError::Failed)?
             }
             Err(err) => {
-                let msg = format!("Can not deserialize input data {}. Msg: {:?}", input, err);
-                println!("{}", msg);
-                return Err(msg);
+                eprintln!("Tool call {} got invalid arguments. Err: {:?}", self.fn_name, err);
+                return Err(err);
             }
         };
 
         Ok(ExecutedToolCall {
             structured_json: serde_json::to_string(&output.data).unwrap(),
             instruction: output.instruction,
+            content_meta: output.content_meta,
+            citations: output.citations,
+            cached: output.cached,
+            content: output.content,
+            void_output: is_void_output::<OutputData>(),
         })
     }
+
+    async fn dry_run(&self, ctx: &ToolCallContext) -> Option<Result<(), McpToolCallError>> {
+        if !self.holder.supports_dry_run() {
+            return None;
+        }
+
+        Some(ctx.parse_arguments::<InputData>().map(|_| ()))
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.holder.supports_streaming()
+    }
+
+    async fn get_output_params_for_version(
+        &self,
+        protocol_version: &str,
+    ) -> Option<my_json::json_writer::JsonObjectWriter> {
+        self.holder
+            .get_output_params_for_version(protocol_version)
+            .await
+    }
 }
@@ -0,0 +1,61 @@
+//! The pre-parse request filter gets the live `HttpContext`, which only
+//! hyper can produce — so it is exercised over a real HTTP server.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{post, send_raw, serve, status_code};
+use mcp_server_middleware::McpMiddleware;
+use mcp_server_middleware::McpRequestFilter;
+use mcp_server_middleware::my_http_server::{
+    HttpContext, HttpFailResult, HttpOkResult, HttpOutput, async_trait,
+};
+use parking_lot::Mutex;
+
+/// Blocks every body that contains a banned token and remembers the
+/// bodies it let through.
+#[derive(Default)]
+struct BannedTokenFilter {
+    passed: Mutex<Vec<String>>,
+}
+
+#[async_trait::async_trait]
+impl McpRequestFilter for BannedTokenFilter {
+    async fn filter(
+        &self,
+        _ctx: &HttpContext,
+        body: &[u8],
+    ) -> Option<Result<HttpOkResult, HttpFailResult>> {
+        let body = String::from_utf8_lossy(body);
+
+        if body.contains("DROP TABLE") {
+            return Some(
+                HttpOutput::from_builder()
+                    .set_status_code(403)
+                    .into_ok_result(false),
+            );
+        }
+
+        self.passed.lock().push(body.to_string());
+        None
+    }
+}
+
+#[tokio::test]
+async fn filter_blocks_bodies_with_a_banned_token() {
+    let filter = Arc::new(BannedTokenFilter::default());
+    let mut mcp = McpMiddleware::new("/mcp", "test-server", "0.0.1", "test instructions");
+    mcp.register_request_filter(filter.clone());
+    let addr = serve(mcp).await;
+
+    // Not even valid JSON-RPC: the filter runs before any parsing.
+    let head = send_raw(addr, post("/mcp", "DROP TABLE users; --", None)).await;
+    assert_eq!(status_code(&head), 403);
+    assert!(filter.passed.lock().is_empty());
+
+    let body = r#"{"jsonrpc":"2.0","method":"tools/list","id":1}"#;
+    let head = send_raw(addr, post("/mcp", body, Some("client-owned-id"))).await;
+    assert_eq!(status_code(&head), 200);
+    assert_eq!(filter.passed.lock().clone(), vec![body.to_string()]);
+}
@@ -5,21 +5,17 @@
 //! be produced by hyper — so unlike the rest of the middleware tests
 //! this one drives an actual `MyHttpServer` over a loopback socket.
 
-use std::collections::HashMap;
+mod common;
+
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
 
-use mcp_server_middleware::my_http_server::{
-    HttpContext, HttpRequestHeaders, MyHttpServer, async_trait,
-};
+use common::{post, send_raw, serve, session_header, status_code};
+use mcp_server_middleware::my_http_server::{HttpContext, HttpRequestHeaders, async_trait};
 use mcp_server_middleware::{
     McpConnectionInfo, McpInputData, McpInputPayload, McpMiddleware, McpSession,
 };
 use parking_lot::Mutex;
-use rust_extensions::{ApplicationStates, Logger};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
 
 const INITIALIZE_BODY: &str = r#"{"jsonrpc":"2.0","method":"initialize","id":1,"params":{"protocolVersion":"2025-06-18","capabilities":{},"clientInfo":{"name":"claude-code","version":"0.5.0"}}}"#;
 
@@ -87,151 +83,13 @@ impl McpConnectionInfo for Recorder {
     }
 }
 
-struct TestAppStates;
-
-impl ApplicationStates for TestAppStates {
-    fn is_initialized(&self) -> bool {
-        true
-    }
-
-    fn is_shutting_down(&self) -> bool {
-        false
-    }
-}
-
-struct TestLogger;
-
-impl Logger for TestLogger {
-    fn write_info(&self, _process: String, _message: String, _ctx: Option<HashMap<String, String>>) {
-    }
-
-    fn write_warning(
-        &self,
-        _process: String,
-        _message: String,
-        _ctx: Option<HashMap<String, String>>,
-    ) {
-    }
-
-    fn write_error(
-        &self,
-        _process: String,
-        _message: String,
-        _ctx: Option<HashMap<String, String>>,
-    ) {
-    }
-
-    fn write_fatal_error(
-        &self,
-        _process: String,
-        _message: String,
-        _ctx: Option<HashMap<String, String>>,
-    ) {
-    }
-
-    fn write_debug_info(
-        &self,
-        _process: String,
-        _message: String,
-        _ctx: Option<HashMap<String, String>>,
-    ) {
-    }
-}
-
 /// Starts the middleware behind a real HTTP server on a free loopback
 /// port and returns the address to talk to.
 async fn start_server(recorder: Arc<Recorder>) -> SocketAddr {
     let mut mcp = McpMiddleware::new("/mcp", "test-server", "0.0.1", "test instructions");
     mcp.register_connection_info(recorder);
 
-    // Take a port from the OS, then hand it over to the http server.
-    let addr = {
-        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
-        listener.local_addr().unwrap()
-    };
-
-    let mut server = MyHttpServer::new(addr);
-    server.add_middleware(Arc::new(mcp));
-    server.start(Arc::new(TestAppStates), Arc::new(TestLogger));
-
-    for _ in 0..100 {
-        if TcpStream::connect(addr).await.is_ok() {
-            return addr;
-        }
-        tokio::time::sleep(Duration::from_millis(20)).await;
-    }
-
-    panic!("http server did not start listening on {}", addr);
-}
-
-/// Writes a raw request and returns the response head (everything up to
-/// the empty line). Bodies are streamed and the connection is kept
-/// alive, so reading further would just block.
-async fn send_raw(addr: SocketAddr, request: String) -> String {
-    let mut stream = TcpStream::connect(addr).await.unwrap();
-    stream.write_all(request.as_bytes()).await.unwrap();
-    stream.flush().await.unwrap();
-
-    let mut response = Vec::new();
-    let mut buf = [0u8; 1024];
-
-    loop {
-        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
-            .await
-            .expect("timed out reading the response")
-            .expect("failed to read the response");
-
-        if read == 0 {
-            break;
-        }
-
-        response.extend_from_slice(&buf[..read]);
-
-        if let Some(pos) = find_head_end(&response) {
-            response.truncate(pos);
-            break;
-        }
-    }
-
-    String::from_utf8_lossy(&response).to_string()
-}
-
-fn find_head_end(src: &[u8]) -> Option<usize> {
-    src.windows(4).position(|w| w == b"\r\n\r\n")
-}
-
-fn status_code(head: &str) -> u16 {
-    head.lines()
-        .next()
-        .and_then(|line| line.split_whitespace().nth(1))
-        .and_then(|code| code.parse().ok())
-        .expect("no status code in the response")
-}
-
-fn session_header(head: &str) -> Option<String> {
-    head.lines().find_map(|line| {
-        let (name, value) = line.split_once(':')?;
-        if name.eq_ignore_ascii_case("mcp-session-id") {
-            Some(value.trim().to_string())
-        } else {
-            None
-        }
-    })
-}
-
-fn post(path: &str, body: &str, session_id: Option<&str>) -> String {
-    let session_header = match session_id {
-        Some(session_id) => format!("mcp-session-id: {}\r\n", session_id),
-        None => String::new(),
-    };
-
-    format!(
-        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nx-test-client: web-console\r\n{}Content-Length: {}\r\n\r\n{}",
-        path,
-        session_header,
-        body.len(),
-        body
-    )
+    serve(mcp).await
 }
 
 #[tokio::test]